@@ -1,3 +1,118 @@
+use super::sgr::SgrAttribute;
+
+/// One `;`-separated CSI parameter with any `:`-separated sub-parameters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsiParam {
+    pub value: Option<u16>,             // None when the parameter was omitted
+    pub subparams: Vec<Option<u16>>,    // Values following each ':'
+}
+
+impl CsiParam {
+    fn parse(bytes: &[u8]) -> Self {
+        let mut parts = bytes.split(|&b| b == b':').map(parse_number);
+        let value = parts.next().flatten();
+        Self { value, subparams: parts.collect() }
+    }
+
+    pub fn repr(&self) -> String {
+        let mut repr = self.value.map(|v| v.to_string()).unwrap_or_default();
+        for sub in &self.subparams {
+            repr.push(':');
+            if let Some(sub) = sub {
+                repr.push_str(&sub.to_string());
+            }
+        }
+        repr
+    }
+}
+
+/// A control sequence split per ECMA-48: CSI P...P I...I F
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsiSequence {
+    pub private_marker: Option<char>,   // Leading '<', '=', '>' or '?'
+    pub params: Vec<CsiParam>,
+    pub intermediates: String,          // Bytes 0x20-0x2F
+    pub final_byte: char,               // Byte 0x40-0x7E
+}
+
+impl CsiSequence {
+    /// Split raw parameter bytes (0x30-0x3F) into the private marker and parameters
+    pub fn new(param_bytes: &[u8], intermediates: &[u8], final_byte: u8) -> Self {
+        let (private_marker, param_bytes) = match param_bytes.first() {
+            Some(&marker @ (b'<' | b'=' | b'>' | b'?')) => (Some(marker as char), &param_bytes[1..]),
+            _ => (None, param_bytes),
+        };
+
+        let params = if param_bytes.is_empty() {
+            Vec::new()
+        } else {
+            param_bytes.split(|&b| b == b';').map(CsiParam::parse).collect()
+        };
+
+        Self {
+            private_marker,
+            params,
+            intermediates: String::from_utf8_lossy(intermediates).to_string(),
+            final_byte: final_byte as char,
+        }
+    }
+
+    /// Main value of a parameter, None when omitted
+    pub fn param(&self, index: usize) -> Option<u16> {
+        self.params.get(index).and_then(|p| p.value)
+    }
+
+    /// Main value of a parameter, with omitted or zero values replaced by a default
+    pub fn param_or(&self, index: usize, default: u16) -> u16 {
+        match self.param(index) {
+            Some(0) | None => default,
+            Some(value) => value,
+        }
+    }
+
+    /// Main values of all parameters, omitted ones as 0
+    pub fn values(&self) -> Vec<u16> {
+        self.params.iter().map(|p| p.value.unwrap_or(0)).collect()
+    }
+
+    pub fn has_subparams(&self) -> bool {
+        self.params.iter().any(|p| !p.subparams.is_empty())
+    }
+
+    /// Everything between `ESC [` and the final byte
+    pub fn body(&self) -> String {
+        let mut body = String::new();
+        if let Some(marker) = self.private_marker {
+            body.push(marker);
+        }
+        body.push_str(&self.params.iter()
+            .map(|p| p.repr())
+            .collect::<Vec<String>>()
+            .join(";"));
+        body.push_str(&self.intermediates);
+        body
+    }
+
+    pub fn escape_repr(&self) -> String {
+        format!("\\x1b[{}{}", self.body(), self.final_byte)
+    }
+}
+
+// Parse a decimal parameter, saturating instead of overflowing
+fn parse_number(bytes: &[u8]) -> Option<u16> {
+    if bytes.is_empty() {
+        return None;
+    }
+    let mut value: u16 = 0;
+    for &b in bytes {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        value = value.saturating_mul(10).saturating_add((b - b'0') as u16);
+    }
+    Some(value)
+}
+
 #[derive(Debug, Clone)]
 pub enum CSI {
     // Cursor movement
@@ -11,23 +126,42 @@ pub enum CSI {
     CursorToColumn(u32),             // ESC [ G
     CursorNextLine(u32),             // ESC [ E
     CursorPreviousLine(u32),         // ESC [ F
+    CursorToRow(u32),                // ESC [ d
+    CursorForwardTab(u32),           // ESC [ I
+    CursorBackwardTab(u32),          // ESC [ Z
     
     // Erase functions
     EraseInDisplay(u8),
     EraseInLine(u8),
+    EraseCharacters(u32),            // ESC [ X
+    
+    // Editing
+    InsertCharacters(u32),           // ESC [ @
+    DeleteCharacters(u32),           // ESC [ P
+    InsertLines(u32),                // ESC [ L
+    DeleteLines(u32),                // ESC [ M
+    RepeatCharacter(u32),            // ESC [ b
+    
+    // Tab stops
+    TabSet,                          // ESC H
+    TabClear(u8),                    // ESC [ g
     
     // Graphics and attributes
-    SetGraphicsMode(Vec<u8>),
+    SetGraphicsMode(Vec<SgrAttribute>),
     SetForegroundColor(u8, u8, u8),  // RGB
     SetBackgroundColor(u8, u8, u8),  // RGB
     SetForegroundColor256(u8),       // 8-bit/256 color
     SetBackgroundColor256(u8),       // 8-bit/256 color
     ResetAttributes,
     
-    // Screen modes
+    // ANSI modes (SM/RM)
     SetMode(Vec<u16>),               // ESC [ h
     ResetMode(Vec<u16>),             // ESC [ l
     
+    // DEC private modes (DECSET/DECRST)
+    SetPrivateMode(Vec<u16>),        // ESC [ ? h
+    ResetPrivateMode(Vec<u16>),      // ESC [ ? l
+    
     // Terminal modes
     ApplicationKeypadMode,           // Was ESC =
     NumericKeypadMode,               // Was ESC >
@@ -39,6 +173,10 @@ pub enum CSI {
     // Scrolling
     ScrollUp(u32),                   // ESC [ S
     ScrollDown(u32),                 // ESC [ T
+    SetScrollRegion(u32, Option<u32>), // ESC [ top ; bottom r
+    Index,                           // ESC D
+    NextLine,                        // ESC E
+    ReverseIndex,                    // ESC M
     
     // Window manipulation
     WindowManipulation(Vec<u16>),    // ESC [ t
@@ -47,11 +185,86 @@ pub enum CSI {
     DeviceStatusReport,              // ESC [ 6 n
     CursorPositionReport,            // ESC [ ? 6 n
     
+    // Cursor style and terminal reset
+    SetCursorStyle(u16),             // ESC [ n SP q (DECSCUSR)
+    SoftReset,                       // ESC [ ! p (DECSTR)
+    FullReset,                       // ESC c (RIS)
+    
+    // Keyboard protocols
+    SetModifyKeys(u16, Option<u16>), // ESC [ > resource ; value m (XTMODKEYS)
+    PushKeyboardFlags(u16),          // ESC [ > flags u
+    PopKeyboardFlags(u16),           // ESC [ < n u
+    SetKeyboardFlags(u16, u16),      // ESC [ = flags ; mode u
+    QueryKeyboardFlags,              // ESC [ ? u
+    
+    // Well-formed CSI commands without a dedicated variant
+    Unrecognized(CsiSequence),
+    
     // Other CSI commands
     Unknown(String),
 }
 
 impl CSI {
+    /// Standard mnemonic of the command, e.g. "CUP" or "SGR"
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            CSI::CursorUp(_) => "CUU",
+            CSI::CursorDown(_) => "CUD",
+            CSI::CursorForward(_) => "CUF",
+            CSI::CursorBackward(_) => "CUB",
+            CSI::CursorPosition(..) => "CUP",
+            CSI::CursorSavePosition => "DECSC",
+            CSI::CursorRestorePosition => "DECRC",
+            CSI::CursorToColumn(_) => "CHA",
+            CSI::CursorNextLine(_) => "CNL",
+            CSI::CursorPreviousLine(_) => "CPL",
+            CSI::CursorToRow(_) => "VPA",
+            CSI::CursorForwardTab(_) => "CHT",
+            CSI::CursorBackwardTab(_) => "CBT",
+            CSI::EraseInDisplay(_) => "ED",
+            CSI::EraseInLine(_) => "EL",
+            CSI::EraseCharacters(_) => "ECH",
+            CSI::InsertCharacters(_) => "ICH",
+            CSI::DeleteCharacters(_) => "DCH",
+            CSI::InsertLines(_) => "IL",
+            CSI::DeleteLines(_) => "DL",
+            CSI::RepeatCharacter(_) => "REP",
+            CSI::TabSet => "HTS",
+            CSI::TabClear(_) => "TBC",
+            CSI::SetGraphicsMode(_) |
+            CSI::SetForegroundColor(..) |
+            CSI::SetBackgroundColor(..) |
+            CSI::SetForegroundColor256(_) |
+            CSI::SetBackgroundColor256(_) |
+            CSI::ResetAttributes => "SGR",
+            CSI::SetMode(_) => "SM",
+            CSI::ResetMode(_) => "RM",
+            CSI::SetPrivateMode(_) => "DECSET",
+            CSI::ResetPrivateMode(_) => "DECRST",
+            CSI::ApplicationKeypadMode => "DECKPAM",
+            CSI::NumericKeypadMode => "DECKPNM",
+            CSI::SetG0SpecialChars | CSI::SetG0NormalChars => "SCS",
+            CSI::ScrollUp(_) => "SU",
+            CSI::ScrollDown(_) => "SD",
+            CSI::SetScrollRegion(..) => "DECSTBM",
+            CSI::Index => "IND",
+            CSI::NextLine => "NEL",
+            CSI::ReverseIndex => "RI",
+            CSI::WindowManipulation(_) => "XTWINOPS",
+            CSI::DeviceStatusReport => "DSR",
+            CSI::CursorPositionReport => "DECXCPR",
+            CSI::SetCursorStyle(_) => "DECSCUSR",
+            CSI::SoftReset => "DECSTR",
+            CSI::FullReset => "RIS",
+            CSI::SetModifyKeys(..) => "XTMODKEYS",
+            CSI::PushKeyboardFlags(_) |
+            CSI::PopKeyboardFlags(_) |
+            CSI::SetKeyboardFlags(..) |
+            CSI::QueryKeyboardFlags => "KITTYKB",
+            CSI::Unrecognized(_) | CSI::Unknown(_) => "Unknown",
+        }
+    }
+
     pub fn escape_repr(&self) -> String {
         match self {
             CSI::CursorUp(n) => format!("\\x1b[{}A", n),
@@ -64,11 +277,22 @@ impl CSI {
             CSI::CursorToColumn(n) => format!("\\x1b[{}G", n),
             CSI::CursorNextLine(n) => format!("\\x1b[{}E", n),
             CSI::CursorPreviousLine(n) => format!("\\x1b[{}F", n),
+            CSI::CursorToRow(n) => format!("\\x1b[{}d", n),
+            CSI::CursorForwardTab(n) => format!("\\x1b[{}I", n),
+            CSI::CursorBackwardTab(n) => format!("\\x1b[{}Z", n),
             CSI::EraseInDisplay(n) => format!("\\x1b[{}J", n),
             CSI::EraseInLine(n) => format!("\\x1b[{}K", n),
-            CSI::SetGraphicsMode(params) => {
-                let params_str = params.iter()
-                    .map(|p| p.to_string())
+            CSI::EraseCharacters(n) => format!("\\x1b[{}X", n),
+            CSI::InsertCharacters(n) => format!("\\x1b[{}@", n),
+            CSI::DeleteCharacters(n) => format!("\\x1b[{}P", n),
+            CSI::InsertLines(n) => format!("\\x1b[{}L", n),
+            CSI::DeleteLines(n) => format!("\\x1b[{}M", n),
+            CSI::RepeatCharacter(n) => format!("\\x1b[{}b", n),
+            CSI::TabSet => "\\x1bH".to_string(),
+            CSI::TabClear(n) => format!("\\x1b[{}g", n),
+            CSI::SetGraphicsMode(attributes) => {
+                let params_str = attributes.iter()
+                    .map(|a| a.params())
                     .collect::<Vec<String>>()
                     .join(";");
                 format!("\\x1b[{}m", params_str)
@@ -92,12 +316,31 @@ impl CSI {
                     .join(";");
                 format!("\\x1b[{}l", params_str)
             },
+            CSI::SetPrivateMode(params) => {
+                let params_str = params.iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>()
+                    .join(";");
+                format!("\\x1b[?{}h", params_str)
+            },
+            CSI::ResetPrivateMode(params) => {
+                let params_str = params.iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<String>>()
+                    .join(";");
+                format!("\\x1b[?{}l", params_str)
+            },
             CSI::ApplicationKeypadMode => "\\x1b=".to_string(),
             CSI::NumericKeypadMode => "\\x1b>".to_string(),
             CSI::SetG0SpecialChars => "\\x1b(0".to_string(),
             CSI::SetG0NormalChars => "\\x1b(B".to_string(),
             CSI::ScrollUp(n) => format!("\\x1b[{}S", n),
             CSI::ScrollDown(n) => format!("\\x1b[{}T", n),
+            CSI::SetScrollRegion(top, Some(bottom)) => format!("\\x1b[{};{}r", top, bottom),
+            CSI::SetScrollRegion(top, None) => format!("\\x1b[{}r", top),
+            CSI::Index => "\\x1bD".to_string(),
+            CSI::NextLine => "\\x1bE".to_string(),
+            CSI::ReverseIndex => "\\x1bM".to_string(),
             CSI::WindowManipulation(params) => {
                 let params_str = params.iter()
                     .map(|p| p.to_string())
@@ -107,6 +350,16 @@ impl CSI {
             },
             CSI::DeviceStatusReport => "\\x1b[6n".to_string(),
             CSI::CursorPositionReport => "\\x1b[?6n".to_string(),
+            CSI::SetCursorStyle(n) => format!("\\x1b[{} q", n),
+            CSI::SoftReset => "\\x1b[!p".to_string(),
+            CSI::FullReset => "\\x1bc".to_string(),
+            CSI::SetModifyKeys(resource, Some(value)) => format!("\\x1b[>{};{}m", resource, value),
+            CSI::SetModifyKeys(resource, None) => format!("\\x1b[>{}m", resource),
+            CSI::PushKeyboardFlags(flags) => format!("\\x1b[>{}u", flags),
+            CSI::PopKeyboardFlags(n) => format!("\\x1b[<{}u", n),
+            CSI::SetKeyboardFlags(flags, mode) => format!("\\x1b[={};{}u", flags, mode),
+            CSI::QueryKeyboardFlags => "\\x1b[?u".to_string(),
+            CSI::Unrecognized(seq) => seq.escape_repr(),
             CSI::Unknown(seq) => format!("\\x1b[{}", seq),
         }
     }
@@ -123,6 +376,23 @@ impl CSI {
             CSI::CursorToColumn(n) => format!("Move cursor to column {}", n),
             CSI::CursorNextLine(n) => format!("Move cursor to beginning of line {} lines down", n),
             CSI::CursorPreviousLine(n) => format!("Move cursor to beginning of line {} lines up", n),
+            CSI::CursorToRow(n) => format!("Move cursor to row {}", n),
+            CSI::CursorForwardTab(n) => format!("Move cursor forward {} tab stops", n),
+            CSI::CursorBackwardTab(n) => format!("Move cursor backward {} tab stops", n),
+            CSI::EraseCharacters(n) => format!("Erase {} characters", n),
+            CSI::InsertCharacters(n) => format!("Insert {} blank characters", n),
+            CSI::DeleteCharacters(n) => format!("Delete {} characters", n),
+            CSI::InsertLines(n) => format!("Insert {} lines", n),
+            CSI::DeleteLines(n) => format!("Delete {} lines", n),
+            CSI::RepeatCharacter(n) => format!("Repeat preceding character {} times", n),
+            CSI::TabSet => "Set tab stop at cursor column".to_string(),
+            CSI::TabClear(n) => {
+                match n {
+                    0 => "Clear tab stop at cursor column".to_string(),
+                    3 => "Clear all tab stops".to_string(),
+                    _ => format!("Unknown tab clear mode: {}", n),
+                }
+            },
             CSI::EraseInDisplay(n) => {
                 match n {
                     0 => "Erase from cursor to end of screen".to_string(),
@@ -140,44 +410,14 @@ impl CSI {
                     _ => format!("Unknown erase line mode: {}", n),
                 }
             },
-            CSI::SetGraphicsMode(params) => {
-                if params.is_empty() {
+            CSI::SetGraphicsMode(attributes) => {
+                if attributes.is_empty() {
                     return "Reset all attributes".to_string();
                 }
                 
-                let descriptions: Vec<String> = params.iter().map(|&param| {
-                    match param {
-                        0 => "Reset all".to_string(),
-                        1 => "Bold".to_string(),
-                        2 => "Faint".to_string(),
-                        3 => "Italic".to_string(),
-                        4 => "Underline".to_string(),
-                        5 => "Slow blink".to_string(),
-                        6 => "Rapid blink".to_string(),
-                        7 => "Reverse".to_string(),
-                        8 => "Conceal".to_string(),
-                        9 => "Crossed-out".to_string(),
-                        21 => "Double underline".to_string(),
-                        22 => "Normal intensity".to_string(),
-                        23 => "Not italic".to_string(),
-                        24 => "Not underlined".to_string(),
-                        25 => "Not blinking".to_string(),
-                        27 => "Not reversed".to_string(),
-                        28 => "Reveal".to_string(),
-                        29 => "Not crossed out".to_string(),
-                        30..=37 => format!("Foreground color: {}", param - 30),
-                        38 => "Set foreground color".to_string(), // Would need more params to decode fully
-                        39 => "Reset foreground color".to_string(),
-                        40..=47 => format!("Background color: {}", param - 40),
-                        48 => "Set background color".to_string(), // Would need more params to decode fully
-                        49 => "Reset background color".to_string(),
-                        90..=97 => format!("Bright foreground color: {}", param - 90),
-                        100..=107 => format!("Bright background color: {}", param - 100),
-                        _ => format!("Unknown parameter: {}", param),
-                    }
-                }).collect();
+                let descriptions: Vec<String> = attributes.iter().map(|a| a.description()).collect();
                 
-                format!("Set mode: {}", descriptions.join(", "))
+                format!("Set graphics mode: {}", descriptions.join(", "))
             },
             CSI::SetForegroundColor(r, g, b) => format!("Set foreground color to RGB({},{},{})", r, g, b),
             CSI::SetBackgroundColor(r, g, b) => format!("Set background color to RGB({},{},{})", r, g, b),
@@ -185,18 +425,48 @@ impl CSI {
             CSI::SetBackgroundColor256(n) => format!("Set background color to 256-color: {}", n),
             CSI::ResetAttributes => "Reset all attributes".to_string(),
             CSI::SetMode(params) => {
+                let descriptions: Vec<String> = params.iter().map(|&param| {
+                    match param {
+                        2 => "Lock keyboard (KAM)".to_string(),
+                        4 => "Insert mode (IRM)".to_string(),
+                        12 => "Disable local echo (SRM)".to_string(),
+                        20 => "Automatic newline (LNM)".to_string(),
+                        _ => format!("Unknown mode: {}", param),
+                    }
+                }).collect();
+                
+                format!("Set mode: {}", descriptions.join(", "))
+            },
+            CSI::ResetMode(params) => {
+                let descriptions: Vec<String> = params.iter().map(|&param| {
+                    match param {
+                        2 => "Unlock keyboard (KAM)".to_string(),
+                        4 => "Replace mode (IRM)".to_string(),
+                        12 => "Enable local echo (SRM)".to_string(),
+                        20 => "No automatic newline (LNM)".to_string(),
+                        _ => format!("Unknown mode: {}", param),
+                    }
+                }).collect();
+                
+                format!("Reset mode: {}", descriptions.join(", "))
+            },
+            CSI::SetPrivateMode(params) => {
                 let descriptions: Vec<String> = params.iter().map(|&param| {
                     match param {
                         1 => "Application cursor keys".to_string(),
                         2 => "Designate US G0 character set".to_string(),
                         3 => "132 column mode".to_string(),
                         4 => "Smooth scroll".to_string(),
+                        5 => "Reverse video".to_string(),
                         6 => "Origin mode".to_string(),
                         7 => "Auto-wrap mode".to_string(),
+                        8 => "Auto-repeat keys".to_string(),
+                        9 => "Send mouse X/Y on button press".to_string(),
                         12 => "Start blinking cursor".to_string(),
-                        20 => "Automatic newline".to_string(),
                         25 => "Show cursor".to_string(),
                         47 => "Use alternate screen buffer".to_string(),
+                        66 => "Application keypad mode".to_string(),
+                        69 => "Enable left and right margins".to_string(),
                         1000 => "Send mouse X/Y on button press and release".to_string(),
                         1001 => "Use hilite mouse tracking".to_string(),
                         1002 => "Use cell motion mouse tracking".to_string(),
@@ -205,36 +475,53 @@ impl CSI {
                         1005 => "Enable UTF-8 mouse mode".to_string(),
                         1006 => "Enable SGR mouse mode".to_string(),
                         1007 => "Enable alternate scroll mode".to_string(),
+                        1015 => "Enable urxvt mouse mode".to_string(),
+                        1016 => "Enable SGR-Pixels mouse mode".to_string(),
+                        1047 => "Use alternate screen buffer".to_string(),
+                        1048 => "Save cursor as in DECSC".to_string(),
                         1049 => "Save cursor as in DECSC and use alternate screen buffer".to_string(),
-                        _ => format!("Unknown mode: {}", param),
+                        2004 => "Enable bracketed paste mode".to_string(),
+                        2026 => "Begin synchronized output".to_string(),
+                        _ => format!("Unknown private mode: {}", param),
                     }
                 }).collect();
                 
-                format!("Set mode: {}", descriptions.join(", "))
+                format!("Set private mode: {}", descriptions.join(", "))
             },
-            CSI::ResetMode(params) => {
+            CSI::ResetPrivateMode(params) => {
                 let descriptions: Vec<String> = params.iter().map(|&param| {
                     match param {
                         1 => "Normal cursor keys".to_string(),
+                        2 => "VT52 mode".to_string(),
                         3 => "80 column mode".to_string(),
                         4 => "Jump scroll".to_string(),
+                        5 => "Normal video".to_string(),
                         6 => "Normal cursor mode".to_string(),
                         7 => "No auto-wrap mode".to_string(),
+                        8 => "No auto-repeat keys".to_string(),
+                        9 => "Don't send mouse X/Y on button press".to_string(),
                         12 => "Stop blinking cursor".to_string(),
-                        20 => "No automatic newline".to_string(),
                         25 => "Hide cursor".to_string(),
                         47 => "Use normal screen buffer".to_string(),
+                        66 => "Numeric keypad mode".to_string(),
+                        69 => "Disable left and right margins".to_string(),
                         1000..=1003 => "Turn off mouse tracking".to_string(),
                         1004 => "Don't send focus events to tty".to_string(),
                         1005 => "Disable UTF-8 mouse mode".to_string(),
                         1006 => "Disable SGR mouse mode".to_string(),
                         1007 => "Disable alternate scroll mode".to_string(),
+                        1015 => "Disable urxvt mouse mode".to_string(),
+                        1016 => "Disable SGR-Pixels mouse mode".to_string(),
+                        1047 => "Use normal screen buffer".to_string(),
+                        1048 => "Restore cursor as in DECRC".to_string(),
                         1049 => "Use normal screen buffer and restore cursor as in DECRC".to_string(),
-                        _ => format!("Unknown mode: {}", param),
+                        2004 => "Disable bracketed paste mode".to_string(),
+                        2026 => "End synchronized output".to_string(),
+                        _ => format!("Unknown private mode: {}", param),
                     }
                 }).collect();
                 
-                format!("Reset mode: {}", descriptions.join(", "))
+                format!("Reset private mode: {}", descriptions.join(", "))
             },
             CSI::ApplicationKeypadMode => "Application keypad mode".to_string(),
             CSI::NumericKeypadMode => "Numeric keypad mode".to_string(),
@@ -242,6 +529,11 @@ impl CSI {
             CSI::SetG0NormalChars => "Set G0 normal chars mode".to_string(),
             CSI::ScrollUp(n) => format!("Scroll up {} lines", n),
             CSI::ScrollDown(n) => format!("Scroll down {} lines", n),
+            CSI::SetScrollRegion(top, Some(bottom)) => format!("Set scroll region to lines {}-{}", top, bottom),
+            CSI::SetScrollRegion(top, None) => format!("Set scroll region to lines {}-bottom", top),
+            CSI::Index => "Move cursor down one line, scrolling if needed".to_string(),
+            CSI::NextLine => "Move cursor to beginning of next line, scrolling if needed".to_string(),
+            CSI::ReverseIndex => "Move cursor up one line, scrolling if needed".to_string(),
             CSI::WindowManipulation(params) => {
                 if params.is_empty() {
                     return "Unknown window manipulation".to_string();
@@ -336,6 +628,44 @@ impl CSI {
             },
             CSI::DeviceStatusReport => "Request cursor position".to_string(),
             CSI::CursorPositionReport => "Request extended cursor position".to_string(),
+            CSI::SetCursorStyle(n) => {
+                match n {
+                    0 | 1 => "Set cursor style: blinking block".to_string(),
+                    2 => "Set cursor style: steady block".to_string(),
+                    3 => "Set cursor style: blinking underline".to_string(),
+                    4 => "Set cursor style: steady underline".to_string(),
+                    5 => "Set cursor style: blinking bar".to_string(),
+                    6 => "Set cursor style: steady bar".to_string(),
+                    _ => format!("Unknown cursor style: {}", n),
+                }
+            },
+            CSI::SoftReset => "Soft terminal reset".to_string(),
+            CSI::FullReset => "Full terminal reset".to_string(),
+            CSI::SetModifyKeys(resource, value) => {
+                let name = match resource {
+                    0 => "modifyKeyboard".to_string(),
+                    1 => "modifyCursorKeys".to_string(),
+                    2 => "modifyFunctionKeys".to_string(),
+                    4 => "modifyOtherKeys".to_string(),
+                    _ => format!("key modifier resource {}", resource),
+                };
+                match value {
+                    Some(value) => format!("Set {} to {}", name, value),
+                    None => format!("Reset {}", name),
+                }
+            },
+            CSI::PushKeyboardFlags(flags) => format!("Push keyboard protocol flags: {}", flags),
+            CSI::PopKeyboardFlags(n) => format!("Pop {} keyboard protocol flag entries", n),
+            CSI::SetKeyboardFlags(flags, mode) => {
+                match mode {
+                    1 => format!("Set keyboard protocol flags to {}", flags),
+                    2 => format!("Add keyboard protocol flags {}", flags),
+                    3 => format!("Remove keyboard protocol flags {}", flags),
+                    _ => format!("Unknown keyboard protocol flags mode {}: {}", mode, flags),
+                }
+            },
+            CSI::QueryKeyboardFlags => "Query keyboard protocol flags".to_string(),
+            CSI::Unrecognized(seq) => format!("Unknown CSI sequence: {}{}", seq.body(), seq.final_byte),
             CSI::Unknown(seq) => format!("Unknown CSI sequence: {}", seq),
         }
     }
@@ -31,6 +31,22 @@ impl ControlCharacter {
         }
     }
 
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            ControlCharacter::Null => 0x00,
+            ControlCharacter::Bell => 0x07,
+            ControlCharacter::Backspace => 0x08,
+            ControlCharacter::Tab => 0x09,
+            ControlCharacter::LineFeed => 0x0A,
+            ControlCharacter::VerticalTab => 0x0B,
+            ControlCharacter::FormFeed => 0x0C,
+            ControlCharacter::CarriageReturn => 0x0D,
+            ControlCharacter::Escape => 0x1B,
+            ControlCharacter::Delete => 0x7F,
+            ControlCharacter::Other(byte) => *byte,
+        }
+    }
+
     pub fn escape_repr(&self) -> String {
         match self {
             ControlCharacter::Null => "\\0".to_string(),
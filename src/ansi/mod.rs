@@ -1,35 +1,283 @@
 pub mod csi;
 pub mod ctrl;
+pub mod osc;
+pub mod sgr;
+pub mod string;
+
+/// Where an element came from in the original input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,   // Byte offset of the first byte
+    pub end: usize,     // Byte offset just past the last byte
+    pub line: usize,    // 1-based line of the first byte
+    pub column: usize,  // 1-based column (in characters) of the first byte
+}
+
+impl Span {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// An element together with its span in the input
+#[derive(Debug, Clone)]
+pub struct Spanned {
+    pub element: AnsiElement,
+    pub span: Span,
+    pub raw: Vec<u8>,       // Input bytes covered by the span
+}
+
+impl Spanned {
+    pub fn new(element: AnsiElement, span: Span, raw: Vec<u8>) -> Self {
+        Self { element, span, raw }
+    }
+}
+
+/// Input bytes of a sequence, kept only when they differ from its canonical form
+/// (omitted defaults, literal `\e`, ESC 7 for CSI s, ...). Build elements with
+/// `None` to have them written in canonical form.
+pub type Verbatim = Option<Vec<u8>>;
 
 #[derive(Debug, Clone)]
 pub enum AnsiElement {
     Text(String),
-    Csi(csi::CSI),
-    Ctrl(ctrl::ControlCharacter),
+    InvalidUtf8(Vec<u8>),
+    Csi(csi::CSI, Verbatim),
+    Osc(osc::OSC, osc::Terminator, Verbatim),
+    ControlString(string::ControlString, Verbatim),
+    Ctrl(ctrl::ControlCharacter, Verbatim),
 }
 
 impl AnsiElement {
     pub fn element_type(&self) -> &'static str {
         match self {
             AnsiElement::Text(_) => "Text",
-            AnsiElement::Csi(_) => "CSI",
-            AnsiElement::Ctrl(_) => "Ctrl",
+            AnsiElement::InvalidUtf8(_) => "Invalid",
+            AnsiElement::Csi(..) => "CSI",
+            AnsiElement::Osc(..) => "OSC",
+            AnsiElement::ControlString(string, _) => string.introducer.name(),
+            AnsiElement::Ctrl(..) => "Ctrl",
         }
     }
     
     pub fn escape_repr(&self) -> String {
         match self {
             AnsiElement::Text(text) => text.clone(),
-            AnsiElement::Csi(csi) => csi.escape_repr(),
-            AnsiElement::Ctrl(ctrl) => ctrl.escape_repr(),
+            AnsiElement::InvalidUtf8(bytes) => bytes.iter().map(|b| format!("\\x{:02X}", b)).collect(),
+            AnsiElement::Csi(csi, _) => csi.escape_repr(),
+            AnsiElement::Osc(osc, terminator, _) => osc.escape_repr(*terminator),
+            AnsiElement::ControlString(string, _) => string.escape_repr(),
+            AnsiElement::Ctrl(ctrl, _) => ctrl.escape_repr(),
         }
     }
     
     pub fn description(&self) -> String {
         match self {
-            AnsiElement::Text(text) => format!("{}", text),
-            AnsiElement::Csi(csi) => csi.description(),
-            AnsiElement::Ctrl(ctrl) => ctrl.description(),
+            AnsiElement::Text(text) => text.to_string(),
+            AnsiElement::InvalidUtf8(bytes) => {
+                let bytes_str = bytes.iter()
+                    .map(|b| format!("0x{:02X}", b))
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                if is_truncated_utf8(bytes) {
+                    format!("Truncated UTF-8 sequence: {}", bytes_str)
+                } else {
+                    format!("Invalid UTF-8 sequence: {}", bytes_str)
+                }
+            },
+            AnsiElement::Csi(csi, _) => csi.description(),
+            AnsiElement::Osc(osc, osc::Terminator::Unterminated, _) => format!("{} (unterminated)", osc.description()),
+            AnsiElement::Osc(osc, _, _) => osc.description(),
+            AnsiElement::ControlString(string, _) if string.terminator == osc::Terminator::Unterminated => {
+                format!("{} (unterminated)", string.description())
+            },
+            AnsiElement::ControlString(string, _) => string.description(),
+            AnsiElement::Ctrl(ctrl, _) => ctrl.description(),
+        }
+    }
+    
+    /// Bytes of the element exactly as they appeared in the input
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self.verbatim() {
+            Some(bytes) => bytes.to_vec(),
+            None => self.canonical_bytes(),
+        }
+    }
+    
+    /// Bytes of the element written the way seqsee would write it
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        match self {
+            AnsiElement::Text(text) => text.as_bytes().to_vec(),
+            AnsiElement::InvalidUtf8(bytes) => bytes.clone(),
+            AnsiElement::Ctrl(ctrl, _) => vec![ctrl.to_byte()],
+            // Typed sequences only ever contain printable ASCII after the escape
+            AnsiElement::Csi(csi, _) => unescape(&csi.escape_repr()),
+            AnsiElement::Osc(osc, terminator, _) => {
+                let mut bytes = b"\x1b]".to_vec();
+                bytes.extend_from_slice(osc.payload().as_bytes());
+                bytes.extend_from_slice(terminator.to_bytes());
+                bytes
+            },
+            AnsiElement::ControlString(string, _) => {
+                let mut bytes = unescape(string.introducer.escape_repr());
+                bytes.extend_from_slice(string.params.as_bytes());
+                bytes.extend_from_slice(string.intermediates.as_bytes());
+                if let Some(final_byte) = string.final_byte {
+                    bytes.push(final_byte as u8);
+                }
+                bytes.extend_from_slice(&string.data);
+                bytes.extend_from_slice(string.terminator.to_bytes());
+                bytes
+            },
         }
     }
-} 
\ No newline at end of file
+    
+    /// Remember the input bytes of the element if its canonical form differs from them
+    pub fn with_verbatim(mut self, raw: &[u8]) -> Self {
+        if self.canonical_bytes() == raw {
+            return self;
+        }
+        match &mut self {
+            AnsiElement::Csi(_, verbatim) |
+            AnsiElement::Osc(_, _, verbatim) |
+            AnsiElement::ControlString(_, verbatim) |
+            AnsiElement::Ctrl(_, verbatim) => *verbatim = Some(raw.to_vec()),
+            // Text is decoded losslessly, invalid bytes are kept as they are
+            AnsiElement::Text(_) | AnsiElement::InvalidUtf8(_) => {},
+        }
+        self
+    }
+    
+    fn verbatim(&self) -> Option<&[u8]> {
+        match self {
+            AnsiElement::Csi(_, verbatim) |
+            AnsiElement::Osc(_, _, verbatim) |
+            AnsiElement::ControlString(_, verbatim) |
+            AnsiElement::Ctrl(_, verbatim) => verbatim.as_deref(),
+            AnsiElement::Text(_) | AnsiElement::InvalidUtf8(_) => None,
+        }
+    }
+}
+
+// Turn the \xNN escapes of an escape_repr back into bytes
+fn unescape(repr: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(repr.len());
+    let mut rest = repr.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'\\' && tail.len() >= 3 && tail[0] == b'x' {
+            if let Ok(value) = u8::from_str_radix(&String::from_utf8_lossy(&tail[1..3]), 16) {
+                bytes.push(value);
+                rest = &tail[3..];
+                continue;
+            }
+        }
+        bytes.push(byte);
+        rest = tail;
+    }
+    bytes
+}
+
+// A truncated sequence is a valid lead byte followed by too few continuation bytes
+fn is_truncated_utf8(bytes: &[u8]) -> bool {
+    let expected = match bytes.first() {
+        Some(0xC2..=0xDF) => 2,
+        Some(0xE0..=0xEF) => 3,
+        Some(0xF0..=0xF4) => 4,
+        _ => return false,
+    };
+    bytes.len() < expected && bytes[1..].iter().all(|b| (0x80..=0xBF).contains(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::AnsiParser;
+
+    // Writing the parsed elements back gives the input, and each element writes its own span
+    fn assert_round_trip(input: &[u8]) {
+        let elements = AnsiParser::parse_spanned(input).unwrap();
+        for spanned in &elements {
+            assert_eq!(spanned.element.to_bytes(), spanned.raw, "{:?}", spanned.element);
+            assert_eq!(&input[spanned.span.start..spanned.span.end], &spanned.raw[..]);
+        }
+        let written: Vec<u8> = elements.iter().flat_map(|spanned| spanned.element.to_bytes()).collect();
+        assert_eq!(written, input, "{:?}", String::from_utf8_lossy(input));
+
+        // Fed a byte at a time, the parser finds the same elements
+        let mut parser = AnsiParser::new();
+        let mut fed: Vec<Spanned> = input.iter().flat_map(|byte| parser.feed(std::slice::from_ref(byte))).collect();
+        fed.extend(parser.finish());
+        assert_eq!(format!("{:?}", fed), format!("{:?}", elements));
+    }
+
+    #[test]
+    fn literal_escapes() {
+        assert_round_trip(b"\\e[1mbold\\E[0m");
+        assert_round_trip(b"\\e]0;title\\e\\\\ \\x and \\\\e");
+        assert_round_trip(b"trailing \\");
+    }
+
+    #[test]
+    fn omitted_and_explicit_defaults() {
+        assert_round_trip(b"\x1b[H\x1b[;H\x1b[1;1H\x1b[0;0H\x1b[m\x1b[0m\x1b[00m");
+        assert_round_trip(b"\x1b[A\x1b[1A\x1b[0A\x1b[K\x1b[0K\x1b[J");
+        assert_round_trip(b"\x1b[38;5;1m\x1b[38:2::1:2:3m\x1b[4:3m\x1b[;1m");
+    }
+
+    #[test]
+    fn aliases() {
+        assert_round_trip(b"\x1b7\x1b[s\x1b8\x1b[u\x1bc\x1b[!p\x1bD\x1bM\x1bE");
+        assert_round_trip(b"\x1b(0qqq\x1b(B\x1b(Z\x1b=\x1b>");
+    }
+
+    #[test]
+    fn eight_bit_controls() {
+        assert_round_trip(b"a\x9b31mb\x9d0;x\x07c\x90q\x9c");
+        assert_round_trip(b"\x85\x8d\x9e\x9f");
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        assert_round_trip(b"caf\xc3\xa9 \xff\xfe ok");
+        assert_round_trip(b"cut \xe2\x82");
+        assert_round_trip(b"cut \xe2\x82\x1b[1m\xe2");
+        assert_round_trip(b"\xf0\x9f\x98");
+    }
+
+    #[test]
+    fn unterminated_and_interrupted_strings() {
+        assert_round_trip(b"\x1b]0;never closed");
+        assert_round_trip(b"\x1bPq~~~");
+        assert_round_trip(b"\x1b]0;cut\x1b[1mx\x1b_apc\x1bPq\x1b\\");
+        assert_round_trip(b"\x1b]8;;https://x\x1b\\link\x1b]8;;\x07");
+        assert_round_trip(b"\x1b");
+        assert_round_trip(b"\x1b[");
+        assert_round_trip(b"\x1b[12;");
+        assert_round_trip(b"\x1b(");
+    }
+
+    #[test]
+    fn unknown_sequences() {
+        assert_round_trip(b"\x1b[?1049h\x1b[?1;2;3$p\x1b[>4;1m\x1b[ q\x1b[>c\x1b[=5u\x1b%G\x1b#8");
+        assert_round_trip(b"\x1b[99999999999999999999m\x1b[1;2;3;4;5;6;7;8;9;10;11;12;13;14;15;16;17;18;19;20m");
+    }
+
+    #[test]
+    fn random_streams() {
+        let alphabet: &[u8] = b"\x1b\x1b\x1b[[];;:0123459mHJK?hl\\e\x07 a\x9b\xc3\xa9\xff\r\n\x08PX_^(c78";
+        let mut seed: u64 = 0x5eed;
+        let mut next = |bound: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+        for _ in 0..2000 {
+            let len = next(40);
+            let input: Vec<u8> = (0..len).map(|_| alphabet[next(alphabet.len())]).collect();
+            assert_round_trip(&input);
+        }
+    }
+}
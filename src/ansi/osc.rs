@@ -0,0 +1,281 @@
+/// How an OSC (or other string) sequence was terminated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Terminator {
+    Bel,            // \a
+    St,             // ESC \
+    Unterminated,   // Input ended or another sequence interrupted it
+}
+
+impl Terminator {
+    pub fn escape_repr(&self) -> &'static str {
+        match self {
+            Terminator::Bel => "\\a",
+            Terminator::St => "\\x1b\\\\",
+            Terminator::Unterminated => "",
+        }
+    }
+
+    pub fn to_bytes(&self) -> &'static [u8] {
+        match self {
+            Terminator::Bel => b"\x07",
+            Terminator::St => b"\x1b\\",
+            Terminator::Unterminated => b"",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum OSC {
+    // Window titles
+    SetIconNameAndTitle(String),         // OSC 0
+    SetIconName(String),                 // OSC 1
+    SetWindowTitle(String),              // OSC 2
+
+    // Palette
+    SetPaletteColor(Vec<(u16, String)>), // OSC 4 ; c ; spec ...
+    ResetPaletteColor(Vec<u16>),         // OSC 104 [; c ...]
+
+    // Current working directory
+    SetWorkingDirectory(String),         // OSC 7 ; file://host/path
+
+    // Hyperlinks
+    Hyperlink(String, String),           // OSC 8 ; params ; uri
+
+    // Dynamic colors: first code and the specs that follow it
+    DynamicColor(u16, Vec<String>),      // OSC 10/11/12 ; spec ...
+
+    // Clipboard
+    Clipboard(String, String),           // OSC 52 ; selection ; base64 data
+
+    // Shell integration (FinalTerm / iTerm2 / VS Code marks)
+    ShellIntegration(String, Vec<String>), // OSC 133 ; mark [; params]
+
+    // Other OSC commands (raw payload)
+    Unknown(String),
+}
+
+impl OSC {
+    /// Decode the payload between `ESC ]` and the terminator
+    pub fn from_payload(payload: &str) -> Self {
+        let (code, rest) = match payload.split_once(';') {
+            Some((code, rest)) => (code, rest),
+            None => (payload, ""),
+        };
+
+        let code = match code.parse::<u16>() {
+            Ok(code) => code,
+            Err(_) => return OSC::Unknown(payload.to_string()),
+        };
+
+        match code {
+            0 => OSC::SetIconNameAndTitle(rest.to_string()),
+            1 => OSC::SetIconName(rest.to_string()),
+            2 => OSC::SetWindowTitle(rest.to_string()),
+            4 => {
+                let parts: Vec<&str> = rest.split(';').collect();
+                let mut colors = Vec::new();
+                for pair in parts.chunks(2) {
+                    match (pair[0].parse::<u16>(), pair.get(1)) {
+                        (Ok(index), Some(spec)) => colors.push((index, spec.to_string())),
+                        _ => return OSC::Unknown(payload.to_string()),
+                    }
+                }
+                OSC::SetPaletteColor(colors)
+            }
+            7 => OSC::SetWorkingDirectory(rest.to_string()),
+            8 => match rest.split_once(';') {
+                Some((params, uri)) => OSC::Hyperlink(params.to_string(), uri.to_string()),
+                None => OSC::Unknown(payload.to_string()),
+            },
+            10..=12 => OSC::DynamicColor(code, rest.split(';').map(|s| s.to_string()).collect()),
+            52 => match rest.split_once(';') {
+                Some((selection, data)) => OSC::Clipboard(selection.to_string(), data.to_string()),
+                None => OSC::Unknown(payload.to_string()),
+            },
+            104 => {
+                if rest.is_empty() {
+                    return OSC::ResetPaletteColor(Vec::new());
+                }
+                let indexes: Result<Vec<u16>, _> = rest.split(';').map(|s| s.parse::<u16>()).collect();
+                match indexes {
+                    Ok(indexes) => OSC::ResetPaletteColor(indexes),
+                    Err(_) => OSC::Unknown(payload.to_string()),
+                }
+            }
+            133 => {
+                let mut parts = rest.split(';');
+                let mark = parts.next().unwrap_or("").to_string();
+                OSC::ShellIntegration(mark, parts.map(|s| s.to_string()).collect())
+            }
+            _ => OSC::Unknown(payload.to_string()),
+        }
+    }
+
+    /// Rebuild the payload between `ESC ]` and the terminator
+    pub fn payload(&self) -> String {
+        match self {
+            OSC::SetIconNameAndTitle(title) => format!("0;{}", title),
+            OSC::SetIconName(name) => format!("1;{}", name),
+            OSC::SetWindowTitle(title) => format!("2;{}", title),
+            OSC::SetPaletteColor(colors) => {
+                let colors_str = colors.iter()
+                    .map(|(index, spec)| format!("{};{}", index, spec))
+                    .collect::<Vec<String>>()
+                    .join(";");
+                format!("4;{}", colors_str)
+            },
+            OSC::ResetPaletteColor(indexes) => {
+                if indexes.is_empty() {
+                    return "104".to_string();
+                }
+                let indexes_str = indexes.iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<String>>()
+                    .join(";");
+                format!("104;{}", indexes_str)
+            },
+            OSC::SetWorkingDirectory(uri) => format!("7;{}", uri),
+            OSC::Hyperlink(params, uri) => format!("8;{};{}", params, uri),
+            OSC::DynamicColor(code, specs) => format!("{};{}", code, specs.join(";")),
+            OSC::Clipboard(selection, data) => format!("52;{};{}", selection, data),
+            OSC::ShellIntegration(mark, params) => {
+                let mut payload = format!("133;{}", mark);
+                for param in params {
+                    payload.push(';');
+                    payload.push_str(param);
+                }
+                payload
+            },
+            OSC::Unknown(payload) => payload.clone(),
+        }
+    }
+
+    pub fn escape_repr(&self, terminator: Terminator) -> String {
+        format!("\\x1b]{}{}", self.payload(), terminator.escape_repr())
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            OSC::SetIconNameAndTitle(title) => format!("Set icon name and window title to \"{}\"", title),
+            OSC::SetIconName(name) => format!("Set icon name to \"{}\"", name),
+            OSC::SetWindowTitle(title) => format!("Set window title to \"{}\"", title),
+            OSC::SetPaletteColor(colors) => {
+                let descriptions: Vec<String> = colors.iter().map(|(index, spec)| {
+                    if spec == "?" {
+                        format!("query color {}", index)
+                    } else {
+                        format!("color {} to {}", index, spec)
+                    }
+                }).collect();
+
+                format!("Set palette: {}", descriptions.join(", "))
+            },
+            OSC::ResetPaletteColor(indexes) => {
+                if indexes.is_empty() {
+                    return "Reset all palette colors".to_string();
+                }
+                let indexes_str = indexes.iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                format!("Reset palette colors: {}", indexes_str)
+            },
+            OSC::SetWorkingDirectory(uri) => format!("Set working directory to {}", uri),
+            OSC::Hyperlink(params, uri) => {
+                if uri.is_empty() {
+                    "End hyperlink".to_string()
+                } else if params.is_empty() {
+                    format!("Start hyperlink to {}", uri)
+                } else {
+                    format!("Start hyperlink to {} ({})", uri, params)
+                }
+            },
+            OSC::DynamicColor(code, specs) => {
+                // Each additional spec applies to the next dynamic color
+                let descriptions: Vec<String> = specs.iter().enumerate().map(|(offset, spec)| {
+                    let name = match code + offset as u16 {
+                        10 => "foreground color".to_string(),
+                        11 => "background color".to_string(),
+                        12 => "cursor color".to_string(),
+                        13 => "mouse foreground color".to_string(),
+                        14 => "mouse background color".to_string(),
+                        15 => "Tektronix foreground color".to_string(),
+                        16 => "Tektronix background color".to_string(),
+                        17 => "highlight background color".to_string(),
+                        18 => "Tektronix cursor color".to_string(),
+                        19 => "highlight foreground color".to_string(),
+                        n => format!("dynamic color {}", n),
+                    };
+                    if spec == "?" {
+                        format!("Query {}", name)
+                    } else {
+                        format!("Set {} to {}", name, spec)
+                    }
+                }).collect();
+
+                descriptions.join(", ")
+            },
+            OSC::Clipboard(selection, data) => {
+                let target = if selection.is_empty() { "c" } else { selection.as_str() };
+                if data == "?" {
+                    format!("Query clipboard ({})", target)
+                } else if data.is_empty() {
+                    format!("Clear clipboard ({})", target)
+                } else {
+                    match decode_base64(data) {
+                        Some(decoded) => format!(
+                            "Copy to clipboard ({}): \"{}\"",
+                            target,
+                            String::from_utf8_lossy(&decoded)
+                        ),
+                        None => format!("Copy to clipboard ({}): invalid base64 data", target),
+                    }
+                }
+            },
+            OSC::ShellIntegration(mark, params) => {
+                match mark.as_str() {
+                    "A" => "Shell integration: prompt start".to_string(),
+                    "B" => "Shell integration: command input start".to_string(),
+                    "C" => "Shell integration: command output start".to_string(),
+                    "D" => match params.first() {
+                        Some(code) => format!("Shell integration: command finished with exit code {}", code),
+                        None => "Shell integration: command finished".to_string(),
+                    },
+                    "L" => "Shell integration: fresh line".to_string(),
+                    "N" => "Shell integration: new command".to_string(),
+                    "P" => format!("Shell integration: property {}", params.join(";")),
+                    _ => format!("Shell integration: unknown mark {}", mark),
+                }
+            },
+            OSC::Unknown(payload) => format!("Unknown OSC sequence: {}", payload),
+        }
+    }
+}
+
+// Decode standard base64 (as used by OSC 52), ignoring padding
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(data.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+
+    for byte in data.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => continue,
+            _ => return None,
+        };
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    Some(result)
+}
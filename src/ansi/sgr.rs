@@ -0,0 +1,496 @@
+use super::csi::{CsiParam, CSI};
+
+/// Default xterm colors for the 16 standard and bright palette entries
+pub const XTERM_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+    (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+/// A color as selected by SGR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SgrColor {
+    Standard(u8),       // 30-37 / 40-47, index 0-7
+    Bright(u8),         // 90-97 / 100-107, index 0-7
+    Indexed(u8),        // 38;5;n 256-color palette
+    Rgb(u8, u8, u8),    // 38;2;r;g;b
+}
+
+impl SgrColor {
+    /// Resolve to RGB, taking the first 16 colors from the given palette
+    pub fn to_rgb(&self, palette: &[(u8, u8, u8); 16]) -> (u8, u8, u8) {
+        match *self {
+            SgrColor::Standard(n) => palette[(n & 7) as usize],
+            SgrColor::Bright(n) => palette[(n & 7) as usize + 8],
+            SgrColor::Indexed(n) if n < 16 => palette[n as usize],
+            // 6x6x6 color cube
+            SgrColor::Indexed(n) if n < 232 => {
+                let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+                let n = n - 16;
+                (level(n / 36), level((n / 6) % 6), level(n % 6))
+            },
+            // Grayscale ramp
+            SgrColor::Indexed(n) => {
+                let gray = 8 + (n - 232) * 10;
+                (gray, gray, gray)
+            },
+            SgrColor::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+
+    /// Parameters following 38/48/58 that select this color
+    fn extended_params(&self) -> String {
+        match self {
+            SgrColor::Standard(n) => format!("5;{}", n),
+            SgrColor::Bright(n) => format!("5;{}", n + 8),
+            SgrColor::Indexed(n) => format!("5;{}", n),
+            SgrColor::Rgb(r, g, b) => format!("2;{};{};{}", r, g, b),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            SgrColor::Standard(n) => n.to_string(),
+            SgrColor::Bright(n) => format!("bright {}", n),
+            SgrColor::Indexed(n) => format!("256-color {}", n),
+            SgrColor::Rgb(r, g, b) => format!("RGB({},{},{})", r, g, b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnderlineStyle {
+    #[default]
+    None,           // 24 or 4:0
+    Single,         // 4 or 4:1
+    Double,         // 21 or 4:2
+    Curly,          // 4:3
+    Dotted,         // 4:4
+    Dashed,         // 4:5
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SgrAttribute {
+    Reset,                          // 0
+
+    // Intensity
+    Bold,                           // 1
+    Faint,                          // 2
+    NormalIntensity,                // 22
+
+    // Style
+    Italic,                         // 3
+    NotItalic,                      // 23
+    Underline(UnderlineStyle),      // 4, 4:n, 21, 24
+    SlowBlink,                      // 5
+    RapidBlink,                     // 6
+    NotBlinking,                    // 25
+    Reverse,                        // 7
+    NotReversed,                    // 27
+    Conceal,                        // 8
+    Reveal,                         // 28
+    CrossedOut,                     // 9
+    NotCrossedOut,                  // 29
+
+    // Fonts: 0 is the primary font, 1-9 alternatives, 10 Fraktur
+    Font(u8),                       // 10-20
+
+    // Colors
+    Foreground(SgrColor),           // 30-37, 38, 90-97
+    DefaultForeground,              // 39
+    Background(SgrColor),           // 40-47, 48, 100-107
+    DefaultBackground,              // 49
+    UnderlineColor(SgrColor),       // 58
+    DefaultUnderlineColor,          // 59
+
+    // Decorations
+    Framed,                         // 51
+    Encircled,                      // 52
+    Overline,                       // 53
+    NotFramedOrEncircled,           // 54
+    NotOverlined,                   // 55
+    Superscript,                    // 73
+    Subscript,                      // 74
+    NotSuperscriptOrSubscript,      // 75
+
+    // Parameters that don't map to an attribute, kept as written
+    Unknown(String),
+}
+
+impl SgrAttribute {
+    /// Walk an SGR parameter list, consuming extended color arguments as they come
+    pub fn parse(params: &[CsiParam]) -> Vec<SgrAttribute> {
+        let mut attributes = Vec::new();
+        let mut i = 0;
+
+        while i < params.len() {
+            let param = &params[i];
+            let value = param.value.unwrap_or(0);
+            i += 1;
+
+            let attribute = match value {
+                0 => SgrAttribute::Reset,
+                1 => SgrAttribute::Bold,
+                2 => SgrAttribute::Faint,
+                3 => SgrAttribute::Italic,
+                4 => match param.subparams.first() {
+                    None => SgrAttribute::Underline(UnderlineStyle::Single),
+                    Some(Some(0)) => SgrAttribute::Underline(UnderlineStyle::None),
+                    Some(Some(1)) => SgrAttribute::Underline(UnderlineStyle::Single),
+                    Some(Some(2)) => SgrAttribute::Underline(UnderlineStyle::Double),
+                    Some(Some(3)) => SgrAttribute::Underline(UnderlineStyle::Curly),
+                    Some(Some(4)) => SgrAttribute::Underline(UnderlineStyle::Dotted),
+                    Some(Some(5)) => SgrAttribute::Underline(UnderlineStyle::Dashed),
+                    Some(_) => SgrAttribute::Unknown(param.repr()),
+                },
+                5 => SgrAttribute::SlowBlink,
+                6 => SgrAttribute::RapidBlink,
+                7 => SgrAttribute::Reverse,
+                8 => SgrAttribute::Conceal,
+                9 => SgrAttribute::CrossedOut,
+                10..=20 => SgrAttribute::Font((value - 10) as u8),
+                21 => SgrAttribute::Underline(UnderlineStyle::Double),
+                22 => SgrAttribute::NormalIntensity,
+                23 => SgrAttribute::NotItalic,
+                24 => SgrAttribute::Underline(UnderlineStyle::None),
+                25 => SgrAttribute::NotBlinking,
+                27 => SgrAttribute::NotReversed,
+                28 => SgrAttribute::Reveal,
+                29 => SgrAttribute::NotCrossedOut,
+                30..=37 => SgrAttribute::Foreground(SgrColor::Standard((value - 30) as u8)),
+                39 => SgrAttribute::DefaultForeground,
+                40..=47 => SgrAttribute::Background(SgrColor::Standard((value - 40) as u8)),
+                49 => SgrAttribute::DefaultBackground,
+                51 => SgrAttribute::Framed,
+                52 => SgrAttribute::Encircled,
+                53 => SgrAttribute::Overline,
+                54 => SgrAttribute::NotFramedOrEncircled,
+                55 => SgrAttribute::NotOverlined,
+                59 => SgrAttribute::DefaultUnderlineColor,
+                73 => SgrAttribute::Superscript,
+                74 => SgrAttribute::Subscript,
+                75 => SgrAttribute::NotSuperscriptOrSubscript,
+                90..=97 => SgrAttribute::Foreground(SgrColor::Bright((value - 90) as u8)),
+                100..=107 => SgrAttribute::Background(SgrColor::Bright((value - 100) as u8)),
+                38 | 48 | 58 => {
+                    let color = if param.subparams.is_empty() {
+                        Self::parse_color_args(params, &mut i)
+                    } else {
+                        Self::parse_color_subparams(&param.subparams)
+                    };
+                    match (value, color) {
+                        (38, Some(color)) => SgrAttribute::Foreground(color),
+                        (48, Some(color)) => SgrAttribute::Background(color),
+                        (58, Some(color)) => SgrAttribute::UnderlineColor(color),
+                        // A truncated color takes the rest of the list with it, rather than
+                        // leaving its arguments to be read as attributes
+                        _ if param.subparams.is_empty() => {
+                            let rest: Vec<String> = params[i - 1..].iter().map(CsiParam::repr).collect();
+                            i = params.len();
+                            SgrAttribute::Unknown(rest.join(";"))
+                        },
+                        _ => SgrAttribute::Unknown(param.repr()),
+                    }
+                },
+                _ => SgrAttribute::Unknown(param.repr()),
+            };
+
+            attributes.push(attribute);
+        }
+
+        attributes
+    }
+
+    // Color given as following parameters: 5;n or 2;r;g;b
+    fn parse_color_args(params: &[CsiParam], i: &mut usize) -> Option<SgrColor> {
+        let arg = |offset: usize| params.get(*i + offset).map(|p| p.value.unwrap_or(0));
+
+        match arg(0)? {
+            5 => {
+                let index = arg(1)?;
+                *i += 2;
+                Some(SgrColor::Indexed(index.min(255) as u8))
+            },
+            2 => {
+                let (r, g, b) = (arg(1)?, arg(2)?, arg(3)?);
+                *i += 4;
+                Some(SgrColor::Rgb(r.min(255) as u8, g.min(255) as u8, b.min(255) as u8))
+            },
+            _ => None,
+        }
+    }
+
+    // Color given as sub-parameters: 5:n, 2:r:g:b or 2:colorspace:r:g:b
+    fn parse_color_subparams(subparams: &[Option<u16>]) -> Option<SgrColor> {
+        let values: Vec<u8> = subparams.iter().map(|s| s.unwrap_or(0).min(255) as u8).collect();
+
+        match values.as_slice() {
+            [5, n, ..] => Some(SgrColor::Indexed(*n)),
+            [2, _, r, g, b, ..] => Some(SgrColor::Rgb(*r, *g, *b)),
+            [2, r, g, b] => Some(SgrColor::Rgb(*r, *g, *b)),
+            _ => None,
+        }
+    }
+
+    /// SGR parameters that select this attribute
+    pub fn params(&self) -> String {
+        match self {
+            SgrAttribute::Reset => "0".to_string(),
+            SgrAttribute::Bold => "1".to_string(),
+            SgrAttribute::Faint => "2".to_string(),
+            SgrAttribute::NormalIntensity => "22".to_string(),
+            SgrAttribute::Italic => "3".to_string(),
+            SgrAttribute::NotItalic => "23".to_string(),
+            SgrAttribute::Underline(style) => match style {
+                UnderlineStyle::None => "24".to_string(),
+                UnderlineStyle::Single => "4".to_string(),
+                UnderlineStyle::Double => "21".to_string(),
+                UnderlineStyle::Curly => "4:3".to_string(),
+                UnderlineStyle::Dotted => "4:4".to_string(),
+                UnderlineStyle::Dashed => "4:5".to_string(),
+            },
+            SgrAttribute::SlowBlink => "5".to_string(),
+            SgrAttribute::RapidBlink => "6".to_string(),
+            SgrAttribute::NotBlinking => "25".to_string(),
+            SgrAttribute::Reverse => "7".to_string(),
+            SgrAttribute::NotReversed => "27".to_string(),
+            SgrAttribute::Conceal => "8".to_string(),
+            SgrAttribute::Reveal => "28".to_string(),
+            SgrAttribute::CrossedOut => "9".to_string(),
+            SgrAttribute::NotCrossedOut => "29".to_string(),
+            SgrAttribute::Font(n) => (10 + *n as u16).to_string(),
+            SgrAttribute::Foreground(color) => match color {
+                SgrColor::Standard(n) => (30 + *n as u16).to_string(),
+                SgrColor::Bright(n) => (90 + *n as u16).to_string(),
+                _ => format!("38;{}", color.extended_params()),
+            },
+            SgrAttribute::DefaultForeground => "39".to_string(),
+            SgrAttribute::Background(color) => match color {
+                SgrColor::Standard(n) => (40 + *n as u16).to_string(),
+                SgrColor::Bright(n) => (100 + *n as u16).to_string(),
+                _ => format!("48;{}", color.extended_params()),
+            },
+            SgrAttribute::DefaultBackground => "49".to_string(),
+            SgrAttribute::UnderlineColor(color) => format!("58;{}", color.extended_params()),
+            SgrAttribute::DefaultUnderlineColor => "59".to_string(),
+            SgrAttribute::Framed => "51".to_string(),
+            SgrAttribute::Encircled => "52".to_string(),
+            SgrAttribute::Overline => "53".to_string(),
+            SgrAttribute::NotFramedOrEncircled => "54".to_string(),
+            SgrAttribute::NotOverlined => "55".to_string(),
+            SgrAttribute::Superscript => "73".to_string(),
+            SgrAttribute::Subscript => "74".to_string(),
+            SgrAttribute::NotSuperscriptOrSubscript => "75".to_string(),
+            SgrAttribute::Unknown(params) => params.clone(),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            SgrAttribute::Reset => "Reset all".to_string(),
+            SgrAttribute::Bold => "Bold".to_string(),
+            SgrAttribute::Faint => "Faint".to_string(),
+            SgrAttribute::NormalIntensity => "Normal intensity".to_string(),
+            SgrAttribute::Italic => "Italic".to_string(),
+            SgrAttribute::NotItalic => "Not italic".to_string(),
+            SgrAttribute::Underline(style) => match style {
+                UnderlineStyle::None => "Not underlined".to_string(),
+                UnderlineStyle::Single => "Underline".to_string(),
+                UnderlineStyle::Double => "Double underline".to_string(),
+                UnderlineStyle::Curly => "Curly underline".to_string(),
+                UnderlineStyle::Dotted => "Dotted underline".to_string(),
+                UnderlineStyle::Dashed => "Dashed underline".to_string(),
+            },
+            SgrAttribute::SlowBlink => "Slow blink".to_string(),
+            SgrAttribute::RapidBlink => "Rapid blink".to_string(),
+            SgrAttribute::NotBlinking => "Not blinking".to_string(),
+            SgrAttribute::Reverse => "Reverse".to_string(),
+            SgrAttribute::NotReversed => "Not reversed".to_string(),
+            SgrAttribute::Conceal => "Conceal".to_string(),
+            SgrAttribute::Reveal => "Reveal".to_string(),
+            SgrAttribute::CrossedOut => "Crossed-out".to_string(),
+            SgrAttribute::NotCrossedOut => "Not crossed out".to_string(),
+            SgrAttribute::Font(0) => "Primary font".to_string(),
+            SgrAttribute::Font(10) => "Fraktur font".to_string(),
+            SgrAttribute::Font(n) => format!("Alternative font {}", n),
+            SgrAttribute::Foreground(SgrColor::Standard(n)) => format!("Foreground color: {}", n),
+            SgrAttribute::Foreground(SgrColor::Bright(n)) => format!("Bright foreground color: {}", n),
+            SgrAttribute::Foreground(color) => format!("Foreground color: {}", color.description()),
+            SgrAttribute::DefaultForeground => "Reset foreground color".to_string(),
+            SgrAttribute::Background(SgrColor::Standard(n)) => format!("Background color: {}", n),
+            SgrAttribute::Background(SgrColor::Bright(n)) => format!("Bright background color: {}", n),
+            SgrAttribute::Background(color) => format!("Background color: {}", color.description()),
+            SgrAttribute::DefaultBackground => "Reset background color".to_string(),
+            SgrAttribute::UnderlineColor(color) => format!("Underline color: {}", color.description()),
+            SgrAttribute::DefaultUnderlineColor => "Reset underline color".to_string(),
+            SgrAttribute::Framed => "Framed".to_string(),
+            SgrAttribute::Encircled => "Encircled".to_string(),
+            SgrAttribute::Overline => "Overline".to_string(),
+            SgrAttribute::NotFramedOrEncircled => "Not framed or encircled".to_string(),
+            SgrAttribute::NotOverlined => "Not overlined".to_string(),
+            SgrAttribute::Superscript => "Superscript".to_string(),
+            SgrAttribute::Subscript => "Subscript".to_string(),
+            SgrAttribute::NotSuperscriptOrSubscript => "Not superscript or subscript".to_string(),
+            SgrAttribute::Unknown(params) => format!("Unknown parameter: {}", params),
+        }
+    }
+}
+
+/// Graphics state accumulated by applying SGR attributes in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SgrState {
+    pub foreground: Option<SgrColor>,       // None is the terminal default
+    pub background: Option<SgrColor>,
+    pub underline_color: Option<SgrColor>,
+    pub bold: bool,
+    pub faint: bool,
+    pub italic: bool,
+    pub underline: UnderlineStyle,
+    pub slow_blink: bool,
+    pub rapid_blink: bool,
+    pub reverse: bool,
+    pub conceal: bool,
+    pub crossed_out: bool,
+    pub font: u8,
+    pub framed: bool,
+    pub encircled: bool,
+    pub overline: bool,
+    pub superscript: bool,
+    pub subscript: bool,
+}
+
+impl SgrState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(&mut self, attribute: &SgrAttribute) {
+        match attribute {
+            SgrAttribute::Reset => *self = Self::default(),
+            SgrAttribute::Bold => self.bold = true,
+            SgrAttribute::Faint => self.faint = true,
+            SgrAttribute::NormalIntensity => {
+                self.bold = false;
+                self.faint = false;
+            },
+            SgrAttribute::Italic => self.italic = true,
+            SgrAttribute::NotItalic => self.italic = false,
+            SgrAttribute::Underline(style) => self.underline = *style,
+            SgrAttribute::SlowBlink => {
+                self.slow_blink = true;
+                self.rapid_blink = false;
+            },
+            SgrAttribute::RapidBlink => {
+                self.rapid_blink = true;
+                self.slow_blink = false;
+            },
+            SgrAttribute::NotBlinking => {
+                self.slow_blink = false;
+                self.rapid_blink = false;
+            },
+            SgrAttribute::Reverse => self.reverse = true,
+            SgrAttribute::NotReversed => self.reverse = false,
+            SgrAttribute::Conceal => self.conceal = true,
+            SgrAttribute::Reveal => self.conceal = false,
+            SgrAttribute::CrossedOut => self.crossed_out = true,
+            SgrAttribute::NotCrossedOut => self.crossed_out = false,
+            SgrAttribute::Font(n) => self.font = *n,
+            SgrAttribute::Foreground(color) => self.foreground = Some(*color),
+            SgrAttribute::DefaultForeground => self.foreground = None,
+            SgrAttribute::Background(color) => self.background = Some(*color),
+            SgrAttribute::DefaultBackground => self.background = None,
+            SgrAttribute::UnderlineColor(color) => self.underline_color = Some(*color),
+            SgrAttribute::DefaultUnderlineColor => self.underline_color = None,
+            SgrAttribute::Framed => self.framed = true,
+            SgrAttribute::Encircled => self.encircled = true,
+            SgrAttribute::Overline => self.overline = true,
+            SgrAttribute::NotFramedOrEncircled => {
+                self.framed = false;
+                self.encircled = false;
+            },
+            SgrAttribute::NotOverlined => self.overline = false,
+            SgrAttribute::Superscript => {
+                self.superscript = true;
+                self.subscript = false;
+            },
+            SgrAttribute::Subscript => {
+                self.subscript = true;
+                self.superscript = false;
+            },
+            SgrAttribute::NotSuperscriptOrSubscript => {
+                self.superscript = false;
+                self.subscript = false;
+            },
+            SgrAttribute::Unknown(_) => {},
+        }
+    }
+
+    /// Apply a parsed SGR command. Returns false if the command isn't SGR.
+    pub fn apply_csi(&mut self, csi: &CSI) -> bool {
+        match csi {
+            CSI::SetGraphicsMode(attributes) => {
+                for attribute in attributes {
+                    self.apply(attribute);
+                }
+            },
+            CSI::ResetAttributes => *self = Self::default(),
+            CSI::SetForegroundColor(r, g, b) => self.foreground = Some(SgrColor::Rgb(*r, *g, *b)),
+            CSI::SetBackgroundColor(r, g, b) => self.background = Some(SgrColor::Rgb(*r, *g, *b)),
+            CSI::SetForegroundColor256(n) => self.foreground = Some(SgrColor::Indexed(*n)),
+            CSI::SetBackgroundColor256(n) => self.background = Some(SgrColor::Indexed(*n)),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Attributes that produce this state when applied to the default state
+    pub fn attributes(&self) -> Vec<SgrAttribute> {
+        let mut attributes = Vec::new();
+        let flags = [
+            (self.bold, SgrAttribute::Bold),
+            (self.faint, SgrAttribute::Faint),
+            (self.italic, SgrAttribute::Italic),
+            (self.underline != UnderlineStyle::None, SgrAttribute::Underline(self.underline)),
+            (self.slow_blink, SgrAttribute::SlowBlink),
+            (self.rapid_blink, SgrAttribute::RapidBlink),
+            (self.reverse, SgrAttribute::Reverse),
+            (self.conceal, SgrAttribute::Conceal),
+            (self.crossed_out, SgrAttribute::CrossedOut),
+            (self.font != 0, SgrAttribute::Font(self.font)),
+            (self.framed, SgrAttribute::Framed),
+            (self.encircled, SgrAttribute::Encircled),
+            (self.overline, SgrAttribute::Overline),
+            (self.superscript, SgrAttribute::Superscript),
+            (self.subscript, SgrAttribute::Subscript),
+        ];
+        for (set, attribute) in flags {
+            if set {
+                attributes.push(attribute);
+            }
+        }
+
+        if let Some(color) = self.foreground {
+            attributes.push(SgrAttribute::Foreground(color));
+        }
+        if let Some(color) = self.background {
+            attributes.push(SgrAttribute::Background(color));
+        }
+        if let Some(color) = self.underline_color {
+            attributes.push(SgrAttribute::UnderlineColor(color));
+        }
+
+        attributes
+    }
+
+    /// Escape sequence that switches a terminal from any state to this one
+    pub fn sequence(&self) -> String {
+        let mut params = vec!["0".to_string()];
+        params.extend(self.attributes().iter().map(|a| a.params()));
+        format!("\x1b[{}m", params.join(";"))
+    }
+}
@@ -0,0 +1,215 @@
+use super::osc::Terminator;
+
+// Longest payload shown verbatim in escape_repr, larger ones are abbreviated
+const MAX_REPR_DATA: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Introducer {
+    Dcs,            // ESC P
+    Apc,            // ESC _
+    Pm,             // ESC ^
+    Sos,            // ESC X
+}
+
+impl Introducer {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            b'P' => Some(Introducer::Dcs),
+            b'_' => Some(Introducer::Apc),
+            b'^' => Some(Introducer::Pm),
+            b'X' => Some(Introducer::Sos),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Introducer::Dcs => "DCS",
+            Introducer::Apc => "APC",
+            Introducer::Pm => "PM",
+            Introducer::Sos => "SOS",
+        }
+    }
+
+    pub fn escape_repr(&self) -> &'static str {
+        match self {
+            Introducer::Dcs => "\\x1bP",
+            Introducer::Apc => "\\x1b_",
+            Introducer::Pm => "\\x1b^",
+            Introducer::Sos => "\\x1bX",
+        }
+    }
+}
+
+/// A control string (DCS, APC, PM or SOS) running up to ST
+#[derive(Debug, Clone)]
+pub struct ControlString {
+    pub introducer: Introducer,
+    pub params: String,             // DCS parameter bytes (0x30-0x3F)
+    pub intermediates: String,      // DCS intermediate bytes (0x20-0x2F)
+    pub final_byte: Option<char>,   // DCS final byte, None for APC/PM/SOS
+    pub data: Vec<u8>,
+    pub terminator: Terminator,
+}
+
+impl ControlString {
+    pub fn escape_repr(&self) -> String {
+        let mut repr = String::from(self.introducer.escape_repr());
+        repr.push_str(&self.params);
+        repr.push_str(&self.intermediates);
+        if let Some(final_byte) = self.final_byte {
+            repr.push(final_byte);
+        }
+
+        if self.data.len() > MAX_REPR_DATA {
+            repr.push_str(&escape_bytes(&self.data[..MAX_REPR_DATA]));
+            repr.push_str(&format!("...({} bytes)", self.data.len()));
+        } else {
+            repr.push_str(&escape_bytes(&self.data));
+        }
+
+        repr.push_str(self.terminator.escape_repr());
+        repr
+    }
+
+    pub fn description(&self) -> String {
+        match self.introducer {
+            Introducer::Dcs => self.dcs_description(),
+            Introducer::Apc => {
+                if self.data.first() == Some(&b'G') {
+                    // Kitty graphics protocol: G<control data>;<payload>
+                    let data = String::from_utf8_lossy(&self.data[1..]);
+                    let (control, payload) = data.split_once(';').unwrap_or((&data, ""));
+                    format!("Kitty graphics command: {} ({} bytes of data)", control, payload.len())
+                } else {
+                    format!("Application program command ({} bytes)", self.data.len())
+                }
+            },
+            Introducer::Pm => format!("Privacy message ({} bytes)", self.data.len()),
+            Introducer::Sos => format!("Start of string ({} bytes)", self.data.len()),
+        }
+    }
+
+    fn dcs_description(&self) -> String {
+        let data = String::from_utf8_lossy(&self.data);
+
+        match (self.intermediates.as_str(), self.final_byte) {
+            ("$", Some('q')) => {
+                format!("Request setting (DECRQSS): {}", setting_name(&data))
+            },
+            ("$", Some('r')) => {
+                let valid = if self.params == "1" { "valid" } else { "invalid" };
+                format!("Setting report (DECRPSS, {}): {}", valid, data)
+            },
+            ("+", Some('q')) => {
+                format!("Request termcap/terminfo capabilities (XTGETTCAP): {}", decode_capabilities(&data))
+            },
+            ("+", Some('r')) => {
+                let valid = if self.params == "1" { "valid" } else { "invalid" };
+                let names: Vec<String> = data.split(';')
+                    .map(|entry| decode_hex(entry.split('=').next().unwrap_or("")))
+                    .collect();
+                format!("Capability report (XTGETTCAP, {}): {}", valid, names.join(", "))
+            },
+            ("", Some('q')) => {
+                let params: Vec<&str> = self.params.split(';').collect();
+                let mut details = Vec::new();
+                if let Some(aspect) = params.first().filter(|p| !p.is_empty()) {
+                    details.push(format!("aspect ratio mode {}", aspect));
+                }
+                if let Some(background) = params.get(1).filter(|p| !p.is_empty()) {
+                    let mode = if *background == "1" { "transparent" } else { "opaque" };
+                    details.push(format!("{} background", mode));
+                }
+                if details.is_empty() {
+                    format!("Sixel graphics ({} bytes)", self.data.len())
+                } else {
+                    format!("Sixel graphics ({} bytes, {})", self.data.len(), details.join(", "))
+                }
+            },
+            ("", Some('t')) if data.starts_with("mux;") => {
+                // Inner sequence has every ESC doubled
+                format!("tmux passthrough: {}", escape_bytes(&unescape_tmux(&self.data[4..])))
+            },
+            ("", Some('|')) => {
+                let params: Vec<&str> = self.params.split(';').collect();
+                let clear = match params.first().copied() {
+                    Some("1") => "clear matching keys",
+                    _ => "clear all keys",
+                };
+                let lock = match params.get(1).copied() {
+                    Some("1") => "unlocked",
+                    _ => "locked",
+                };
+                let keys = data.split(';').filter(|k| !k.is_empty()).count();
+                format!("Define user-defined keys (DECUDK): {} keys, {}, {}", keys, clear, lock)
+            },
+            _ => format!("Device control string ({} bytes)", self.data.len()),
+        }
+    }
+}
+
+// Collapse doubled ESC bytes inside tmux passthrough data
+fn unescape_tmux(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        result.push(data[i]);
+        if data[i] == 0x1B && data.get(i + 1) == Some(&0x1B) {
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    result
+}
+
+fn setting_name(setting: &str) -> String {
+    let name = match setting {
+        "m" => "graphic rendition (SGR)",
+        "r" => "top and bottom margins (DECSTBM)",
+        "s" => "left and right margins (DECSLRM)",
+        "t" => "lines per page (DECSLPP)",
+        " q" => "cursor style (DECSCUSR)",
+        "\"p" => "conformance level (DECSCL)",
+        "\"q" => "character protection attribute (DECSCA)",
+        "$|" => "columns per page (DECSCPP)",
+        "*|" => "lines per screen (DECSNLS)",
+        _ => return format!("unknown setting \"{}\"", setting),
+    };
+    name.to_string()
+}
+
+fn decode_capabilities(data: &str) -> String {
+    data.split(';')
+        .map(decode_hex)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// Decode a hex-encoded capability name, falling back to the raw text
+fn decode_hex(hex: &str) -> String {
+    if !hex.len().is_multiple_of(2) {
+        return hex.to_string();
+    }
+    let bytes: Option<Vec<u8>> = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect();
+    match bytes {
+        Some(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+        None => hex.to_string(),
+    }
+}
+
+fn escape_bytes(data: &[u8]) -> String {
+    let mut result = String::new();
+    for &byte in data {
+        match byte {
+            0x1B => result.push_str("\\x1b"),
+            0x20..=0x7E => result.push(byte as char),
+            _ => result.push_str(&format!("\\x{:02X}", byte)),
+        }
+    }
+    result
+}
@@ -0,0 +1,126 @@
+use crate::record::{Chunk, Recording, RecordingError};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+const VERSION: u64 = 2;
+
+/// Whether the start of a file is an asciicast v2 header line
+pub fn is_asciicast(start: &[u8]) -> bool {
+    let Some(line) = start.split(|&b| b == b'\n').next().filter(|_| start.contains(&b'\n')) else {
+        return false;
+    };
+    serde_json::from_slice::<Value>(line).is_ok_and(|header| header["version"] == VERSION)
+}
+
+/// Read an asciicast v2 file. Output events become chunks; input, resize and marker events are skipped.
+pub fn read(input: impl BufRead) -> Result<Recording, RecordingError> {
+    let mut lines = input.lines();
+    let header = lines.next().transpose()?.unwrap_or_default();
+    let invalid = || RecordingError::InvalidHeader(header.clone());
+
+    let value: Value = serde_json::from_str(&header).map_err(|_| invalid())?;
+    if value["version"] != VERSION {
+        return Err(invalid());
+    }
+    let size = |key: &str| value[key].as_u64().and_then(|n| u16::try_from(n).ok()).ok_or_else(invalid);
+    let mut recording = Recording::new(size("width")?, size("height")?);
+
+    for (number, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |reason: &str| RecordingError::InvalidEvent(number + 2, reason.to_string());
+        let event: Value = serde_json::from_str(&line).map_err(|err| invalid(&err.to_string()))?;
+        let (time, code, data) = match event.as_array().map(Vec::as_slice) {
+            Some([time, code, data]) => (time.as_f64(), code.as_str(), data.as_str()),
+            _ => return Err(invalid("expected [time, code, data]")),
+        };
+        let time = time.and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+            .ok_or_else(|| invalid("invalid time"))?;
+        if code == Some("o") {
+            let data = data.ok_or_else(|| invalid("data is not a string"))?;
+            recording.chunks.push(Chunk { time, data: data.as_bytes().to_vec() });
+        }
+    }
+
+    Ok(recording)
+}
+
+/// Write a recording as asciicast v2. A character split between chunks is written with
+/// the chunk that completes it; invalid UTF-8 becomes U+FFFD, as JSON strings can't hold it.
+pub fn write(recording: &Recording, output: &mut impl Write) -> io::Result<()> {
+    let header = json!({ "version": VERSION, "width": recording.columns, "height": recording.rows });
+    writeln!(output, "{}", header)?;
+
+    let mut pending: Vec<u8> = Vec::new();
+    let mut time = Duration::ZERO;
+    for chunk in &recording.chunks {
+        pending.extend_from_slice(&chunk.data);
+        time = chunk.time;
+        let complete = complete_len(&pending);
+        if complete > 0 {
+            write_event(output, time, &pending[..complete])?;
+            pending.drain(..complete);
+        }
+    }
+    if !pending.is_empty() {
+        write_event(output, time, &pending)?;
+    }
+
+    Ok(())
+}
+
+fn write_event(output: &mut impl Write, time: Duration, data: &[u8]) -> io::Result<()> {
+    // Whole microseconds keep the times short
+    let seconds = time.as_micros() as f64 / 1_000_000.0;
+    writeln!(output, "{}", json!([seconds, "o", String::from_utf8_lossy(data)]))
+}
+
+// Length of the bytes that don't end in the middle of a character
+fn complete_len(bytes: &[u8]) -> usize {
+    let mut start = 0;
+    loop {
+        match std::str::from_utf8(&bytes[start..]) {
+            Ok(_) => return bytes.len(),
+            Err(err) => match err.error_len() {
+                Some(len) => start += err.valid_up_to() + len,
+                None => return start + err.valid_up_to(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_casts_read_back() {
+        let mut recording = Recording::untimed(100, 30, b"a\x1b[1m".to_vec());
+        recording.chunks.push(Chunk { time: Duration::from_millis(1250), data: "b\u{e9}\n".as_bytes().to_vec() });
+        let mut cast = Vec::new();
+        write(&recording, &mut cast).unwrap();
+        assert!(is_asciicast(&cast));
+        assert_eq!(read(&cast[..]).unwrap(), recording);
+    }
+
+    #[test]
+    fn split_characters_go_with_the_completing_chunk() {
+        let mut recording = Recording::untimed(80, 24, vec![b'a', 0xc3]);
+        recording.chunks.push(Chunk { time: Duration::from_millis(500), data: vec![0xa9] });
+        let mut cast = Vec::new();
+        write(&recording, &mut cast).unwrap();
+        let events: Vec<&str> = std::str::from_utf8(&cast).unwrap().lines().skip(1).collect();
+        assert_eq!(events, ["[0.0,\"o\",\"a\"]", "[0.5,\"o\",\"\u{e9}\"]"]);
+    }
+
+    #[test]
+    fn only_output_events_are_read() {
+        let cast = "{\"version\": 2, \"width\": 80, \"height\": 24}\n[0.1, \"i\", \"x\"]\n[0.2, \"o\", \"y\"]\n[0.3, \"r\", \"90x30\"]\n";
+        let recording = read(cast.as_bytes()).unwrap();
+        assert_eq!(recording.chunks, [Chunk { time: Duration::from_millis(200), data: b"y".to_vec() }]);
+        assert!(read(&b"{\"version\": 1}\n"[..]).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use crate::ansi::{AnsiElement, Span, Spanned, csi::CSI, ctrl::ControlCharacter, osc::OSC};
+use crate::ansi::sgr::{SgrState, XTERM_PALETTE};
+use crate::parser::ParserError;
+use crate::sanitize::Category;
+use crate::screen::{Screen, DEFAULT_COLUMNS, DEFAULT_ROWS};
+use std::io::{self, Write};
+use termio::{Color, Decoration, StyledText};
+
+// Longest piece of text quoted in a finding
+const MAX_QUOTE: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Low => "LOW",
+            Severity::Medium => "MEDIUM",
+            Severity::High => "HIGH",
+        }
+    }
+}
+
+/// A sequence, or run of text, that could be abused
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+}
+
+// How the cursor got to where the next text is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Movement {
+    None,
+    Overstrike,     // CR or BS, as used by progress bars and man pages
+    Jump,           // CSI cursor movement
+}
+
+// Hyperlink opened by OSC 8 and the text shown for it so far
+struct OpenLink {
+    uri: String,
+    span: Span,
+    text: String,
+}
+
+/// Scans a stream for terminal-injection risks
+pub struct Auditor {
+    findings: Vec<Finding>,
+    screen: Screen,
+    title_set: Option<Span>,
+    link: Option<OpenLink>,
+    movement: Movement,
+}
+
+impl Auditor {
+    /// Audit for a terminal of the given size, which decides what text overwrites
+    pub fn new(columns: usize, rows: usize) -> Self {
+        let mut screen = Screen::new(columns, rows);
+        screen.set_newline_mode(true);
+        Self {
+            findings: Vec::new(),
+            screen,
+            title_set: None,
+            link: None,
+            movement: Movement::None,
+        }
+    }
+
+    /// Audit a whole stream, most severe findings first
+    pub fn audit(elements: &[Spanned]) -> Vec<Finding> {
+        let mut auditor = Self::default();
+        for spanned in elements {
+            auditor.feed(spanned);
+        }
+        auditor.finish()
+    }
+
+    /// Audit a whole stream as it is parsed and write the report shown by --audit
+    pub fn audit_stream(
+        mut self,
+        elements: impl Iterator<Item = Result<Spanned, ParserError>>,
+        output: &mut impl Write,
+        colorize: bool,
+    ) -> Result<(), ParserError> {
+        for element in elements {
+            self.feed(&element?);
+        }
+        write_report(&self.finish(), output, colorize)?;
+        Ok(())
+    }
+
+    pub fn feed(&mut self, spanned: &Spanned) {
+        let span = spanned.span;
+        match &spanned.element {
+            AnsiElement::Text(text) => self.text(text, span),
+            AnsiElement::Ctrl(ControlCharacter::CarriageReturn | ControlCharacter::Backspace, _) => {
+                self.movement = Movement::Overstrike;
+            },
+            AnsiElement::Ctrl(ControlCharacter::LineFeed, _) => self.movement = Movement::None,
+            AnsiElement::Csi(csi, _) => self.csi(csi, Category::of(&spanned.element), span),
+            AnsiElement::Osc(osc, _, _) => self.osc(osc, span),
+            AnsiElement::ControlString(string, _) => match (string.intermediates.as_str(), string.final_byte) {
+                ("$", Some('q')) => self.report(Severity::Medium, span, format!(
+                    "DECRQSS request: the terminal answers with the current setting, which can be echoed back as input ({})",
+                    spanned.element.escape_repr(),
+                )),
+                ("", Some('t')) if string.data.starts_with(b"mux;") => self.report(Severity::Medium, span,
+                    "tmux passthrough: the wrapped sequence reaches the outer terminal unfiltered".to_string()),
+                _ => {},
+            },
+            _ => {},
+        }
+        self.screen.apply(&spanned.element);
+    }
+
+    pub fn finish(mut self) -> Vec<Finding> {
+        self.close_link();
+        // Stable sort keeps stream order within a severity
+        self.findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+        self.findings
+    }
+
+    fn report(&mut self, severity: Severity, span: Span, message: String) {
+        self.findings.push(Finding { severity, span, message });
+    }
+
+    fn text(&mut self, text: &str, span: Span) {
+        if let Some(link) = &mut self.link {
+            link.text.push_str(text);
+        }
+
+        let visible = text.trim();
+        if !visible.is_empty() {
+            if self.screen.style().conceal {
+                self.report(Severity::Medium, span, format!("Concealed text (SGR 8): {}", quote(visible)));
+            } else if self.same_colors() {
+                self.report(Severity::Medium, span, format!("Text drawn in its background color: {}", quote(visible)));
+            }
+        }
+
+        if self.movement != Movement::None {
+            if let Some(previous) = self.overwritten(text) {
+                let (severity, how) = match self.movement {
+                    Movement::Jump => (Severity::Medium, "after a cursor jump"),
+                    _ => (Severity::Low, "after a carriage return or backspace"),
+                };
+                self.report(severity, span, format!(
+                    "Text overwrites previously printed {} {}: now {}",
+                    quote(&previous), how, quote(text),
+                ));
+            }
+        }
+        self.movement = Movement::None;
+    }
+
+    // Visible text already on screen where this text is about to be written
+    fn overwritten(&self, text: &str) -> Option<String> {
+        let (row, col) = self.screen.cursor();
+        let mut previous = String::new();
+        let mut changed = false;
+        for (offset, c) in text.chars().enumerate() {
+            let Some(cell) = self.screen.cell(row, col + offset) else { break };
+            if cell.text.trim().is_empty() {
+                continue;
+            }
+            previous.push_str(&cell.text);
+            changed |= cell.text != c.to_string();
+        }
+        changed.then_some(previous)
+    }
+
+    // Foreground and background explicitly set to the same color
+    fn same_colors(&self) -> bool {
+        let style = self.screen.style();
+        match (style.foreground, style.background) {
+            (Some(fg), Some(bg)) => fg.to_rgb(&XTERM_PALETTE) == bg.to_rgb(&XTERM_PALETTE),
+            _ => false,
+        }
+    }
+
+    fn csi(&mut self, csi: &CSI, category: Category, span: Span) {
+        // The screen follows the graphics state, including saves, restores and resets
+        if SgrState::default().apply_csi(csi) {
+            return;
+        }
+
+        match csi {
+            CSI::WindowManipulation(params) if matches!(params.first(), Some(20 | 21)) => {
+                match self.title_set {
+                    Some(set) => self.report(Severity::High, span, format!(
+                        "Title report after the title was set at {}..{}: the terminal types the chosen title into the program's input",
+                        set.start, set.end,
+                    )),
+                    None => self.report(Severity::Medium, span,
+                        "Title report: the terminal types its title into the program's input".to_string()),
+                }
+            },
+            CSI::WindowManipulation(_) if category == Category::Window => {
+                self.report(Severity::Low, span, format!("Window manipulation: {}", csi.description()));
+            },
+            CSI::CursorUp(_) |
+            CSI::CursorDown(_) |
+            CSI::CursorForward(_) |
+            CSI::CursorBackward(_) |
+            CSI::CursorPosition(..) |
+            CSI::CursorRestorePosition |
+            CSI::CursorToColumn(_) |
+            CSI::CursorNextLine(_) |
+            CSI::CursorPreviousLine(_) |
+            CSI::CursorToRow(_) |
+            CSI::CursorForwardTab(_) |
+            CSI::CursorBackwardTab(_) => self.movement = Movement::Jump,
+            _ if category == Category::Query => self.report(Severity::Low, span, format!(
+                "Terminal query, answered on the program's input: {}", csi.description(),
+            )),
+            _ => {},
+        }
+    }
+
+    fn osc(&mut self, osc: &OSC, span: Span) {
+        match osc {
+            OSC::SetIconNameAndTitle(_) | OSC::SetWindowTitle(_) | OSC::SetIconName(_) => {
+                self.title_set = Some(span);
+            },
+            OSC::Clipboard(selection, data) if data == "?" => {
+                self.report(Severity::High, span, format!("Clipboard read request for selection '{}'", selection));
+            },
+            OSC::Clipboard(_, _) => {
+                self.report(Severity::High, span, format!("Clipboard write: {}", osc.description()));
+            },
+            OSC::Hyperlink(_, uri) => {
+                self.close_link();
+                if !uri.is_empty() {
+                    self.link = Some(OpenLink { uri: uri.clone(), span, text: String::new() });
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // Compare the text shown for a link with where it actually points
+    fn close_link(&mut self) {
+        let Some(link) = self.link.take() else { return };
+
+        let target = host(&link.uri);
+        if let Some(shown) = shown_host(&link.text) {
+            if target.as_deref().map(normalize_host) != Some(normalize_host(&shown)) {
+                self.report(Severity::High, link.span, format!(
+                    "Hyperlink text {} disguises its target {}", quote(link.text.trim()), link.uri,
+                ));
+                return;
+            }
+        }
+
+        let scheme = link.uri.split_once(':').map(|(scheme, _)| scheme.to_lowercase());
+        if !matches!(scheme.as_deref(), Some("http" | "https" | "mailto")) {
+            self.report(Severity::Low, link.span, format!("Hyperlink to non-web URI: {}", link.uri));
+        }
+    }
+}
+
+impl Default for Auditor {
+    fn default() -> Self {
+        Self::new(DEFAULT_COLUMNS, DEFAULT_ROWS)
+    }
+}
+
+// Host part of a URI, skipping any user info
+fn host(uri: &str) -> Option<String> {
+    let (_, rest) = uri.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = authority.rsplit('@').next().unwrap_or("");
+    let host = host.split(':').next().unwrap_or("");
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+// Host named by link text that looks like a URL or a bare domain
+fn shown_host(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.contains("://") {
+        return host(text);
+    }
+    let candidate = text.split('/').next().unwrap_or("");
+    let looks_like_domain = candidate.contains('.')
+        && !candidate.starts_with('.')
+        && !candidate.ends_with('.')
+        && candidate.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        && candidate.chars().any(|c| c.is_ascii_alphabetic());
+    looks_like_domain.then(|| candidate.to_string())
+}
+
+fn normalize_host(host: &str) -> String {
+    let host = host.to_lowercase();
+    host.strip_prefix("www.").map(str::to_string).unwrap_or(host)
+}
+
+/// Write each finding with its severity, then a count per severity
+pub fn write_report(findings: &[Finding], output: &mut impl Write, colorize: bool) -> io::Result<()> {
+    if findings.is_empty() {
+        return writeln!(output, "No findings");
+    }
+
+    for finding in findings {
+        let severity = format!("{:<6}", finding.severity.name());
+        let severity = if colorize {
+            let color = match finding.severity {
+                Severity::High => Color::Red,
+                Severity::Medium => Color::Yellow,
+                Severity::Low => Color::IntenseBlack,
+            };
+            severity.color(color).decoration(Decoration::Bold).to_string()
+        } else {
+            severity
+        };
+        let offset = format!("{}..{}", finding.span.start, finding.span.end);
+        writeln!(output, "{}  {:<12} {}", severity, offset, finding.message)?;
+    }
+
+    let count = |severity| findings.iter().filter(|f| f.severity == severity).count();
+    writeln!(output, "\n{} finding(s): {} high, {} medium, {} low",
+        findings.len(), count(Severity::High), count(Severity::Medium), count(Severity::Low))
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted: String = text.chars().take(MAX_QUOTE).collect();
+    if text.chars().count() > MAX_QUOTE {
+        quoted.push_str("...");
+    }
+    format!("{:?}", quoted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::AnsiParser;
+
+    fn messages(input: &[u8]) -> Vec<String> {
+        Auditor::audit(&AnsiParser::parse_spanned(input).unwrap())
+            .into_iter()
+            .map(|finding| finding.message)
+            .collect()
+    }
+
+    fn concealed(input: &[u8]) -> bool {
+        messages(input).iter().any(|message| message.starts_with("Concealed text"))
+    }
+
+    #[test]
+    fn concealed_text_is_reported() {
+        assert!(concealed(b"\x1b[8msecret"));
+        assert!(!concealed(b"\x1b[8m\x1b[28mshown"));
+    }
+
+    #[test]
+    fn restored_style_is_followed() {
+        assert!(concealed(b"\x1b[8m\x1b7\x1b[0mvisible\x1b8secret"));
+        assert!(concealed(b"\x1b[8m\x1b[s\x1b[0mvisible\x1b[usecret"));
+        assert!(concealed(b"\x1b[8m\x1b[?1049h\x1b[0mvisible\x1b[?1049lsecret"));
+    }
+
+    #[test]
+    fn resets_clear_the_style() {
+        assert!(!concealed(b"\x1b[8m\x1bcshown"));
+        assert!(!concealed(b"\x1b[8m\x1b[!pshown"));
+    }
+
+    #[test]
+    fn report_counts_findings_by_severity() {
+        let mut output = Vec::new();
+        Auditor::default().audit_stream(AnsiParser::spanned_elements(&b"\x1b[8mx"[..]), &mut output, false).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(),
+            "MEDIUM  4..5         Concealed text (SGR 8): \"x\"\n\n1 finding(s): 0 high, 1 medium, 0 low\n");
+
+        let mut output = Vec::new();
+        Auditor::default().audit_stream(AnsiParser::spanned_elements(&b"plain"[..]), &mut output, false).unwrap();
+        assert_eq!(output, b"No findings\n");
+    }
+}
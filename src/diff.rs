@@ -0,0 +1,579 @@
+use crate::ansi::{AnsiElement, Span, Spanned, csi::CSI};
+use crate::ansi::sgr::{SgrAttribute, SgrColor, SgrState, UnderlineStyle};
+use crate::screen::{Cell, Screen};
+use std::io::{self, Write};
+use termio::{Color, StyledText};
+
+/// What happened to a token between the old and the new stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifferenceKind {
+    Removed,
+    Inserted,
+    Changed,
+}
+
+impl DifferenceKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DifferenceKind::Removed => "removed",
+            DifferenceKind::Inserted => "inserted",
+            DifferenceKind::Changed => "changed",
+        }
+    }
+}
+
+/// A unit of the diff: one element, or a run of text or of SGR sequences taken together
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub span: Span,
+    pub escape: String,
+    pub description: String,
+    kind: &'static str,     // Tokens only count as changed into tokens of the same kind
+    key: Key,
+}
+
+// What a token does, so spellings with the same effect compare equal
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Key {
+    Text(String),
+    // The run applied to two states that differ in every field, which tells apart
+    // every combination of attributes, plus parameters the state can't hold
+    Sgr(SgrState, SgrState, Vec<String>),
+    Other(Vec<u8>),     // Canonical bytes
+}
+
+#[derive(Debug, Clone)]
+pub struct Difference {
+    pub kind: DifferenceKind,
+    pub old: Option<Token>,
+    pub new: Option<Token>,
+}
+
+/// Align two streams and list the tokens removed, inserted and changed
+pub fn diff(old: &[Spanned], new: &[Spanned]) -> Vec<Difference> {
+    let old = tokens(old);
+    let new = tokens(new);
+    let old_keys: Vec<&Key> = old.iter().map(|t| &t.key).collect();
+    let new_keys: Vec<&Key> = new.iter().map(|t| &t.key).collect();
+
+    let mut differences = Vec::new();
+    let (mut removed, mut inserted) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    for edit in edit_script(&old_keys, &new_keys) {
+        match edit {
+            Edit::Delete => {
+                removed.push(old[i].clone());
+                i += 1;
+            },
+            Edit::Insert => {
+                inserted.push(new[j].clone());
+                j += 1;
+            },
+            Edit::Equal => {
+                pair(&mut removed, &mut inserted, &mut differences);
+                i += 1;
+                j += 1;
+            },
+        }
+    }
+    pair(&mut removed, &mut inserted, &mut differences);
+
+    differences
+}
+
+// Between the same two matches, removed and inserted tokens are aligned again by kind,
+// and the aligned pairs count as changed
+fn pair(removed: &mut Vec<Token>, inserted: &mut Vec<Token>, differences: &mut Vec<Difference>) {
+    let old_kinds: Vec<&str> = removed.iter().map(|t| t.kind).collect();
+    let new_kinds: Vec<&str> = inserted.iter().map(|t| t.kind).collect();
+    let edits = edit_script(&old_kinds, &new_kinds);
+
+    let mut removed = removed.drain(..);
+    let mut inserted = inserted.drain(..);
+    for edit in edits {
+        differences.push(match edit {
+            Edit::Equal => Difference { kind: DifferenceKind::Changed, old: removed.next(), new: inserted.next() },
+            Edit::Delete => Difference { kind: DifferenceKind::Removed, old: removed.next(), new: None },
+            Edit::Insert => Difference { kind: DifferenceKind::Inserted, old: None, new: inserted.next() },
+        });
+    }
+}
+
+/// Group a stream into diff tokens: adjacent text, and adjacent SGR sequences, become one token each
+pub fn tokens(elements: &[Spanned]) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut group: Vec<&Spanned> = Vec::new();
+
+    for spanned in elements {
+        let joins = match (group.first().map(|first| &first.element), &spanned.element) {
+            (Some(AnsiElement::Text(_)), AnsiElement::Text(_)) => true,
+            (Some(AnsiElement::Csi(first, _)), AnsiElement::Csi(csi, _)) => is_sgr(first) && is_sgr(csi),
+            _ => false,
+        };
+        if !joins && !group.is_empty() {
+            tokens.push(token(&group));
+            group.clear();
+        }
+        group.push(spanned);
+    }
+    if !group.is_empty() {
+        tokens.push(token(&group));
+    }
+
+    tokens
+}
+
+fn token(group: &[&Spanned]) -> Token {
+    let first = group[0];
+    let span = Span { end: group[group.len() - 1].span.end, ..first.span };
+
+    match &first.element {
+        AnsiElement::Text(_) => {
+            let text: String = group.iter()
+                .filter_map(|s| match &s.element {
+                    AnsiElement::Text(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect();
+            let element = AnsiElement::Text(text.clone());
+            Token {
+                span,
+                escape: element.escape_repr(),
+                description: element.description(),
+                kind: "Text",
+                key: Key::Text(text),
+            }
+        },
+        AnsiElement::Csi(csi, _) if is_sgr(csi) => {
+            let (mut plain, mut probe) = (SgrState::default(), all_set());
+            let mut unknown = Vec::new();
+            for spanned in group {
+                if let AnsiElement::Csi(csi, _) = &spanned.element {
+                    plain.apply_csi(csi);
+                    probe.apply_csi(csi);
+                    if let CSI::SetGraphicsMode(attributes) = csi {
+                        unknown.extend(attributes.iter().filter_map(|a| match a {
+                            SgrAttribute::Unknown(params) => Some(params.clone()),
+                            _ => None,
+                        }));
+                    }
+                }
+            }
+            Token {
+                span,
+                escape: group.iter().map(|s| s.element.escape_repr()).collect(),
+                description: group.iter().map(|s| s.element.description()).collect::<Vec<String>>().join("; "),
+                kind: "SGR",
+                key: Key::Sgr(plain, probe, unknown),
+            }
+        },
+        element => Token {
+            span,
+            escape: element.escape_repr(),
+            description: element.description(),
+            kind: kind(element),
+            key: Key::Other(element.canonical_bytes()),
+        },
+    }
+}
+
+fn kind(element: &AnsiElement) -> &'static str {
+    match element {
+        // Setting a mode and resetting it are changes of one another
+        AnsiElement::Csi(CSI::SetMode(_) | CSI::ResetMode(_), _) => "SM",
+        AnsiElement::Csi(CSI::SetPrivateMode(_) | CSI::ResetPrivateMode(_), _) => "DECSET",
+        AnsiElement::Csi(csi, _) => csi.mnemonic(),
+        element => element.element_type(),
+    }
+}
+
+// A state with every field away from its default
+fn all_set() -> SgrState {
+    SgrState {
+        foreground: Some(SgrColor::Standard(0)),
+        background: Some(SgrColor::Standard(0)),
+        underline_color: Some(SgrColor::Standard(0)),
+        bold: true,
+        faint: true,
+        italic: true,
+        underline: UnderlineStyle::Single,
+        slow_blink: true,
+        rapid_blink: true,
+        reverse: true,
+        conceal: true,
+        crossed_out: true,
+        font: 1,
+        framed: true,
+        encircled: true,
+        overline: true,
+        superscript: true,
+        subscript: true,
+    }
+}
+
+fn is_sgr(csi: &CSI) -> bool {
+    SgrState::default().apply_csi(csi)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+// Shortest edit script by Myers' algorithm, after trimming the common prefix and suffix
+fn edit_script<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let offset = n + m + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // Furthest x on each diagonal before each round, kept only for the diagonals the round can reach
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=n + m {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) { v[i + 1] } else { v[i - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut edits = vec![Edit::Equal; suffix];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            edits.push(Edit::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            edits.push(if x == previous_x { Edit::Insert } else { Edit::Delete });
+        }
+        (x, y) = (previous_x, previous_y);
+    }
+    edits.extend(std::iter::repeat_n(Edit::Equal, prefix));
+    edits.reverse();
+
+    edits
+}
+
+/// A difference between two rendered screens
+#[derive(Debug, Clone)]
+pub enum ScreenDifference {
+    Text { row: usize, old: String, new: String },
+    Style { row: usize, first: usize, last: usize },    // Same text, styled differently from column first to last
+    Cursor { old: (usize, usize), new: (usize, usize) },
+    Title { old: String, new: String },
+    AlternateScreen { old: bool, new: bool },
+}
+
+/// Compare what two screens show, row by row
+pub fn diff_screens(old: &Screen, new: &Screen) -> Vec<ScreenDifference> {
+    let mut differences = Vec::new();
+    let (old_text, new_text) = (old.text(), new.text());
+
+    for (row, (old_line, new_line)) in old.lines().iter().zip(new.lines()).enumerate() {
+        if old_text[row] != new_text[row] {
+            differences.push(ScreenDifference::Text {
+                row,
+                old: old_text[row].clone(),
+                new: new_text[row].clone(),
+            });
+            continue;
+        }
+        let styled = |(_, (a, b)): &(usize, (&Cell, &Cell))| a.style != b.style;
+        let first = old_line.iter().zip(new_line).enumerate().find(styled);
+        let last = old_line.iter().zip(new_line).enumerate().rfind(styled);
+        if let (Some((first, _)), Some((last, _))) = (first, last) {
+            differences.push(ScreenDifference::Style { row, first, last });
+        }
+    }
+
+    if old.cursor() != new.cursor() {
+        differences.push(ScreenDifference::Cursor { old: old.cursor(), new: new.cursor() });
+    }
+    if old.title() != new.title() {
+        differences.push(ScreenDifference::Title { old: old.title().to_string(), new: new.title().to_string() });
+    }
+    if old.is_alternate_screen() != new.is_alternate_screen() {
+        differences.push(ScreenDifference::AlternateScreen {
+            old: old.is_alternate_screen(),
+            new: new.is_alternate_screen(),
+        });
+    }
+
+    differences
+}
+
+/// Compare the screens two streams leave on a terminal of the given size
+pub fn diff_rendered(old: &[Spanned], new: &[Spanned], columns: usize, rows: usize) -> Vec<ScreenDifference> {
+    let replay = |elements: &[Spanned]| {
+        let mut screen = Screen::new(columns, rows);
+        screen.set_newline_mode(true);
+        screen.apply_all(elements.iter().map(|spanned| &spanned.element));
+        screen
+    };
+    diff_screens(&replay(old), &replay(new))
+}
+
+/// Write the element differences shown by the diff subcommand, with a count of each kind
+pub fn write_report(differences: &[Difference], output: &mut impl Write, colorize: bool) -> io::Result<()> {
+    if differences.is_empty() {
+        return writeln!(output, "No differences");
+    }
+
+    let offset = |token: &Option<Token>| token.as_ref()
+        .map_or("-".to_string(), |token| format!("{}..{}", token.span.start, token.span.end));
+    for difference in differences {
+        let kind = format!("{:<8}", difference.kind.name());
+        let kind = if colorize {
+            let color = match difference.kind {
+                DifferenceKind::Removed => Color::Red,
+                DifferenceKind::Inserted => Color::Green,
+                DifferenceKind::Changed => Color::Yellow,
+            };
+            kind.color(color).to_string()
+        } else {
+            kind
+        };
+        let (escape, description) = match (&difference.old, &difference.new) {
+            (Some(old), Some(new)) => (
+                format!("{} -> {}", old.escape, new.escape),
+                format!("{} -> {}", old.description, new.description),
+            ),
+            (Some(token), None) | (None, Some(token)) => (token.escape.clone(), token.description.clone()),
+            (None, None) => continue,
+        };
+        writeln!(output, "{} {:<12} {:<12} {:<20} {}",
+            kind, offset(&difference.old), offset(&difference.new), escape, description)?;
+    }
+
+    let count = |kind| differences.iter().filter(|d| d.kind == kind).count();
+    writeln!(output, "\n{} removed, {} inserted, {} changed",
+        count(DifferenceKind::Removed), count(DifferenceKind::Inserted), count(DifferenceKind::Changed))
+}
+
+/// Write the screen differences shown by the diff subcommand with --screen.
+/// Rows and columns are 1-based, as in cursor positioning sequences.
+pub fn write_screen_report(differences: &[ScreenDifference], output: &mut impl Write) -> io::Result<()> {
+    if differences.is_empty() {
+        return writeln!(output, "Screens are identical");
+    }
+
+    for difference in differences {
+        match difference {
+            ScreenDifference::Text { row, old, new } => {
+                writeln!(output, "row {}:", row + 1)?;
+                writeln!(output, "  - {}", old)?;
+                writeln!(output, "  + {}", new)?;
+            },
+            ScreenDifference::Style { row, first, last } => {
+                writeln!(output, "row {}: same text, styled differently in columns {}-{}", row + 1, first + 1, last + 1)?;
+            },
+            ScreenDifference::Cursor { old, new } => {
+                writeln!(output, "cursor: {},{} -> {},{}", old.0 + 1, old.1 + 1, new.0 + 1, new.1 + 1)?;
+            },
+            ScreenDifference::Title { old, new } => writeln!(output, "title: {:?} -> {:?}", old, new)?,
+            ScreenDifference::AlternateScreen { old, new } => {
+                let active = |active: bool| if active { "active" } else { "inactive" };
+                writeln!(output, "alternate screen: {} -> {}", active(*old), active(*new))?;
+            },
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::AnsiParser;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    // Check that the edits turn old into new, and return how many were not Equal
+    fn check(old: &str, new: &str) -> usize {
+        let (old, new) = (chars(old), chars(new));
+        let edits = edit_script(&old, &new);
+        let (mut i, mut j) = (0, 0);
+        let mut result = Vec::new();
+        for edit in &edits {
+            match edit {
+                Edit::Equal => {
+                    assert_eq!(old[i], new[j], "Equal edit pairs different items");
+                    result.push(old[i]);
+                    i += 1;
+                    j += 1;
+                },
+                Edit::Delete => i += 1,
+                Edit::Insert => {
+                    result.push(new[j]);
+                    j += 1;
+                },
+            }
+        }
+        assert_eq!((i, j), (old.len(), new.len()), "edits don't cover both sides");
+        assert_eq!(result, new);
+        edits.iter().filter(|edit| **edit != Edit::Equal).count()
+    }
+
+    fn stream_diff(old: &[u8], new: &[u8]) -> Vec<Difference> {
+        diff(&AnsiParser::parse_spanned(old).unwrap(), &AnsiParser::parse_spanned(new).unwrap())
+    }
+
+    #[test]
+    fn edit_script_empty() {
+        assert!(edit_script::<char>(&[], &[]).is_empty());
+        assert_eq!(edit_script(&[], &chars("ab")), vec![Edit::Insert, Edit::Insert]);
+        assert_eq!(edit_script(&chars("ab"), &[]), vec![Edit::Delete, Edit::Delete]);
+    }
+
+    #[test]
+    fn edit_script_common_prefix() {
+        assert_eq!(edit_script(&chars("abc"), &chars("abcde")), vec![
+            Edit::Equal, Edit::Equal, Edit::Equal, Edit::Insert, Edit::Insert,
+        ]);
+        assert_eq!(edit_script(&chars("abcde"), &chars("abc")), vec![
+            Edit::Equal, Edit::Equal, Edit::Equal, Edit::Delete, Edit::Delete,
+        ]);
+    }
+
+    #[test]
+    fn edit_script_common_suffix() {
+        assert_eq!(edit_script(&chars("xyabc"), &chars("abc")), vec![
+            Edit::Delete, Edit::Delete, Edit::Equal, Edit::Equal, Edit::Equal,
+        ]);
+        assert_eq!(edit_script(&chars("abc"), &chars("xabc")), vec![
+            Edit::Insert, Edit::Equal, Edit::Equal, Edit::Equal,
+        ]);
+    }
+
+    #[test]
+    fn edit_script_mixed() {
+        assert_eq!(check("abc", "abc"), 0);
+        assert_eq!(check("abc", "xyz"), 6);
+        // The example from Myers' paper, with an edit distance of 5
+        assert_eq!(check("abcabba", "cbabac"), 5);
+        assert_eq!(check("a1b2c3", "ab12c3x"), 3);
+        assert_eq!(check("the quick brown fox", "a quick brown dog"), 8);
+    }
+
+    #[test]
+    fn edit_script_is_shortest() {
+        // Edit distance from the longest common subsequence, by dynamic programming
+        fn distance(old: &[char], new: &[char]) -> usize {
+            let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+            for i in 0..old.len() {
+                for j in 0..new.len() {
+                    lcs[i + 1][j + 1] = if old[i] == new[j] { lcs[i][j] + 1 } else { lcs[i][j + 1].max(lcs[i + 1][j]) };
+                }
+            }
+            old.len() + new.len() - 2 * lcs[old.len()][new.len()]
+        }
+
+        // Every pair of strings over a small alphabet up to length 5
+        let strings: Vec<String> = (0..6u32)
+            .flat_map(|len| (0..3u32.pow(len)).map(move |n| {
+                (0..len).map(|k| (b'a' + (n / 3u32.pow(k) % 3) as u8) as char).collect()
+            }))
+            .collect();
+        for old in strings.iter().step_by(7) {
+            for new in &strings {
+                assert_eq!(check(old, new), distance(&chars(old), &chars(new)), "{:?} -> {:?}", old, new);
+            }
+        }
+    }
+
+    #[test]
+    fn identical_streams_have_no_differences() {
+        assert!(stream_diff(b"a\x1b[1mb\x1b[0m", b"a\x1b[1mb\x1b[0m").is_empty());
+        assert!(stream_diff(b"", b"").is_empty());
+    }
+
+    #[test]
+    fn equivalent_spellings_have_no_differences() {
+        assert!(stream_diff(b"\x1b[m", b"\x1b[0m").is_empty());
+        assert!(stream_diff(b"\x1b[H", b"\x1b[1;1H").is_empty());
+        assert!(stream_diff(b"\x1b[1;31mx", b"\x1b[31;1mx").is_empty());
+        assert!(stream_diff(b"\x1b[1;31mx", b"\x1b[31m\x1b[1mx").is_empty());
+        // Text split differently between elements is still the same text
+        assert!(stream_diff(b"ab\x1b[Hcd", b"ab\x1b[1;1Hcd").is_empty());
+    }
+
+    #[test]
+    fn different_sgr_is_a_change() {
+        let differences = stream_diff(b"a\x1b[31mb", b"a\x1b[32mb");
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].kind, DifferenceKind::Changed);
+        assert_eq!(differences[0].old.as_ref().unwrap().escape, "\\x1b[31m");
+        assert_eq!(differences[0].new.as_ref().unwrap().escape, "\\x1b[32m");
+    }
+
+    #[test]
+    fn removed_and_inserted_elements() {
+        // Sequences of different kinds are never changes of one another
+        let differences = stream_diff(b"a\x1b[2Jb", b"a\x1b[Kb");
+        let kinds: Vec<DifferenceKind> = differences.iter().map(|d| d.kind).collect();
+        assert_eq!(kinds, vec![DifferenceKind::Removed, DifferenceKind::Inserted]);
+        assert_eq!(differences[0].old.as_ref().unwrap().escape, "\\x1b[2J");
+        assert_eq!(differences[1].new.as_ref().unwrap().escape, "\\x1b[0K");
+
+        let differences = stream_diff(b"x\x1b[1m", b"x\x1b[1m\x1b[?25l");
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].kind, DifferenceKind::Inserted);
+        assert_eq!(differences[0].new.as_ref().unwrap().escape, "\\x1b[?25l");
+    }
+
+    #[test]
+    fn mode_set_and_reset_are_changes() {
+        let differences = stream_diff(b"\x1b[?25h", b"\x1b[?25l");
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].kind, DifferenceKind::Changed);
+    }
+
+    #[test]
+    fn report_lists_differences_with_their_offsets() {
+        let mut output = Vec::new();
+        write_report(&stream_diff(b"a\x1b[31mb", b"a\x1b[32mb\x1b[?25l"), &mut output, false).unwrap();
+        let report = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[0].starts_with("changed  1..6         1..6         \\x1b[31m -> \\x1b[32m"), "{}", report);
+        assert!(lines[1].starts_with("inserted -            7..13        \\x1b[?25l"), "{}", report);
+        assert_eq!(lines[3], "0 removed, 1 inserted, 1 changed");
+    }
+
+    #[test]
+    fn screen_report_is_one_based() {
+        let parse = |input: &[u8]| AnsiParser::parse_spanned(input).unwrap();
+        let differences = diff_rendered(&parse(b"ab"), &parse(b"\x1b[2;3Hab"), 10, 4);
+        let mut output = Vec::new();
+        write_screen_report(&differences, &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "row 1:\n  - ab\n  + \nrow 2:\n  - \n  +   ab\ncursor: 1,3 -> 2,5\n");
+
+        let mut output = Vec::new();
+        write_screen_report(&diff_rendered(&parse(b"\x1b[1;1Hx"), &parse(b"x"), 10, 4), &mut output).unwrap();
+        assert_eq!(output, b"Screens are identical\n");
+    }
+}
@@ -0,0 +1,592 @@
+use crate::ansi::{AnsiElement, csi::CSI, ctrl::ControlCharacter, osc::{OSC, Terminator}};
+use crate::ansi::sgr::{SgrAttribute, SgrColor, UnderlineStyle};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum EncodeError {
+    #[error("Unrecognized instruction: {0}")]
+    UnknownInstruction(String),
+
+    #[error("Invalid argument in \"{0}\": {1}")]
+    InvalidArgument(String, String),
+}
+
+/// How generated bytes are written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notation {
+    Raw,        // The bytes themselves
+    Printf,     // Format string for printf(1), safe inside single quotes
+    Rust,       // Rust string literal
+    C,          // C string literal
+    Python,     // Python string literal
+}
+
+impl Notation {
+    pub const NAMES: [&'static str; 5] = ["raw", "printf", "rust", "c", "python"];
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "raw" => Some(Notation::Raw),
+            "printf" => Some(Notation::Printf),
+            "rust" => Some(Notation::Rust),
+            "c" => Some(Notation::C),
+            "python" => Some(Notation::Python),
+            _ => None,
+        }
+    }
+
+    /// Write bytes in this notation
+    pub fn write(&self, bytes: &[u8]) -> String {
+        let text = String::from_utf8_lossy(bytes);
+        match self {
+            Notation::Raw => text.into_owned(),
+            Notation::Printf => text.chars().map(printf_escape).collect(),
+            Notation::Rust | Notation::Python => {
+                format!("\"{}\"", text.chars().map(hex_escape).collect::<String>())
+            },
+            Notation::C => format!("\"{}\"", text.chars().map(octal_escape).collect::<String>()),
+        }
+    }
+}
+
+/// Builds a stream of escape sequences and text
+#[derive(Debug, Clone, Default)]
+pub struct Encoder {
+    elements: Vec<AnsiElement>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self { elements: Vec::new() }
+    }
+
+    pub fn element(mut self, element: AnsiElement) -> Self {
+        self.elements.push(element);
+        self
+    }
+
+    pub fn csi(self, csi: CSI) -> Self {
+        self.element(AnsiElement::Csi(csi, None))
+    }
+
+    pub fn osc(self, osc: OSC) -> Self {
+        self.element(AnsiElement::Osc(osc, Terminator::St, None))
+    }
+
+    pub fn ctrl(self, ctrl: ControlCharacter) -> Self {
+        self.element(AnsiElement::Ctrl(ctrl, None))
+    }
+
+    pub fn text(self, text: &str) -> Self {
+        self.element(AnsiElement::Text(text.to_string()))
+    }
+
+    /// Add a graphics attribute, joining it to an SGR sequence just before it
+    pub fn sgr(mut self, attribute: SgrAttribute) -> Self {
+        if let Some(AnsiElement::Csi(CSI::SetGraphicsMode(attributes), _)) = self.elements.last_mut() {
+            attributes.push(attribute);
+            return self;
+        }
+        self.csi(CSI::SetGraphicsMode(vec![attribute]))
+    }
+
+    pub fn reset(self) -> Self {
+        self.sgr(SgrAttribute::Reset)
+    }
+
+    pub fn bold(self) -> Self {
+        self.sgr(SgrAttribute::Bold)
+    }
+
+    pub fn faint(self) -> Self {
+        self.sgr(SgrAttribute::Faint)
+    }
+
+    pub fn italic(self) -> Self {
+        self.sgr(SgrAttribute::Italic)
+    }
+
+    pub fn underline(self) -> Self {
+        self.sgr(SgrAttribute::Underline(UnderlineStyle::Single))
+    }
+
+    pub fn reverse(self) -> Self {
+        self.sgr(SgrAttribute::Reverse)
+    }
+
+    pub fn fg(self, color: SgrColor) -> Self {
+        self.sgr(SgrAttribute::Foreground(color))
+    }
+
+    pub fn bg(self, color: SgrColor) -> Self {
+        self.sgr(SgrAttribute::Background(color))
+    }
+
+    /// Move the cursor to a 1-based row and column
+    pub fn cursor_to(self, row: u32, col: u32) -> Self {
+        self.csi(CSI::CursorPosition(row, col))
+    }
+
+    pub fn cursor_up(self, n: u32) -> Self {
+        self.csi(CSI::CursorUp(n))
+    }
+
+    pub fn cursor_down(self, n: u32) -> Self {
+        self.csi(CSI::CursorDown(n))
+    }
+
+    pub fn cursor_forward(self, n: u32) -> Self {
+        self.csi(CSI::CursorForward(n))
+    }
+
+    pub fn cursor_backward(self, n: u32) -> Self {
+        self.csi(CSI::CursorBackward(n))
+    }
+
+    pub fn clear_screen(self) -> Self {
+        self.csi(CSI::EraseInDisplay(2))
+    }
+
+    pub fn clear_line(self) -> Self {
+        self.csi(CSI::EraseInLine(2))
+    }
+
+    pub fn show_cursor(self, visible: bool) -> Self {
+        self.private_mode(25, visible)
+    }
+
+    pub fn alternate_screen(self, enabled: bool) -> Self {
+        self.private_mode(1049, enabled)
+    }
+
+    /// DECSET or DECRST of a single mode
+    pub fn private_mode(self, mode: u16, enabled: bool) -> Self {
+        if enabled {
+            self.csi(CSI::SetPrivateMode(vec![mode]))
+        } else {
+            self.csi(CSI::ResetPrivateMode(vec![mode]))
+        }
+    }
+
+    pub fn title(self, title: &str) -> Self {
+        self.osc(OSC::SetWindowTitle(title.to_string()))
+    }
+
+    /// Start a hyperlink, or end the current one with an empty URI
+    pub fn hyperlink(self, uri: &str) -> Self {
+        self.osc(OSC::Hyperlink(String::new(), uri.to_string()))
+    }
+
+    pub fn elements(&self) -> &[AnsiElement] {
+        &self.elements
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for element in &self.elements {
+            bytes.extend_from_slice(&element.to_bytes());
+        }
+        bytes
+    }
+
+    pub fn format(&self, notation: Notation) -> String {
+        notation.write(&self.to_bytes())
+    }
+
+    /// Build from comma-separated instructions such as "bold red fg, cursor to 5,10"
+    pub fn from_description(description: &str) -> Result<Self, EncodeError> {
+        let mut encoder = Self::new();
+        for clause in clauses(description) {
+            encoder = encoder.instruction(&clause)?;
+        }
+        Ok(encoder)
+    }
+
+    fn instruction(self, clause: &str) -> Result<Self, EncodeError> {
+        // Quoted text is written as is
+        if let Some(text) = unquote(clause) {
+            return Ok(self.text(&text));
+        }
+
+        let (first, rest) = clause.split_once(char::is_whitespace).unwrap_or((clause, ""));
+        let rest = rest.trim();
+        match first.to_lowercase().as_str() {
+            "text" => return Ok(self.text(&unquote(rest).unwrap_or_else(|| rest.to_string()))),
+            "title" => return Ok(self.title(&unquote(rest).unwrap_or_else(|| rest.to_string()))),
+            "link" => return Ok(self.hyperlink(rest)),
+            _ => {},
+        }
+
+        let lower = clause.to_lowercase();
+        let words: Vec<&str> = lower.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|w| !w.is_empty())
+            .collect();
+        let invalid = |message: &str| EncodeError::InvalidArgument(clause.to_string(), message.to_string());
+
+        // "cursor" and "move" are optional in front of cursor instructions
+        let motion = match words.first() {
+            Some(&"cursor") | Some(&"move") => &words[1..],
+            _ => &words[..],
+        };
+        let count = |index: usize| -> Result<u32, EncodeError> {
+            motion.get(index).map_or(Ok(1), |n| n.parse().map_err(|_| invalid("expected a count")))
+        };
+
+        let encoder = match motion {
+            ["to", row, col] | ["to", "row", row, "col" | "column", col] => {
+                let row = row.parse().map_err(|_| invalid("expected a row number"))?;
+                let col = col.parse().map_err(|_| invalid("expected a column number"))?;
+                self.cursor_to(row, col)
+            },
+            ["home"] => self.cursor_to(1, 1),
+            ["up", ..] if motion.len() <= 2 => self.cursor_up(count(1)?),
+            ["down", ..] if motion.len() <= 2 => self.cursor_down(count(1)?),
+            ["right" | "forward", ..] if motion.len() <= 2 => self.cursor_forward(count(1)?),
+            ["left" | "back" | "backward", ..] if motion.len() <= 2 => self.cursor_backward(count(1)?),
+            ["column" | "col", n] => self.csi(CSI::CursorToColumn(n.parse().map_err(|_| invalid("expected a column number"))?)),
+            ["row", n] => self.csi(CSI::CursorToRow(n.parse().map_err(|_| invalid("expected a row number"))?)),
+            ["save"] | ["save", "cursor"] => self.csi(CSI::CursorSavePosition),
+            ["restore"] | ["restore", "cursor"] => self.csi(CSI::CursorRestorePosition),
+            ["hide"] | ["hide", "cursor"] => self.show_cursor(false),
+            ["show"] | ["show", "cursor"] => self.show_cursor(true),
+            _ => return self.other_instruction(clause, &words),
+        };
+        Ok(encoder)
+    }
+
+    fn other_instruction(self, clause: &str, words: &[&str]) -> Result<Self, EncodeError> {
+        let invalid = |message: &str| EncodeError::InvalidArgument(clause.to_string(), message.to_string());
+        let number = |word: &str| -> Result<u32, EncodeError> {
+            word.parse().map_err(|_| invalid("expected a number"))
+        };
+
+        let encoder = match words {
+            ["clear"] | ["clear", "screen"] => self.clear_screen(),
+            ["clear", "below"] => self.csi(CSI::EraseInDisplay(0)),
+            ["clear", "above"] => self.csi(CSI::EraseInDisplay(1)),
+            ["clear", "scrollback"] => self.csi(CSI::EraseInDisplay(3)),
+            ["clear", "line"] => self.clear_line(),
+            ["clear", "to", "end", ..] => self.csi(CSI::EraseInLine(0)),
+            ["clear", "to", "start", ..] => self.csi(CSI::EraseInLine(1)),
+            ["scroll", "up", n] => self.csi(CSI::ScrollUp(number(n)?)),
+            ["scroll", "down", n] => self.csi(CSI::ScrollDown(number(n)?)),
+            ["scroll", "region", top, bottom] => self.csi(CSI::SetScrollRegion(number(top)?, Some(number(bottom)?))),
+            ["full", "reset"] => self.csi(CSI::FullReset),
+            ["end", "link"] | ["unlink"] => self.hyperlink(""),
+            ["bell"] => self.ctrl(ControlCharacter::Bell),
+            ["newline"] => self.ctrl(ControlCharacter::LineFeed),
+            ["return"] | ["cr"] => self.ctrl(ControlCharacter::CarriageReturn),
+            ["tab"] => self.ctrl(ControlCharacter::Tab),
+            ["backspace"] => self.ctrl(ControlCharacter::Backspace),
+            ["alt" | "alternate", "screen", rest @ ..] => self.alternate_screen(switch(clause, rest)?),
+            ["bracketed", "paste", rest @ ..] => self.private_mode(2004, switch(clause, rest)?),
+            ["focus", "events", rest @ ..] => self.private_mode(1004, switch(clause, rest)?),
+            ["wrap", rest @ ..] => self.private_mode(7, switch(clause, rest)?),
+            ["mouse", rest @ ..] => {
+                // Button tracking with SGR-style reports
+                let enabled = switch(clause, rest)?;
+                self.private_mode(1000, enabled).private_mode(1006, enabled)
+            },
+            ["mode", mode, rest @ ..] => {
+                let mode = mode.parse().map_err(|_| invalid("expected a mode number"))?;
+                match switch(clause, rest)? {
+                    true => self.csi(CSI::SetMode(vec![mode])),
+                    false => self.csi(CSI::ResetMode(vec![mode])),
+                }
+            },
+            ["private", "mode", mode, rest @ ..] => {
+                let mode = mode.parse().map_err(|_| invalid("expected a mode number"))?;
+                self.private_mode(mode, switch(clause, rest)?)
+            },
+            _ => {
+                let mut encoder = self;
+                for attribute in graphics(clause, words)? {
+                    encoder = encoder.sgr(attribute);
+                }
+                encoder
+            },
+        };
+        Ok(encoder)
+    }
+}
+
+// Split a description at commas, except inside quotes and between digits ("5,10")
+fn clauses(description: &str) -> Vec<String> {
+    let chars: Vec<char> = description.chars().collect();
+    let mut clauses = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;    // Previous character was a backslash inside quotes
+
+    for (i, &c) in chars.iter().enumerate() {
+        if escaped {
+            escaped = false;
+            current.push(c);
+            continue;
+        }
+        match (c, quote) {
+            ('\\', Some(_)) => {
+                escaped = true;
+                current.push(c);
+            },
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                current.push(c);
+            },
+            (c, Some(q)) if c == q => {
+                quote = None;
+                current.push(c);
+            },
+            (',', None) => {
+                let between_digits = i > 0
+                    && chars[i - 1].is_ascii_digit()
+                    && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit());
+                if between_digits {
+                    current.push(c);
+                } else {
+                    clauses.push(std::mem::take(&mut current));
+                }
+            },
+            _ => current.push(c),
+        }
+    }
+    clauses.push(current);
+
+    clauses.into_iter()
+        .map(|clause| clause.trim().to_string())
+        .filter(|clause| !clause.is_empty())
+        .collect()
+}
+
+// Contents of a quoted string, with \n, \t, \r, \e and \\ escapes
+fn unquote(text: &str) -> Option<String> {
+    let quote = text.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let inner = text.strip_prefix(quote)?.strip_suffix(quote)?;
+
+    let mut result = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('e') => result.push('\x1b'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    Some(result)
+}
+
+// "on"/"off" after a mode name, defaulting to on
+fn switch(clause: &str, words: &[&str]) -> Result<bool, EncodeError> {
+    match words {
+        [] | ["on"] | ["enable"] => Ok(true),
+        ["off"] | ["disable"] => Ok(false),
+        _ => Err(EncodeError::InvalidArgument(clause.to_string(), "expected on or off".to_string())),
+    }
+}
+
+// Attributes and colors such as "bold red fg on blue" or "no italic"
+fn graphics(clause: &str, words: &[&str]) -> Result<Vec<SgrAttribute>, EncodeError> {
+    let mut attributes = Vec::new();
+    let mut i = 0;
+
+    while i < words.len() {
+        let next = words.get(i + 1).copied();
+        if let Some(attribute) = attribute(words[i], next) {
+            attributes.push(attribute);
+            i += if matches!(words[i], "no" | "not" | "double" | "curly" | "dotted" | "dashed") { 2 } else { 1 };
+            continue;
+        }
+
+        match words[i] {
+            "fg" | "foreground" | "bg" | "background" | "on" | "ul" => {
+                let (color, used) = color(&words[i + 1..])
+                    .ok_or_else(|| EncodeError::InvalidArgument(clause.to_string(), format!("expected a color after '{}'", words[i])))?;
+                attributes.push(match words[i] {
+                    "fg" | "foreground" => SgrAttribute::Foreground(color),
+                    "ul" => SgrAttribute::UnderlineColor(color),
+                    _ => SgrAttribute::Background(color),
+                });
+                i += 1 + used;
+            },
+            "default" => {
+                attributes.push(match next {
+                    Some("fg" | "foreground") => SgrAttribute::DefaultForeground,
+                    Some("bg" | "background") => SgrAttribute::DefaultBackground,
+                    _ => return Err(EncodeError::InvalidArgument(clause.to_string(), "expected fg or bg after 'default'".to_string())),
+                });
+                i += 2;
+            },
+            hex if hex.starts_with('#') && color(&words[i..]).is_none() => {
+                return Err(EncodeError::InvalidArgument(clause.to_string(), format!("'{}' is not a #rrggbb color", hex)));
+            },
+            _ => {
+                let (color, used) = color(&words[i..])
+                    .ok_or_else(|| EncodeError::UnknownInstruction(clause.to_string()))?;
+                i += used;
+                // A color on its own is a foreground color
+                attributes.push(match words.get(i).copied() {
+                    Some("bg" | "background") => SgrAttribute::Background(color),
+                    Some("ul") => SgrAttribute::UnderlineColor(color),
+                    _ => SgrAttribute::Foreground(color),
+                });
+                if matches!(words.get(i).copied(), Some("fg" | "foreground" | "bg" | "background" | "ul")) {
+                    i += 1;
+                }
+            },
+        }
+    }
+
+    Ok(attributes)
+}
+
+// A named attribute, looking at the next word for "no bold" or "double underline"
+fn attribute(word: &str, next: Option<&str>) -> Option<SgrAttribute> {
+    let attribute = match (word, next) {
+        ("reset" | "normal" | "plain", _) => SgrAttribute::Reset,
+        ("bold", _) => SgrAttribute::Bold,
+        ("dim" | "faint", _) => SgrAttribute::Faint,
+        ("italic", _) => SgrAttribute::Italic,
+        ("underline" | "underlined", _) => SgrAttribute::Underline(UnderlineStyle::Single),
+        ("double", Some("underline")) => SgrAttribute::Underline(UnderlineStyle::Double),
+        ("curly", Some("underline")) => SgrAttribute::Underline(UnderlineStyle::Curly),
+        ("dotted", Some("underline")) => SgrAttribute::Underline(UnderlineStyle::Dotted),
+        ("dashed", Some("underline")) => SgrAttribute::Underline(UnderlineStyle::Dashed),
+        ("blink" | "blinking", _) => SgrAttribute::SlowBlink,
+        ("reverse" | "inverse", _) => SgrAttribute::Reverse,
+        ("hidden" | "conceal" | "invisible", _) => SgrAttribute::Conceal,
+        ("strike" | "strikethrough" | "crossed", _) => SgrAttribute::CrossedOut,
+        ("overline", _) => SgrAttribute::Overline,
+        ("no" | "not", Some("bold" | "dim" | "faint")) => SgrAttribute::NormalIntensity,
+        ("no" | "not", Some("italic")) => SgrAttribute::NotItalic,
+        ("no" | "not", Some("underline" | "underlined")) => SgrAttribute::Underline(UnderlineStyle::None),
+        ("no" | "not", Some("blink" | "blinking")) => SgrAttribute::NotBlinking,
+        ("no" | "not", Some("reverse" | "inverse")) => SgrAttribute::NotReversed,
+        ("no" | "not", Some("hidden" | "conceal" | "invisible")) => SgrAttribute::Reveal,
+        ("no" | "not", Some("strike" | "strikethrough" | "crossed")) => SgrAttribute::NotCrossedOut,
+        ("no" | "not", Some("overline")) => SgrAttribute::NotOverlined,
+        _ => return None,
+    };
+    Some(attribute)
+}
+
+// A color at the start of the words and how many words it took
+fn color(words: &[&str]) -> Option<(SgrColor, usize)> {
+    let index = |name: &str| -> Option<u8> {
+        let names = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+        names.iter().position(|n| *n == name).map(|i| i as u8)
+    };
+
+    match words {
+        ["bright" | "light", name, ..] => index(name).map(|n| (SgrColor::Bright(n), 2)),
+        ["gray" | "grey", ..] => Some((SgrColor::Bright(0), 1)),
+        ["color" | "colour", n, ..] => n.parse().ok().map(|n| (SgrColor::Indexed(n), 2)),
+        ["rgb", r, g, b, ..] => Some((SgrColor::Rgb(r.parse().ok()?, g.parse().ok()?, b.parse().ok()?), 4)),
+        [hex, ..] if hex.len() == 7 && hex.starts_with('#') && hex[1..].chars().all(|c| c.is_ascii_hexdigit()) => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+            Some((SgrColor::Rgb(channel(1)?, channel(3)?, channel(5)?), 1))
+        },
+        [name, ..] => index(name).map(|n| (SgrColor::Standard(n), 1)),
+        [] => None,
+    }
+}
+
+fn printf_escape(c: char) -> String {
+    match c {
+        '\\' => "\\\\".to_string(),
+        '%' => "%%".to_string(),
+        '\'' => "\\047".to_string(),
+        '\n' => "\\n".to_string(),
+        '\t' => "\\t".to_string(),
+        '\r' => "\\r".to_string(),
+        c if c.is_ascii_control() => format!("\\{:03o}", c as u32),
+        c => c.to_string(),
+    }
+}
+
+// Rust and Python take exactly two digits after \x
+fn hex_escape(c: char) -> String {
+    match c {
+        '\\' => "\\\\".to_string(),
+        '"' => "\\\"".to_string(),
+        '\n' => "\\n".to_string(),
+        '\t' => "\\t".to_string(),
+        '\r' => "\\r".to_string(),
+        c if c.is_ascii_control() => format!("\\x{:02x}", c as u32),
+        c => c.to_string(),
+    }
+}
+
+// C hex escapes swallow any following hex digits, so controls use three-digit octal
+fn octal_escape(c: char) -> String {
+    match c {
+        '\\' => "\\\\".to_string(),
+        '"' => "\\\"".to_string(),
+        '\n' => "\\n".to_string(),
+        '\t' => "\\t".to_string(),
+        '\r' => "\\r".to_string(),
+        c if c.is_ascii_control() => format!("\\{:03o}", c as u32),
+        c => c.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(description: &str) -> Vec<u8> {
+        Encoder::from_description(description).unwrap().to_bytes()
+    }
+
+    fn error(description: &str) -> EncodeError {
+        Encoder::from_description(description).unwrap_err()
+    }
+
+    #[test]
+    fn graphics() {
+        assert_eq!(encode("bold red"), b"\x1b[1;31m");
+        assert_eq!(encode("bold, red fg on blue"), b"\x1b[1;31;44m");
+        assert_eq!(encode("#ff0080"), b"\x1b[38;2;255;0;128m");
+        assert_eq!(encode("color 208 bg"), b"\x1b[48;5;208m");
+        assert_eq!(encode("no bold, reset"), b"\x1b[22;0m");
+    }
+
+    #[test]
+    fn cursor_and_modes() {
+        assert_eq!(encode("cursor to 5,10"), b"\x1b[5;10H");
+        assert_eq!(encode("up 3, left"), b"\x1b[3A\x1b[1D");
+        assert_eq!(encode("hide cursor, alt screen on"), b"\x1b[?25l\x1b[?1049h");
+        assert_eq!(encode("clear scrollback"), b"\x1b[3J");
+    }
+
+    #[test]
+    fn quoted_text_and_titles() {
+        assert_eq!(encode("\"a, b\", bold"), b"a, b\x1b[1m");
+        assert_eq!(encode("title \"a\\\\\", bold"), b"\x1b]2;a\\\x1b\\\x1b[1m");
+        assert_eq!(encode("title 'it\\'s', bold"), b"\x1b]2;it's\x1b\\\x1b[1m");
+    }
+
+    #[test]
+    fn invalid_instructions() {
+        assert!(matches!(error("frobnicate"), EncodeError::UnknownInstruction(_)));
+        assert!(matches!(error("cursor to a 5"), EncodeError::InvalidArgument(..)));
+        assert!(matches!(error("fg"), EncodeError::InvalidArgument(..)));
+        // Byte length 7, but not seven ASCII characters
+        assert!(matches!(error("#a\u{e9}123"), EncodeError::InvalidArgument(..)));
+        assert!(matches!(error("#+f0080"), EncodeError::InvalidArgument(..)));
+    }
+
+    #[test]
+    fn notations() {
+        let bytes = b"\x1b[1m'%\\";
+        assert_eq!(Notation::Printf.write(bytes), "\\033[1m\\047%%\\\\");
+        assert_eq!(Notation::Rust.write(b"\x1b[1m"), "\"\\x1b[1m\"");
+        assert_eq!(Notation::C.write(b"\x1b[1m"), "\"\\033[1m\"");
+    }
+}
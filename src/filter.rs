@@ -0,0 +1,211 @@
+use crate::ansi::{AnsiElement, Spanned, csi::CSI};
+use crate::parser::ParserError;
+use crate::sanitize::Category;
+use regex::Regex;
+use std::io::Write;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FilterError {
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+
+    #[error("Invalid selector: {0}")]
+    InvalidSelector(String),
+}
+
+/// Matches elements by kind, command or description
+#[derive(Debug, Clone)]
+pub enum Selector {
+    Type(String),                   // Element type as shown in the table: Text, CSI, OSC, Ctrl, DCS, ...
+    Category(Category),             // Sanitizer category: cursor, title, sgr, ...
+    Command(String, Option<u32>),   // CSI mnemonic or OSC, with an optional parameter: SGR, DECSET 1049, OSC 8
+    Description(Regex),             // /regex/ on the description
+}
+
+impl Selector {
+    /// Parse a selector such as "CSI", "cursor", "DECSET 1049" or "/title/".
+    /// A bare word is an element type or, in lowercase, a category; anything else
+    /// is a command. "type:", "category:" and "command:" prefixes pick one explicitly.
+    pub fn parse(selector: &str) -> Result<Self, FilterError> {
+        let selector = selector.trim();
+        if let Some(pattern) = selector.strip_prefix('/').and_then(|s| s.strip_suffix('/')) {
+            return Ok(Selector::Description(Regex::new(pattern)?));
+        }
+
+        let invalid = || FilterError::InvalidSelector(selector.to_string());
+        if let Some(name) = selector.strip_prefix("type:") {
+            return Ok(Selector::Type(name.to_string()));
+        }
+        if let Some(name) = selector.strip_prefix("category:") {
+            return Category::from_name(&name.to_lowercase()).map(Selector::Category).ok_or_else(invalid);
+        }
+        if let Some(command) = selector.strip_prefix("command:") {
+            return Self::command(command).ok_or_else(invalid);
+        }
+
+        if !selector.contains(char::is_whitespace) {
+            if TYPES.iter().any(|t| t.eq_ignore_ascii_case(selector)) {
+                return Ok(Selector::Type(selector.to_string()));
+            }
+            if let Some(category) = Category::from_name(selector) {
+                return Ok(Selector::Category(category));
+            }
+        }
+        Self::command(selector).ok_or_else(invalid)
+    }
+
+    fn command(command: &str) -> Option<Self> {
+        let mut words = command.split_whitespace();
+        let name = words.next()?.to_uppercase();
+        let param = match words.next() {
+            Some(param) => Some(param.parse().ok()?),
+            None => None,
+        };
+        words.next().is_none().then_some(Selector::Command(name, param))
+    }
+
+    pub fn matches(&self, element: &AnsiElement) -> bool {
+        match self {
+            Selector::Type(name) => element.element_type().eq_ignore_ascii_case(name),
+            Selector::Category(category) => Category::of(element) == *category,
+            Selector::Command(name, param) => match command(element) {
+                Some((mnemonic, params)) => {
+                    mnemonic.eq_ignore_ascii_case(name) && param.is_none_or(|p| params.contains(&p))
+                },
+                None => false,
+            },
+            Selector::Description(regex) => regex.is_match(&element.description()),
+        }
+    }
+}
+
+// Element types that can be named without a prefix
+const TYPES: [&str; 9] = ["Text", "Invalid", "CSI", "OSC", "DCS", "APC", "PM", "SOS", "Ctrl"];
+
+/// Which elements are kept: those matching any `only` selector (or all of them
+/// if there are none), minus those matching any `exclude` selector
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub only: Vec<Selector>,
+    pub exclude: Vec<Selector>,
+}
+
+impl Filter {
+    pub fn new(only: Vec<Selector>, exclude: Vec<Selector>) -> Self {
+        Self { only, exclude }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.only.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn keeps(&self, element: &AnsiElement) -> bool {
+        let selected = self.only.is_empty() || self.only.iter().any(|s| s.matches(element));
+        selected && !self.exclude.iter().any(|s| s.matches(element))
+    }
+
+    /// The elements of a parsed stream the filter keeps. Errors are passed on.
+    pub fn select<'a>(
+        &'a self,
+        elements: impl Iterator<Item = Result<Spanned, ParserError>> + 'a,
+    ) -> impl Iterator<Item = Result<Spanned, ParserError>> + 'a {
+        elements.filter(|element| element.as_ref().map_or(true, |spanned| self.keeps(&spanned.element)))
+    }
+}
+
+/// Write elements back out byte for byte, so only what a filter dropped is missing
+pub fn rewrite(
+    elements: impl Iterator<Item = Result<Spanned, ParserError>>,
+    output: &mut impl Write,
+) -> Result<(), ParserError> {
+    for element in elements {
+        output.write_all(&element?.element.to_bytes())?;
+    }
+    Ok(())
+}
+
+// Command name of a sequence and the numeric parameters a selector can match
+fn command(element: &AnsiElement) -> Option<(String, Vec<u32>)> {
+    match element {
+        AnsiElement::Csi(csi, _) => {
+            let params = match csi {
+                CSI::SetMode(modes) |
+                CSI::ResetMode(modes) |
+                CSI::SetPrivateMode(modes) |
+                CSI::ResetPrivateMode(modes) => modes.iter().map(|&m| m as u32).collect(),
+                // Leading code of each attribute, so "SGR 1" matches bold but not 38;5;1
+                CSI::SetGraphicsMode(attributes) => attributes.iter()
+                    .filter_map(|a| numbers(&a.params()).first().copied())
+                    .collect(),
+                _ => numbers(csi.escape_repr().trim_start_matches("\\x1b")),
+            };
+            Some((csi.mnemonic().to_string(), params))
+        },
+        AnsiElement::Osc(osc, _, _) => {
+            let payload = osc.payload();
+            let code = payload.split(';').next().and_then(|code| code.parse().ok());
+            Some(("OSC".to_string(), code.into_iter().collect()))
+        },
+        _ => None,
+    }
+}
+
+fn numbers(text: &str) -> Vec<u32> {
+    text.split(|c: char| !c.is_ascii_digit())
+        .filter_map(|n| n.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::AnsiParser;
+
+    // Bytes of the elements the filter keeps
+    fn kept(input: &[u8], only: &[&str], exclude: &[&str]) -> Vec<u8> {
+        let selectors = |list: &[&str]| list.iter().map(|s| Selector::parse(s).unwrap()).collect();
+        let filter = Filter::new(selectors(only), selectors(exclude));
+        let mut output = Vec::new();
+        rewrite(filter.select(AnsiParser::spanned_elements(input)), &mut output).unwrap();
+        output
+    }
+
+    const INPUT: &[u8] = b"a\x1b[1mb\x1b[2;3H\x1b[?1049h\x1b[?25l\x1b]0;t\x07\x1b]8;;u\x07\r\n";
+
+    #[test]
+    fn empty_filter_keeps_everything() {
+        assert_eq!(kept(INPUT, &[], &[]), INPUT);
+        assert!(Filter::default().is_empty());
+    }
+
+    #[test]
+    fn types_and_categories() {
+        assert_eq!(kept(INPUT, &["Text"], &[]), b"ab");
+        assert_eq!(kept(INPUT, &["csi"], &[]), b"\x1b[1m\x1b[2;3H\x1b[?1049h\x1b[?25l");
+        assert_eq!(kept(INPUT, &["cursor"], &[]), b"\x1b[2;3H");
+        assert_eq!(kept(INPUT, &["category:title"], &[]), b"\x1b]0;t\x07");
+    }
+
+    #[test]
+    fn commands_with_parameters() {
+        assert_eq!(kept(INPUT, &["SGR"], &[]), b"\x1b[1m");
+        assert_eq!(kept(INPUT, &["DECSET 1049"], &[]), b"\x1b[?1049h");
+        assert_eq!(kept(INPUT, &["OSC 8"], &[]), b"\x1b]8;;u\x07");
+        assert_eq!(kept(INPUT, &["command:cup"], &[]), b"\x1b[2;3H");
+    }
+
+    #[test]
+    fn descriptions_and_exclusions() {
+        assert_eq!(kept(INPUT, &["/[Tt]itle/"], &[]), b"\x1b]0;t\x07");
+        assert_eq!(kept(INPUT, &["CSI"], &["SGR", "cursor"]), b"\x1b[?1049h\x1b[?25l");
+        assert_eq!(kept(INPUT, &[], &["Text", "Ctrl", "OSC"]), b"\x1b[1m\x1b[2;3H\x1b[?1049h\x1b[?25l");
+    }
+
+    #[test]
+    fn invalid_selectors() {
+        assert!(matches!(Selector::parse("/(/"), Err(FilterError::InvalidPattern(_))));
+        assert!(matches!(Selector::parse("category:nope"), Err(FilterError::InvalidSelector(_))));
+        assert!(matches!(Selector::parse("DECSET x"), Err(FilterError::InvalidSelector(_))));
+    }
+}
@@ -1,8 +1,24 @@
-use crate::ansi::AnsiElement;
+use crate::ansi::{AnsiElement, Spanned};
 
 /// Trait for formatting ANSI elements
 pub trait FormatAnsi {
     fn format(&self, elements: &[AnsiElement]) -> String;
+    
+    /// Format elements along with their spans; formatters without location output ignore them
+    fn format_spanned(&self, elements: &[Spanned]) -> String {
+        let elements: Vec<AnsiElement> = elements.iter().map(|spanned| spanned.element.clone()).collect();
+        self.format(&elements)
+    }
+    
+    /// Text written once before the first streamed element
+    fn stream_header(&self) -> String {
+        String::new()
+    }
+    
+    /// Format a single element as soon as it is parsed, for streaming output
+    fn format_element(&self, element: &Spanned) -> String {
+        self.format_spanned(std::slice::from_ref(element))
+    }
 }
 
 /// Formats ANSI elements for single-line output (useful for debugging)
@@ -2,6 +2,18 @@ pub mod ansi;
 pub mod parser;
 pub mod formatter;
 pub mod output;
+pub mod screen;
+pub mod sanitize;
+pub mod audit;
+pub mod encode;
+pub mod filter;
+pub mod stats;
+pub mod lint;
+pub mod optimize;
+pub mod diff;
+pub mod run;
+pub mod record;
+pub mod asciicast;
 
 pub use parser::AnsiParser;
 
@@ -0,0 +1,286 @@
+use crate::ansi::{AnsiElement, Span, Spanned, csi::CSI, osc::{OSC, Terminator}};
+use crate::ansi::sgr::{SgrAttribute, SgrState};
+use crate::encode::Notation;
+use crate::parser::ParserError;
+use crate::stats::ALTERNATE_SCREEN_MODES;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use termio::{Color, StyledText};
+
+// DEC private modes that are on until a program turns them off: autowrap, cursor visible
+const PRIVATE_MODES_ON_BY_DEFAULT: [u16; 2] = [7, 25];
+
+/// Terminal state still changed at the end of the stream
+#[derive(Debug, Clone)]
+pub struct Leftover {
+    pub span: Span,             // Sequence that changed the state
+    pub escape: String,
+    pub description: String,
+    pub reset: AnsiElement,     // Sequence that restores the default
+}
+
+// Where a piece of state was last changed, and what it is now
+#[derive(Debug, Clone)]
+struct Change {
+    span: Span,
+    element: AnsiElement,
+}
+
+/// Tracks modes and attributes to find what a stream leaves active
+#[derive(Debug, Clone, Default)]
+pub struct Linter {
+    modes: BTreeMap<(bool, u16), (bool, Change)>,   // (private, mode) -> (enabled, change)
+    style: SgrState,
+    style_change: Option<Change>,
+    keypad: Option<Change>,
+    charset: Option<Change>,
+    scroll_region: Option<Change>,
+    cursor_style: Option<Change>,
+    keyboard_flags: Vec<Change>,    // Kitty keyboard flags pushed and not popped
+    hyperlink: Option<Change>,
+}
+
+impl Linter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lint a whole stream
+    pub fn lint(elements: &[Spanned]) -> Vec<Leftover> {
+        let mut linter = Self::new();
+        for spanned in elements {
+            linter.feed(spanned);
+        }
+        linter.finish()
+    }
+
+    pub fn feed(&mut self, spanned: &Spanned) {
+        let change = || Some(Change { span: spanned.span, element: spanned.element.clone() });
+        match &spanned.element {
+            AnsiElement::Csi(csi, _) => {
+                if self.style.apply_csi(csi) {
+                    self.style_change = if self.style.is_default() { None } else { change() };
+                    return;
+                }
+                match csi {
+                    CSI::SetMode(modes) => self.set_modes(false, modes, true, spanned),
+                    CSI::ResetMode(modes) => self.set_modes(false, modes, false, spanned),
+                    CSI::SetPrivateMode(modes) => self.set_modes(true, modes, true, spanned),
+                    CSI::ResetPrivateMode(modes) => self.set_modes(true, modes, false, spanned),
+                    CSI::ApplicationKeypadMode => self.keypad = change(),
+                    CSI::NumericKeypadMode => self.keypad = None,
+                    CSI::SetG0SpecialChars => self.charset = change(),
+                    CSI::SetG0NormalChars => self.charset = None,
+                    CSI::SetScrollRegion(1, None) => self.scroll_region = None,
+                    CSI::SetScrollRegion(..) => self.scroll_region = change(),
+                    CSI::SetCursorStyle(0) => self.cursor_style = None,
+                    CSI::SetCursorStyle(_) => self.cursor_style = change(),
+                    CSI::PushKeyboardFlags(_) => self.keyboard_flags.extend(change()),
+                    CSI::PopKeyboardFlags(n) => {
+                        let depth = self.keyboard_flags.len().saturating_sub(*n as usize);
+                        self.keyboard_flags.truncate(depth);
+                    },
+                    CSI::SoftReset => self.soft_reset(),
+                    CSI::FullReset => *self = Self::new(),
+                    _ => {},
+                }
+            },
+            AnsiElement::Osc(OSC::Hyperlink(_, uri), _, _) => {
+                self.hyperlink = if uri.is_empty() { None } else { change() };
+            },
+            _ => {},
+        }
+    }
+
+    // DECSTR puts SGR, insert mode, cursor visibility, keypad, charset and margins back
+    fn soft_reset(&mut self) {
+        self.style = SgrState::default();
+        self.style_change = None;
+        self.modes.remove(&(false, 4));
+        self.modes.remove(&(true, 25));
+        self.keypad = None;
+        self.charset = None;
+        self.scroll_region = None;
+    }
+
+    fn set_modes(&mut self, private: bool, modes: &[u16], enabled: bool, spanned: &Spanned) {
+        for &mode in modes {
+            let change = Change { span: spanned.span, element: mode_element(private, mode, enabled) };
+            self.modes.insert((private, mode), (enabled, change));
+        }
+    }
+
+    /// What is still active, in stream order
+    pub fn finish(self) -> Vec<Leftover> {
+        let mut leftovers = Vec::new();
+
+        for ((private, mode), (enabled, change)) in self.modes {
+            let default = private && PRIVATE_MODES_ON_BY_DEFAULT.contains(&mode);
+            if enabled != default {
+                leftovers.push(leftover(change, mode_element(private, mode, default)));
+            }
+        }
+        if let Some(change) = self.style_change {
+            // The whole state, written as the SGR that would set it from scratch
+            let mut attributes = vec![SgrAttribute::Reset];
+            attributes.extend(self.style.attributes());
+            leftovers.push(Leftover {
+                span: change.span,
+                escape: AnsiElement::Csi(CSI::SetGraphicsMode(attributes), None).escape_repr(),
+                description: format!("Graphics state: {}", self.style.attributes().iter()
+                    .map(|a| a.description())
+                    .collect::<Vec<String>>()
+                    .join(", ")),
+                reset: AnsiElement::Csi(CSI::ResetAttributes, None),
+            });
+        }
+        let others = [
+            (self.keypad, CSI::NumericKeypadMode),
+            (self.charset, CSI::SetG0NormalChars),
+            (self.scroll_region, CSI::SetScrollRegion(1, None)),
+            (self.cursor_style, CSI::SetCursorStyle(0)),
+        ];
+        for (change, reset) in others {
+            if let Some(change) = change {
+                leftovers.push(leftover(change, AnsiElement::Csi(reset, None)));
+            }
+        }
+        for change in self.keyboard_flags {
+            leftovers.push(leftover(change, AnsiElement::Csi(CSI::PopKeyboardFlags(1), None)));
+        }
+        if let Some(change) = self.hyperlink {
+            let reset = AnsiElement::Osc(OSC::Hyperlink(String::new(), String::new()), Terminator::St, None);
+            leftovers.push(leftover(change, reset));
+        }
+
+        leftovers.sort_by_key(|leftover| leftover.span.start);
+        leftovers
+    }
+}
+
+/// Bytes that restore everything left active, undoing the latest change first
+pub fn reset_sequence(leftovers: &[Leftover]) -> Vec<u8> {
+    // The alternate screen goes first so the remaining resets apply to the main screen
+    let (alternate, others): (Vec<&Leftover>, Vec<&Leftover>) = leftovers.iter().rev()
+        .partition(|leftover| is_alternate_screen(&leftover.reset));
+    alternate.into_iter()
+        .chain(others)
+        .flat_map(|leftover| leftover.reset.to_bytes())
+        .collect()
+}
+
+/// Lint a whole stream and write the report shown by --lint. Returns whether the state was restored.
+pub fn report(
+    elements: impl Iterator<Item = Result<Spanned, ParserError>>,
+    output: &mut impl Write,
+    colorize: bool,
+) -> Result<bool, ParserError> {
+    let mut linter = Linter::new();
+    for element in elements {
+        linter.feed(&element?);
+    }
+    let leftovers = linter.finish();
+    write_report(&leftovers, output, colorize)?;
+    Ok(leftovers.is_empty())
+}
+
+/// Write the report shown by --lint: each leftover and the sequence that resets them all
+pub fn write_report(leftovers: &[Leftover], output: &mut impl Write, colorize: bool) -> io::Result<()> {
+    if leftovers.is_empty() {
+        return writeln!(output, "Terminal state restored at EOF");
+    }
+
+    writeln!(output, "Left active at EOF:")?;
+    for leftover in leftovers {
+        let offset = format!("{}..{}", leftover.span.start, leftover.span.end);
+        let escape = format!("{:<20}", leftover.escape);
+        let escape = if colorize { escape.color(Color::Magenta).to_string() } else { escape };
+        writeln!(output, "  {:<12} {} {}", offset, escape, leftover.description)?;
+    }
+    writeln!(output, "\nReset sequence: {}", Notation::Printf.write(&reset_sequence(leftovers)))
+}
+
+fn leftover(change: Change, reset: AnsiElement) -> Leftover {
+    Leftover {
+        span: change.span,
+        escape: change.element.escape_repr(),
+        description: change.element.description(),
+        reset,
+    }
+}
+
+// One mode at a time, so each leftover names only the mode it is about
+fn mode_element(private: bool, mode: u16, enabled: bool) -> AnsiElement {
+    let csi = match (private, enabled) {
+        (true, true) => CSI::SetPrivateMode(vec![mode]),
+        (true, false) => CSI::ResetPrivateMode(vec![mode]),
+        (false, true) => CSI::SetMode(vec![mode]),
+        (false, false) => CSI::ResetMode(vec![mode]),
+    };
+    AnsiElement::Csi(csi, None)
+}
+
+fn is_alternate_screen(element: &AnsiElement) -> bool {
+    match element {
+        AnsiElement::Csi(CSI::ResetPrivateMode(modes), _) => modes.iter().any(|m| ALTERNATE_SCREEN_MODES.contains(m)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::AnsiParser;
+
+    fn lint(input: &[u8]) -> Vec<Leftover> {
+        Linter::lint(&AnsiParser::parse_spanned(input).unwrap())
+    }
+
+    fn escapes(input: &[u8]) -> Vec<String> {
+        lint(input).into_iter().map(|leftover| leftover.escape).collect()
+    }
+
+    #[test]
+    fn clean_streams_leave_nothing() {
+        assert!(lint(b"\x1b[1mbold\x1b[0m \x1b[?1049h\x1b[?25l\x1b[?25h\x1b[?1049l").is_empty());
+        assert!(lint(b"\x1b[?2004h\x1b[4h\x1bc").is_empty());
+    }
+
+    #[test]
+    fn graphics_state_is_written_as_one_sgr() {
+        assert_eq!(escapes(b"\x1b[1m\x1b[31mx"), ["\\x1b[0;1;31m"]);
+        assert_eq!(escapes(b"\x1b[1m\x1b[22m"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn modes_left_active() {
+        assert_eq!(escapes(b"\x1b[?1000;1006h\x1b[?25l"), ["\\x1b[?1000h", "\\x1b[?1006h", "\\x1b[?25l"]);
+        // Autowrap and cursor visibility are on by default, so turning them on is no leftover
+        assert!(lint(b"\x1b[?7h\x1b[?25h").is_empty());
+    }
+
+    #[test]
+    fn reset_undoes_the_alternate_screen_first() {
+        let leftovers = lint(b"\x1b[?1049h\x1b[1m\x1b]8;;https://x\x07");
+        assert_eq!(leftovers.len(), 3);
+        assert!(reset_sequence(&leftovers).starts_with(b"\x1b[?1049l"));
+    }
+
+    #[test]
+    fn report_ends_with_the_reset_sequence() {
+        let mut output = Vec::new();
+        assert!(!report(AnsiParser::spanned_elements(&b"ab\x1b[?25l"[..]), &mut output, false).unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(),
+            "Left active at EOF:\n  2..8         \\x1b[?25l            Reset private mode: Hide cursor\n\
+            \nReset sequence: \\033[?25h\n");
+
+        let mut output = Vec::new();
+        write_report(&[], &mut output, false).unwrap();
+        assert_eq!(output, b"Terminal state restored at EOF\n");
+    }
+
+    #[test]
+    fn soft_reset_clears_what_it_resets() {
+        assert_eq!(escapes(b"\x1b[1m\x1b[?25l\x1b[?1000h\x1b[!p"), ["\\x1b[?1000h"]);
+    }
+}
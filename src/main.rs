@@ -1,12 +1,31 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::fs::File;
-use std::io::{self, Read};
-use std::path::PathBuf;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::time::Duration;
 
 use seqsee::formatter::FormatAnsi;
-use seqsee::output::{table::TableFormatter, raw::RawFormatter};
-use seqsee::parser::AnsiParser;
+use seqsee::output::{table::TableFormatter, raw::RawFormatter, json::JsonFormatter, html::HtmlFormatter};
+use seqsee::output::svg::{SvgFormatter, Theme};
+use seqsee::output::strip::StripFormatter;
+use seqsee::ansi::Spanned;
+use seqsee::parser::{AnsiParser, ParserError};
+use seqsee::screen::{self, Screen};
+use seqsee::sanitize::{Category, Policy, Sanitizer};
+use seqsee::audit::Auditor;
+use seqsee::encode::{Encoder, Notation};
+use seqsee::filter::{self, Filter, Selector};
+use seqsee::stats;
+use seqsee::lint;
+use seqsee::optimize::Optimizer;
+use seqsee::diff;
+use seqsee::run::{self, Runner};
+use seqsee::record::{Recording, RecordingError};
+use seqsee::asciicast;
+
+// Input buffer, large enough to hold the header line of an asciicast for detection
+const INPUT_BUFFER_SIZE: usize = 64 * 1024;
 
 #[derive(Parser)]
 #[command(
@@ -16,6 +35,9 @@ use seqsee::parser::AnsiParser;
     about = "A tool for parsing and displaying ANSI escape sequences in a human-readable format"
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    
     /// Input file (reads from stdin if not specified)
     #[arg(short, long)]
     file: Option<PathBuf>,
@@ -28,65 +50,578 @@ struct Cli {
     #[arg(long, short, default_value_t = false, group = "output_format")]
     raw: bool,
     
+    /// Output a JSON array of elements
+    #[arg(long, default_value_t = false, group = "output_format")]
+    json: bool,
+    
+    /// Output one JSON object per element, as each one is parsed
+    #[arg(long, default_value_t = false, group = "output_format")]
+    jsonl: bool,
+    
+    /// Output a standalone HTML page with the text colored as in a terminal
+    #[arg(long, default_value_t = false, group = "output_format")]
+    html: bool,
+    
+    /// Show sequence descriptions as tooltips in the HTML output
+    #[arg(long, requires = "html")]
+    tooltips: bool,
+    
+    /// List every sequence in a side panel of the HTML output
+    #[arg(long, requires = "html")]
+    side_panel: bool,
+    
+    /// Output the input as plain text with all escape sequences removed
+    #[arg(long, default_value_t = false, group = "output_format")]
+    strip: bool,
+    
+    /// Apply carriage returns, backspaces, tabs and line erases when stripping, keeping only the text left visible
+    #[arg(long, requires = "strip")]
+    resolve_overstrike: bool,
+    
+    /// Output the input with sequences outside the allow-list removed, reporting them on stderr
+    #[arg(long, default_value_t = false, group = "output_format")]
+    sanitize: bool,
+    
+    /// Categories kept by --sanitize (comma-separated)
+    #[arg(long, value_delimiter = ',', value_parser = parse_category, requires = "sanitize",
+          default_value = "text,whitespace,sgr,cursor,editing,hyperlink")]
+    allow: Vec<Category>,
+    
+    /// URI schemes of hyperlinks kept by --sanitize (comma-separated)
+    #[arg(long, value_delimiter = ',', requires = "sanitize", default_value = "http,https")]
+    link_schemes: Vec<String>,
+    
+    /// Show removed sequences in escaped form instead of dropping them
+    #[arg(long, requires = "sanitize")]
+    escape_removed: bool,
+    
+    /// Report sequences that could be abused to attack the terminal or its user
+    #[arg(long, default_value_t = false, group = "output_format")]
+    audit: bool,
+    
+    /// Report modes and attributes left active at the end of the input, and how to reset them.
+    /// Exits with status 1 if anything is left active
+    #[arg(long, default_value_t = false, group = "output_format")]
+    lint: bool,
+    
+    /// Summarize the stream: element counts, frequent and unknown sequences, modes left enabled
+    #[arg(long, default_value_t = false, group = "output_format")]
+    stats: bool,
+    
+    /// Output the input unchanged apart from the elements dropped by --only/--exclude
+    #[arg(long, default_value_t = false, group = "output_format")]
+    rewrite: bool,
+    
+    /// Show when each element of a recording arrived, grouped by the write that completed it
+    #[arg(long, default_value_t = false, group = "output_format")]
+    timeline: bool,
+    
+    /// Output the input as an asciicast v2 recording, keeping its timing if it has any
+    #[arg(long, default_value_t = false, group = "output_format")]
+    cast: bool,
+    
+    /// Output the input with redundant sequences removed and adjacent SGR sequences merged.
+    /// What was removed and the bytes saved are reported on stderr
+    #[arg(long, default_value_t = false, group = "output_format")]
+    optimize: bool,
+    
+    /// Keep only elements matching a selector: a type (CSI, OSC, Text, ...), a category (cursor, title, ...),
+    /// a command (SGR, "DECSET 1049", "OSC 8") or /regex/ on the description. Repeatable
+    #[arg(long, value_parser = parse_selector)]
+    only: Vec<Selector>,
+    
+    /// Drop elements matching a selector, with the same syntax as --only. Repeatable
+    #[arg(long, value_parser = parse_selector)]
+    exclude: Vec<Selector>,
+    
+    /// Replay the input on a virtual terminal and print the final screen
+    #[arg(long, default_value_t = false, group = "output_format")]
+    render: bool,
+    
+    /// Output an SVG image of the screen left by the input
+    #[arg(long, default_value_t = false, group = "output_format")]
+    svg: bool,
+    
+    /// Color theme for --svg
+    #[arg(long, default_value = "dark", value_parser = Theme::NAMES, requires = "svg")]
+    theme: String,
+    
+    /// Font size in pixels for --svg
+    #[arg(long, default_value_t = 14.0, requires = "svg")]
+    font_size: f64,
+    
+    /// Screen width in columns for --render, --svg, --audit and --optimize
+    #[arg(long, default_value_t = screen::DEFAULT_COLUMNS)]
+    cols: usize,
+    
+    /// Screen height in rows for --render, --svg, --audit and --optimize
+    #[arg(long, default_value_t = screen::DEFAULT_ROWS)]
+    rows: usize,
+    
+    /// Show the byte range of each element in the input
+    #[arg(long)]
+    offsets: bool,
+    
+    /// Show the line and column of each element in the input
+    #[arg(long)]
+    positions: bool,
+    
     /// Disable colorized output
     #[arg(long)]
     no_color: bool,
 }
 
+impl Cli {
+    // Elements kept by --only and --exclude
+    fn selection(&self) -> Filter {
+        Filter::new(self.only.clone(), self.exclude.clone())
+    }
+    
+    // --cols and --rows as a pseudo-terminal or recording holds them
+    fn terminal_size(&self) -> (u16, u16) {
+        let size = |n: usize| u16::try_from(n).unwrap_or(u16::MAX);
+        (size(self.cols), size(self.rows))
+    }
+    
+    fn policy(&self) -> Policy {
+        Policy {
+            allowed: self.allow.clone(),
+            link_schemes: self.link_schemes.iter().map(|s| s.to_lowercase()).collect(),
+            escape_removed: self.escape_removed,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate escape sequences from a description such as "bold red fg, cursor to 5,10"
+    Encode {
+        /// Comma-separated instructions; several arguments are joined with commas
+        #[arg(required = true)]
+        description: Vec<String>,
+        
+        /// How to print the sequences
+        #[arg(long, default_value = "printf", value_parser = Notation::NAMES)]
+        format: String,
+    },
+    
+    /// Compare two streams by what their sequences do, ignoring differences in how they are written.
+    /// Exits with status 1 if they differ
+    Diff {
+        old: PathBuf,
+        
+        new: PathBuf,
+        
+        /// Compare the screens the two streams leave instead of their elements
+        #[arg(long = "screen")]
+        compare_screens: bool,
+        
+        /// Screen width in columns for --screen
+        #[arg(long, default_value_t = screen::DEFAULT_COLUMNS, requires = "compare_screens")]
+        cols: usize,
+        
+        /// Screen height in rows for --screen
+        #[arg(long, default_value_t = screen::DEFAULT_ROWS, requires = "compare_screens")]
+        rows: usize,
+        
+        /// Disable colored output
+        #[arg(long)]
+        no_color: bool,
+    },
+    
+    /// Run a command under a pseudo-terminal and show its output with the options given before "run".
+    /// The terminal size is taken from --cols and --rows
+    Run {
+        /// Value of TERM for the command
+        #[arg(long, default_value = run::DEFAULT_TERM)]
+        term: String,
+        
+        /// Also copy the command's output to stderr as it runs
+        #[arg(long)]
+        tee: bool,
+        
+        /// Save the output with its timing to a file, for --timeline and replay.
+        /// Files ending in .cast are written as asciicast v2
+        #[arg(long)]
+        record: Option<PathBuf>,
+        
+        /// Command and its arguments, after "--"
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    
+    /// Write a recording or asciicast to stdout again with its original timing
+    Replay {
+        file: PathBuf,
+        
+        /// Playback speed: 2 plays twice as fast, 0.5 at half speed
+        #[arg(long, default_value_t = 1.0, value_parser = parse_speed)]
+        speed: f64,
+        
+        /// Longest pause between writes, in seconds
+        #[arg(long, value_parser = parse_max_wait)]
+        max_wait: Option<Duration>,
+        
+        /// Wait for a key press before each write, or before each element with "--step element". q stops
+        #[arg(long, num_args = 0..=1, default_missing_value = "write", value_parser = ["write", "element"])]
+        step: Option<String>,
+    },
+}
+
 fn main() -> ExitCode {
     let cli = Cli::parse();
     
+    match &cli.command {
+        Some(Command::Encode { description, format }) => return encode(&description.join(", "), format),
+        Some(Command::Diff { old, new, compare_screens, cols, rows, no_color }) => {
+            let screen_size = compare_screens.then_some((*cols, *rows));
+            return diff_files(old, new, screen_size, !no_color);
+        },
+        Some(Command::Run { term, tee, record, command }) => return run_command(command, term, *tee, record.as_deref(), &cli),
+        Some(Command::Replay { file, speed, max_wait, step }) => return replay(file, *speed, *max_wait, step.as_deref()),
+        None => {},
+    }
+    
     // Get input
     let result = match get_input(&cli.file) {
-        Ok(input) => {
-            match process_input(input, &cli) {
-                Ok(output) => {
-                    println!("{}", output);
-                    ExitCode::SUCCESS
+        Ok(Input::Stream(_)) if cli.timeline => {
+            eprintln!("--timeline needs a recording, such as one saved by 'seqsee run --record'");
+            return ExitCode::FAILURE;
+        },
+        // Without timing, everything is written at the start
+        Ok(Input::Stream(mut input)) if cli.cast => {
+            let mut data = Vec::new();
+            match input.read_to_end(&mut data) {
+                Ok(_) => {
+                    let (columns, rows) = cli.terminal_size();
+                    process_recording(&Recording::untimed(columns, rows, data), &cli)
                 },
-                Err(err) => {
-                    eprintln!("Error processing input: {}", err);
-                    ExitCode::FAILURE
-                }
+                Err(err) => Err(err.into()),
             }
         },
+        Ok(Input::Stream(input)) => process_input(input, &cli),
+        Ok(Input::Recording(recording)) => process_recording(&recording, &cli),
         Err(err) => {
             eprintln!("Error reading input: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    
+    match result {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error processing input: {}", err);
             ExitCode::FAILURE
         }
+    }
+}
+
+fn encode(description: &str, format: &str) -> ExitCode {
+    let encoder = match Encoder::from_description(description) {
+        Ok(encoder) => encoder,
+        Err(err) => {
+            eprintln!("Error encoding: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    
+    let notation = Notation::by_name(format).unwrap_or(Notation::Printf);
+    let mut stdout = io::stdout().lock();
+    let result = if notation == Notation::Raw {
+        stdout.write_all(&encoder.to_bytes())
+    } else {
+        writeln!(stdout, "{}", encoder.format(notation))
+    };
+    
+    match result.and_then(|()| stdout.flush()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error writing output: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn diff_files(old: &Path, new: &Path, screen_size: Option<(usize, usize)>, colorize: bool) -> ExitCode {
+    let parse = |path: &Path| -> Result<Vec<Spanned>, String> {
+        let file = File::open(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        AnsiParser::parse_spanned(file).map_err(|err| format!("{}: {}", path.display(), err))
+    };
+    
+    let (old, new) = match (parse(old), parse(new)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("Error reading input: {}", err);
+            // Trouble is status 2, as with diff(1)
+            return ExitCode::from(2);
+        }
+    };
+    
+    let mut stdout = io::stdout().lock();
+    let (result, same) = match screen_size {
+        Some((columns, rows)) => {
+            let differences = diff::diff_rendered(&old, &new, columns, rows);
+            (diff::write_screen_report(&differences, &mut stdout), differences.is_empty())
+        },
+        None => {
+            let differences = diff::diff(&old, &new);
+            (diff::write_report(&differences, &mut stdout, colorize), differences.is_empty())
+        },
+    };
+    match result.and_then(|()| stdout.flush()) {
+        Ok(()) if same => ExitCode::SUCCESS,
+        Ok(()) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("Error writing output: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run_command(command: &[String], term: &str, tee: bool, record: Option<&Path>, cli: &Cli) -> ExitCode {
+    let (columns, rows) = cli.terminal_size();
+    let runner = Runner::new(columns, rows, term);
+    
+    let mut stderr = io::stderr();
+    let recording = match runner.record(command, tee.then_some(&mut stderr as &mut dyn Write)) {
+        Ok((exit, recording)) => {
+            if !exit.success {
+                eprintln!("Command exited with status {}", exit.code);
+            }
+            recording
+        },
+        Err(err) => {
+            eprintln!("Error running command: {}", err);
+            return ExitCode::FAILURE;
+        }
     };
     
-    result
+    if let Some(path) = record {
+        if let Err(err) = recording.save(path) {
+            eprintln!("Error saving recording: {}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+    
+    match process_recording(&recording, cli) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error processing input: {}", err);
+            ExitCode::FAILURE
+        }
+    }
 }
 
-fn get_input(file_path: &Option<PathBuf>) -> io::Result<Box<dyn Read>> {
-    match file_path {
-        Some(path) => {
-            let file = File::open(path)?;
-            Ok(Box::new(file))
+fn replay(file: &Path, speed: f64, max_wait: Option<Duration>, step: Option<&str>) -> ExitCode {
+    let recording = match get_input(&Some(file.to_path_buf())) {
+        Ok(Input::Recording(recording)) => recording,
+        Ok(Input::Stream(_)) => {
+            eprintln!("Error reading recording: {} is neither a seqsee recording nor an asciicast", file.display());
+            return ExitCode::FAILURE;
         },
-        None => Ok(Box::new(io::stdin())),
+        Err(err) => {
+            eprintln!("Error reading recording: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    
+    let mut stdout = io::stdout().lock();
+    let result = match step {
+        Some(unit) => recording.replay_stepwise(&mut stdout, unit == "element"),
+        None => recording.replay(&mut stdout, speed, max_wait),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error replaying: {}", err);
+            ExitCode::FAILURE
+        }
     }
 }
 
-fn process_input(input: Box<dyn Read>, cli: &Cli) -> io::Result<String> {
+// Input as given: a plain stream, or a recording or asciicast with timing
+enum Input {
+    Stream(Box<dyn Read>),
+    Recording(Recording),
+}
+
+fn get_input(file_path: &Option<PathBuf>) -> Result<Input, RecordingError> {
+    let input: Box<dyn Read> = match file_path {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(io::stdin()),
+    };
+    
+    let mut input = BufReader::with_capacity(INPUT_BUFFER_SIZE, input);
+    let start = input.fill_buf()?;
+    if Recording::is_recording(start) {
+        return Ok(Input::Recording(Recording::read(input)?));
+    }
+    if asciicast::is_asciicast(start) {
+        return Ok(Input::Recording(asciicast::read(input)?));
+    }
+    Ok(Input::Stream(Box::new(input)))
+}
+
+fn process_recording(recording: &Recording, cli: &Cli) -> Result<ExitCode, ParserError> {
+    if !cli.timeline && !cli.cast {
+        return process_input(Box::new(io::Cursor::new(recording.bytes())), cli);
+    }
+    
+    let selection = cli.selection();
+    let mut stdout = io::stdout().lock();
+    if cli.timeline {
+        recording.write_timeline(&mut stdout, |element| selection.keeps(element), !cli.no_color)?;
+    } else if selection.is_empty() {
+        asciicast::write(recording, &mut stdout)?;
+    } else {
+        asciicast::write(&recording.retain(|element| selection.keeps(element))?, &mut stdout)?;
+    }
+    stdout.flush()?;
+    
+    Ok(ExitCode::SUCCESS)
+}
+
+fn process_input(input: Box<dyn Read>, cli: &Cli) -> Result<ExitCode, ParserError> {
     let colorize = !cli.no_color;
     
-    // Parse ANSI sequences
-    let elements = match AnsiParser::parse(input) {
-        Ok(elems) => elems,
-        Err(err) => return Err(io::Error::new(io::ErrorKind::Other, format!("{}", err))),
-    };
+    // Selection applies before any formatter sees the elements
+    let selection = cli.selection();
+    let elements = selection.select(AnsiParser::spanned_elements(input));
+    
+    // Modes that pass the stream on report on stderr, so their output stays clean
+    let mut stdout = io::stdout().lock();
+    let mut status = ExitCode::SUCCESS;
+    if cli.render || cli.svg {
+        render_input(elements, cli, colorize, &mut stdout)?;
+    } else if cli.strip {
+        StripFormatter::new(cli.resolve_overstrike).strip(elements, &mut stdout)?;
+    } else if cli.sanitize {
+        Sanitizer::new(cli.policy()).sanitize_stream(elements, &mut stdout, &mut io::stderr())?;
+    } else if cli.audit {
+        Auditor::new(cli.cols, cli.rows).audit_stream(elements, &mut stdout, colorize)?;
+    } else if cli.lint {
+        if !lint::report(elements, &mut stdout, colorize)? {
+            status = ExitCode::FAILURE;
+        }
+    } else if cli.stats {
+        stats::report(elements, &mut stdout)?;
+    } else if cli.rewrite {
+        filter::rewrite(elements, &mut stdout)?;
+    } else if cli.optimize {
+        Optimizer::new(cli.cols, cli.rows).optimize_stream(elements, &mut stdout, &mut io::stderr())?;
+    } else {
+        format_input(elements, cli, colorize, &mut stdout)?;
+    }
+    stdout.flush()?;
+    
+    Ok(status)
+}
+
+fn format_input(
+    elements: impl Iterator<Item = Result<Spanned, ParserError>>,
+    cli: &Cli,
+    colorize: bool,
+    output: &mut impl Write,
+) -> Result<(), ParserError> {
+    // Whole-document formats can only be written once every element is known
+    if cli.json || cli.html {
+        let formatter: Box<dyn FormatAnsi> = if cli.json {
+            Box::new(JsonFormatter::new(false))
+        } else {
+            Box::new(HtmlFormatter { tooltips: cli.tooltips, side_panel: cli.side_panel })
+        };
+        let elements = elements.collect::<Result<Vec<Spanned>, ParserError>>()?;
+        write!(output, "{}", formatter.format_spanned(&elements))?;
+        return Ok(());
+    }
     
     // Format according to the selected mode
-    let output = if cli.raw {
-        let formatter = RawFormatter::new(colorize);
-        formatter.format(&elements)
+    let formatter: Box<dyn FormatAnsi> = if cli.jsonl {
+        Box::new(JsonFormatter::new(true))
+    } else if cli.raw {
+        Box::new(RawFormatter {
+            colorize,
+            show_offsets: cli.offsets,
+            show_positions: cli.positions,
+        })
     } else {
         // Default to table mode
-        let formatter = TableFormatter::new(colorize);
-        formatter.format(&elements)
+        Box::new(TableFormatter {
+            colorize,
+            show_offsets: cli.offsets,
+            show_positions: cli.positions,
+        })
     };
     
-    Ok(output)
-} 
\ No newline at end of file
+    // Parse ANSI sequences and print them as they arrive
+    let mut header_written = false;
+    
+    for element in elements {
+        let element = element?;
+        if !header_written {
+            write!(output, "{}", formatter.stream_header())?;
+            header_written = true;
+        }
+        write!(output, "{}", formatter.format_element(&element))?;
+    }
+    
+    // Keep JSON Lines output strictly one object per line
+    if !cli.jsonl {
+        writeln!(output)?;
+    }
+    
+    Ok(())
+}
+
+fn parse_speed(speed: &str) -> Result<f64, String> {
+    match speed.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!("'{}' is not a positive number", speed)),
+    }
+}
+
+fn parse_max_wait(seconds: &str) -> Result<Duration, String> {
+    seconds.parse::<f64>().ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("'{}' is not a valid number of seconds", seconds))
+}
+
+fn parse_selector(selector: &str) -> Result<Selector, String> {
+    Selector::parse(selector).map_err(|err| err.to_string())
+}
+
+fn parse_category(name: &str) -> Result<Category, String> {
+    Category::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = Category::ALL.iter().map(|c| c.name()).collect();
+        format!("unknown category '{}', expected one of: {}", name, names.join(", "))
+    })
+}
+
+fn render_input(
+    elements: impl Iterator<Item = Result<Spanned, ParserError>>,
+    cli: &Cli,
+    colorize: bool,
+    output: &mut impl Write,
+) -> Result<(), ParserError> {
+    let mut screen = Screen::new(cli.cols, cli.rows);
+    // Piped output usually has bare LF line endings that a tty would have translated
+    screen.set_newline_mode(true);
+    for element in elements {
+        screen.apply(&element?.element);
+    }
+    
+    let rendered = if cli.svg {
+        let formatter = SvgFormatter {
+            columns: cli.cols,
+            rows: cli.rows,
+            font_size: cli.font_size,
+            theme: Theme::by_name(&cli.theme).unwrap_or_default(),
+        };
+        formatter.render(&screen)
+    } else {
+        screen.render(colorize)
+    };
+    write!(output, "{}", rendered)?;
+    
+    Ok(())
+}
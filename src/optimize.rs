@@ -0,0 +1,468 @@
+use crate::ansi::{AnsiElement, Span, Spanned, csi::CSI};
+use crate::ansi::sgr::{SgrAttribute, SgrColor};
+use crate::parser::ParserError;
+use crate::screen::{Screen, DEFAULT_COLUMNS, DEFAULT_ROWS};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+// DEC private modes that do more than flip a flag when set again:
+// origin mode homes the cursor, the others switch screens or save the cursor
+const NON_IDEMPOTENT_MODES: [u16; 5] = [6, 47, 1047, 1048, 1049];
+
+// DEC private modes that save the cursor, with its graphics state, when set and restore it when reset
+const CURSOR_SAVING_MODES: [u16; 2] = [1048, 1049];
+
+/// A sequence the optimizer removed or merged into another one
+#[derive(Debug, Clone)]
+pub struct Redundancy {
+    pub index: usize,       // Position of the element in the input
+    pub escape: String,
+    pub reason: String,
+    pub saved: usize,       // Bytes saved
+}
+
+/// Result of optimizing a stream
+#[derive(Debug, Clone, Default)]
+pub struct Optimized {
+    pub elements: Vec<AnsiElement>,
+    pub redundancies: Vec<Redundancy>,
+    pub original_bytes: usize,
+}
+
+impl Optimized {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.elements.iter().flat_map(|element| element.to_bytes()).collect()
+    }
+
+    pub fn optimized_bytes(&self) -> usize {
+        self.elements.iter().map(|element| element.to_bytes().len()).sum()
+    }
+
+    pub fn saved_bytes(&self) -> usize {
+        self.original_bytes.saturating_sub(self.optimized_bytes())
+    }
+
+    /// Write what was removed and the bytes saved, locating each redundancy by the span of its input element
+    pub fn write_report(&self, spans: &[Span], output: &mut impl Write) -> io::Result<()> {
+        if !self.redundancies.is_empty() {
+            writeln!(output, "Optimized {} sequence(s):", self.redundancies.len())?;
+            for redundancy in &self.redundancies {
+                let span = spans[redundancy.index];
+                let offset = format!("{}..{}", span.start, span.end);
+                writeln!(output, "  {:<12} {:<20} {}", offset, redundancy.escape, redundancy.reason)?;
+            }
+        }
+        writeln!(output, "Saved {} of {} bytes", self.saved_bytes(), self.original_bytes)
+    }
+}
+
+/// Removes sequences that leave the terminal as it was and merges adjacent SGR sequences.
+/// Nothing is assumed about the terminal before the stream: the graphics state, cursor
+/// position and modes only count as known once the stream has set them.
+pub struct Optimizer {
+    pub columns: usize,     // Size of the terminal the stream is written to, for cursor tracking
+    pub rows: usize,
+}
+
+impl Optimizer {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Self { columns, rows }
+    }
+
+    pub fn optimize(&self, elements: &[AnsiElement]) -> Optimized {
+        let mut state = State::new(self.columns, self.rows);
+        let mut optimized = Optimized::default();
+
+        for (index, element) in elements.iter().enumerate() {
+            let bytes = element.to_bytes().len();
+            optimized.original_bytes += bytes;
+
+            let redundant = |reason: &str| Redundancy {
+                index,
+                escape: element.escape_repr(),
+                reason: reason.to_string(),
+                saved: bytes,
+            };
+
+            match element {
+                AnsiElement::Csi(csi, _) if is_sgr(csi) => {
+                    if let Some(redundancy) = state.sgr(csi, index, element, &mut optimized.elements) {
+                        optimized.redundancies.push(redundancy);
+                    }
+                    continue;
+                },
+                AnsiElement::Csi(csi, _) if is_cursor_move(csi) => {
+                    let before = state.cursors();
+                    state.apply(element);
+                    if state.position_known && state.cursors() == before {
+                        optimized.redundancies.push(redundant("cursor is already there"));
+                        continue;
+                    }
+                    if matches!(csi, CSI::CursorPosition(..)) {
+                        state.position_known = true;
+                    }
+                },
+                AnsiElement::Csi(CSI::SetMode(modes) | CSI::ResetMode(modes), _) => {
+                    let enabled = matches!(csi_of(element), Some(CSI::SetMode(_)));
+                    if state.modes_unchanged(false, modes, enabled) {
+                        optimized.redundancies.push(redundant("mode is already in that state"));
+                        continue;
+                    }
+                    state.apply(element);
+                },
+                AnsiElement::Csi(CSI::SetPrivateMode(modes) | CSI::ResetPrivateMode(modes), _) => {
+                    let enabled = matches!(csi_of(element), Some(CSI::SetPrivateMode(_)));
+                    if state.modes_unchanged(true, modes, enabled) {
+                        optimized.redundancies.push(redundant("mode is already in that state"));
+                        continue;
+                    }
+                    // Origin mode makes cursor addressing relative to a scroll region that may not be known
+                    if modes.contains(&6) {
+                        state.position_known = false;
+                    }
+                    if modes.iter().any(|mode| CURSOR_SAVING_MODES.contains(mode)) {
+                        if enabled { state.save() } else { state.restore() }
+                    }
+                    state.apply(element);
+                },
+                _ => state.other(element),
+            }
+            optimized.elements.push(element.clone());
+        }
+
+        optimized
+    }
+
+    /// Optimize a whole stream, writing the result to `output` and what was removed to `report`
+    pub fn optimize_stream(
+        &self,
+        elements: impl Iterator<Item = Result<Spanned, ParserError>>,
+        output: &mut impl Write,
+        report: &mut impl Write,
+    ) -> Result<(), ParserError> {
+        let spanned = elements.collect::<Result<Vec<Spanned>, ParserError>>()?;
+        let elements: Vec<AnsiElement> = spanned.iter().map(|s| s.element.clone()).collect();
+        let optimized = self.optimize(&elements);
+        output.write_all(&optimized.to_bytes())?;
+        output.flush()?;
+
+        let spans: Vec<Span> = spanned.iter().map(|s| s.span).collect();
+        optimized.write_report(&spans, report)?;
+        Ok(())
+    }
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new(DEFAULT_COLUMNS, DEFAULT_ROWS)
+    }
+}
+
+// What is known about the terminal while walking the stream
+struct State {
+    screens: [Screen; 2],                  // Without and with LF also returning the carriage, as a tty may translate it
+    position_known: bool,
+    style_known: bool,                     // The screen's graphics state matches the terminal's
+    saved: (bool, bool),                   // Whether the cursor saved by DECSC had a known position and style
+    modes: HashMap<(bool, u16), bool>,     // (private, mode) -> enabled, for modes the stream has set
+}
+
+impl State {
+    fn new(columns: usize, rows: usize) -> Self {
+        let mut translated = Screen::new(columns, rows);
+        translated.set_newline_mode(true);
+        Self {
+            screens: [Screen::new(columns, rows), translated],
+            position_known: false,
+            style_known: false,
+            saved: (false, false),
+            modes: HashMap::new(),
+        }
+    }
+
+    // Drop an SGR that changes nothing, or merge it into an SGR just before it
+    fn sgr(&mut self, csi: &CSI, index: usize, element: &AnsiElement, output: &mut Vec<AnsiElement>) -> Option<Redundancy> {
+        let attributes = sgr_attributes(csi);
+        let before = *self.screens[0].style();
+        self.apply(element);
+
+        if attributes.iter().any(|a| matches!(a, SgrAttribute::Unknown(_))) {
+            // The state model can't follow attributes it doesn't know
+            self.style_known = false;
+        } else if self.style_known && *self.screens[0].style() == before {
+            return Some(Redundancy {
+                index,
+                escape: element.escape_repr(),
+                reason: "graphics state is already set".to_string(),
+                saved: element.to_bytes().len(),
+            });
+        } else if matches!(attributes.first(), Some(SgrAttribute::Reset)) {
+            self.style_known = true;
+        }
+
+        let previous = match output.last() {
+            Some(AnsiElement::Csi(previous, _)) if is_sgr(previous) => previous,
+            _ => {
+                output.push(element.clone());
+                return None;
+            },
+        };
+
+        // Both sequences in one, or the whole state from scratch when it is known
+        let mut joined = sgr_attributes(previous);
+        joined.extend(attributes);
+        let mut merged = AnsiElement::Csi(CSI::SetGraphicsMode(joined), None);
+        if self.style_known {
+            let mut absolute = vec![SgrAttribute::Reset];
+            absolute.extend(self.screens[0].style().attributes());
+            let absolute = AnsiElement::Csi(CSI::SetGraphicsMode(absolute), None);
+            if absolute.to_bytes().len() < merged.to_bytes().len() {
+                merged = absolute;
+            }
+        }
+
+        // Verbatim input can be shorter than the canonical form the merge is written in
+        let separate = output.last().map_or(0, |previous| previous.to_bytes().len()) + element.to_bytes().len();
+        if merged.to_bytes().len() >= separate {
+            output.push(element.clone());
+            return None;
+        }
+        output.pop();
+        let saved = separate - merged.to_bytes().len();
+        let redundancy = Redundancy {
+            index,
+            escape: element.escape_repr(),
+            reason: format!("merged with the previous SGR into {}", merged.escape_repr()),
+            saved,
+        };
+        output.push(merged);
+        Some(redundancy)
+    }
+
+    // True if every mode is already known to be in the requested state
+    fn modes_unchanged(&mut self, private: bool, modes: &[u16], enabled: bool) -> bool {
+        let unchanged = !modes.is_empty() && modes.iter().all(|&mode| {
+            !(private && NON_IDEMPOTENT_MODES.contains(&mode)) && self.modes.get(&(private, mode)) == Some(&enabled)
+        });
+        for &mode in modes {
+            self.modes.insert((private, mode), enabled);
+        }
+        unchanged
+    }
+
+    fn apply(&mut self, element: &AnsiElement) {
+        for screen in &mut self.screens {
+            screen.apply(element);
+        }
+    }
+
+    fn cursors(&self) -> [((usize, usize), bool); 2] {
+        self.screens.each_ref().map(|screen| (screen.cursor(), screen.wrap_pending()))
+    }
+
+    fn save(&mut self) {
+        self.saved = (self.position_known, self.style_known);
+    }
+
+    fn restore(&mut self) {
+        (self.position_known, self.style_known) = self.saved;
+    }
+
+    fn other(&mut self, element: &AnsiElement) {
+        match element {
+            AnsiElement::Csi(CSI::CursorSavePosition, _) => self.save(),
+            AnsiElement::Csi(CSI::CursorRestorePosition, _) => self.restore(),
+            AnsiElement::Csi(CSI::FullReset, _) => {
+                self.position_known = true;
+                self.style_known = true;
+                self.saved = (true, true);
+                self.modes.clear();
+            },
+            AnsiElement::Csi(CSI::SoftReset, _) => {
+                self.style_known = true;
+                self.saved = (true, true);
+                self.modes.clear();
+            },
+            // Sequences the screen doesn't model may have moved the cursor
+            AnsiElement::Csi(CSI::Unrecognized(_) | CSI::Unknown(_), _) | AnsiElement::ControlString(..) => {
+                self.position_known = false;
+            },
+            _ => {},
+        }
+        self.apply(element);
+    }
+}
+
+fn csi_of(element: &AnsiElement) -> Option<&CSI> {
+    match element {
+        AnsiElement::Csi(csi, _) => Some(csi),
+        _ => None,
+    }
+}
+
+fn is_sgr(csi: &CSI) -> bool {
+    matches!(
+        csi,
+        CSI::SetGraphicsMode(_) |
+        CSI::ResetAttributes |
+        CSI::SetForegroundColor(..) |
+        CSI::SetBackgroundColor(..) |
+        CSI::SetForegroundColor256(_) |
+        CSI::SetBackgroundColor256(_)
+    )
+}
+
+fn is_cursor_move(csi: &CSI) -> bool {
+    matches!(
+        csi,
+        CSI::CursorUp(_) |
+        CSI::CursorDown(_) |
+        CSI::CursorForward(_) |
+        CSI::CursorBackward(_) |
+        CSI::CursorPosition(..) |
+        CSI::CursorToColumn(_) |
+        CSI::CursorToRow(_) |
+        CSI::CursorNextLine(_) |
+        CSI::CursorPreviousLine(_)
+    )
+}
+
+// Attributes of any form of SGR
+fn sgr_attributes(csi: &CSI) -> Vec<SgrAttribute> {
+    match csi {
+        CSI::SetGraphicsMode(attributes) => attributes.clone(),
+        CSI::ResetAttributes => vec![SgrAttribute::Reset],
+        CSI::SetForegroundColor(r, g, b) => vec![SgrAttribute::Foreground(SgrColor::Rgb(*r, *g, *b))],
+        CSI::SetBackgroundColor(r, g, b) => vec![SgrAttribute::Background(SgrColor::Rgb(*r, *g, *b))],
+        CSI::SetForegroundColor256(n) => vec![SgrAttribute::Foreground(SgrColor::Indexed(*n))],
+        CSI::SetBackgroundColor256(n) => vec![SgrAttribute::Background(SgrColor::Indexed(*n))],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ansi::sgr::SgrState;
+    use crate::parser::AnsiParser;
+    use crate::screen::Cell;
+
+    // Everything the screen shows or keeps for later output
+    #[derive(Debug, PartialEq)]
+    struct Snapshot {
+        lines: Vec<Vec<Cell>>,
+        cursor: (usize, usize),
+        wrap_pending: bool,
+        style: SgrState,
+        alternate_screen: bool,
+        title: String,
+    }
+
+    fn snapshot(bytes: &[u8], newline_mode: bool) -> Snapshot {
+        let mut screen = Screen::new(20, 6);
+        screen.set_newline_mode(newline_mode);
+        screen.apply_all(&AnsiParser::parse(bytes).unwrap());
+        Snapshot {
+            lines: screen.lines().to_vec(),
+            cursor: screen.cursor(),
+            wrap_pending: screen.wrap_pending(),
+            style: *screen.style(),
+            alternate_screen: screen.is_alternate_screen(),
+            title: screen.title().to_string(),
+        }
+    }
+
+    fn optimize(bytes: &[u8]) -> Optimized {
+        Optimizer::new(20, 6).optimize(&AnsiParser::parse(bytes).unwrap())
+    }
+
+    fn assert_equivalent(bytes: &[u8]) {
+        let optimized = optimize(bytes).to_bytes();
+        for newline_mode in [false, true] {
+            assert_eq!(
+                snapshot(bytes, newline_mode), snapshot(&optimized, newline_mode),
+                "{:?} optimized to {:?}", String::from_utf8_lossy(bytes), String::from_utf8_lossy(&optimized),
+            );
+        }
+    }
+
+    #[test]
+    fn repeated_sgr_is_removed() {
+        let optimized = optimize(b"\x1b[0m\x1b[31mab\x1b[31mc");
+        assert_eq!(optimized.to_bytes(), b"\x1b[0;31mabc");
+        assert_eq!(optimized.saved_bytes(), optimized.original_bytes - optimized.optimized_bytes());
+    }
+
+    #[test]
+    fn report_locates_redundancies_in_the_input() {
+        let (mut output, mut report) = (Vec::new(), Vec::new());
+        Optimizer::new(20, 6)
+            .optimize_stream(AnsiParser::spanned_elements(&b"\x1b[0mab\x1b[31m\x1b[31mc"[..]), &mut output, &mut report)
+            .unwrap();
+        assert_eq!(output, b"\x1b[0mab\x1b[31mc");
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.starts_with("Optimized 1 sequence(s):\n  11..16       \\x1b[31m"), "{}", report);
+        assert!(report.ends_with("Saved 5 of 17 bytes\n"), "{}", report);
+    }
+
+    #[test]
+    fn sgr_is_kept_while_the_state_is_unknown() {
+        // Nothing is known about the terminal's colors before the stream
+        assert_eq!(optimize(b"\x1b[39mab").to_bytes(), b"\x1b[39mab");
+        assert_eq!(optimize(b"\x1b[0m\x1b[0m").to_bytes(), b"\x1b[0m");
+    }
+
+    #[test]
+    fn adjacent_sgr_is_merged_only_when_shorter() {
+        assert_eq!(optimize(b"\x1b[1m\x1b[31mx").to_bytes(), b"\x1b[1;31mx");
+        assert_eq!(optimize(b"\x1b[1mx\x1b[31mx").to_bytes(), b"\x1b[1mx\x1b[31mx");
+    }
+
+    #[test]
+    fn cursor_moves_are_removed_only_when_the_position_is_known() {
+        assert_eq!(optimize(b"\x1b[3;4Hx\x1b[3;5Hy").to_bytes(), b"\x1b[3;4Hxy");
+        // Relative moves from an unknown position still change where the cursor is
+        assert_eq!(optimize(b"\x1b[A\x1b[A").to_bytes(), b"\x1b[A\x1b[A");
+        // An unknown sequence may have moved the cursor
+        assert_eq!(optimize(b"\x1b[1;1H\x1b[5~\x1b[1;1H").to_bytes(), b"\x1b[1;1H\x1b[5~\x1b[1;1H");
+    }
+
+    #[test]
+    fn modes_are_removed_only_when_already_set() {
+        assert_eq!(optimize(b"\x1b[?25l\x1b[?25l").to_bytes(), b"\x1b[?25l");
+        assert_eq!(optimize(b"\x1b[?25l\x1b[?25h").to_bytes(), b"\x1b[?25l\x1b[?25h");
+        // Switching screens again is not a no-op
+        assert_eq!(optimize(b"\x1b[?1049h\x1b[?1049h").to_bytes(), b"\x1b[?1049h\x1b[?1049h");
+    }
+
+    #[test]
+    fn restored_cursor_state_is_not_assumed() {
+        // DECRC brings back the style saved by DECSC, so the SGR after it is needed
+        assert_equivalent(b"\x1b[0m\x1b7\x1b[1m\x1b8\x1b[1mx");
+        assert_equivalent(b"\x1b[0m\x1b[?1049h\x1b[31m\x1b[?1049l\x1b[31mx");
+    }
+
+    #[test]
+    fn optimized_streams_leave_the_same_screen() {
+        let fragments: [&[u8]; 24] = [
+            b"ab", b"wide \xe7\x95\x8c", b"\r", b"\n", b"\x08", b"\t",
+            b"\x1b[0m", b"\x1b[m", b"\x1b[1m", b"\x1b[31m", b"\x1b[1;31m", b"\x1b[22m", b"\x1b[7;42m",
+            b"\x1b[H", b"\x1b[2;3H", b"\x1b[A", b"\x1b[5C", b"\x1b[20G",
+            b"\x1b7", b"\x1b8", b"\x1b[?1049h", b"\x1b[?1049l", b"\x1bc", b"\x1b[2J",
+        ];
+        // Deterministic pseudo-random streams
+        let mut seed: u64 = 0x5eed;
+        let mut next = |bound: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+        let mut saved = 0;
+        for _ in 0..500 {
+            let len = 1 + next(30);
+            let stream: Vec<u8> = (0..len).flat_map(|_| fragments[next(fragments.len())].to_vec()).collect();
+            assert_equivalent(&stream);
+            saved += optimize(&stream).saved_bytes();
+        }
+        assert!(saved > 0, "nothing was optimized");
+    }
+}
@@ -0,0 +1,211 @@
+use crate::ansi::{AnsiElement, Spanned, ctrl::ControlCharacter};
+use crate::ansi::sgr::{SgrColor, SgrState, UnderlineStyle, XTERM_PALETTE};
+use crate::formatter::FormatAnsi;
+
+const DEFAULT_FOREGROUND: (u8, u8, u8) = (212, 212, 212);
+const DEFAULT_BACKGROUND: (u8, u8, u8) = (30, 30, 30);
+
+const STYLESHEET: &str = "\
+body { margin: 0; background: #1e1e1e; color: #d4d4d4; font-family: ui-monospace, Menlo, Consolas, monospace; }
+.layout { display: flex; align-items: flex-start; }
+pre.terminal { flex: 1; margin: 0; padding: 1em; white-space: pre-wrap; word-break: break-all; line-height: 1.3; }
+pre.terminal span[title] { cursor: help; }
+.blink { animation: blink 1s steps(1) infinite; }
+@keyframes blink { 50% { opacity: 0; } }
+aside.sequences { width: 40%; max-height: 100vh; overflow: auto; padding: 1em; border-left: 1px solid #444; font-size: 0.85em; }
+aside.sequences table { border-collapse: collapse; }
+aside.sequences td { padding: 0.1em 0.6em; vertical-align: top; }
+aside.sequences td.esc { color: #d670d6; white-space: nowrap; }
+aside.sequences td.offset { color: #888; white-space: nowrap; }
+";
+
+/// Standalone HTML page showing the text as a terminal would color it
+pub struct HtmlFormatter {
+    pub tooltips: bool,     // Describe the sequences preceding each run of text in its tooltip
+    pub side_panel: bool,   // List every sequence with its description next to the text
+}
+
+impl HtmlFormatter {
+    pub fn new() -> Self {
+        Self { tooltips: false, side_panel: false }
+    }
+
+    fn document(&self, elements: &[AnsiElement], spans: Option<&[Spanned]>) -> String {
+        let mut body = String::new();
+        let mut state = SgrState::default();
+        let mut pending: Vec<String> = Vec::new();
+        let mut rows: Vec<String> = Vec::new();
+
+        for (index, element) in elements.iter().enumerate() {
+            match element {
+                AnsiElement::Text(text) => {
+                    push_span(&mut body, &state, &html_escape(text), &pending);
+                    pending.clear();
+                },
+                AnsiElement::InvalidUtf8(_) => {
+                    push_span(&mut body, &state, "\u{FFFD}", &pending);
+                    pending.clear();
+                },
+                AnsiElement::Ctrl(ControlCharacter::LineFeed, _) => body.push('\n'),
+                AnsiElement::Ctrl(ControlCharacter::Tab, _) => body.push('\t'),
+                _ => {
+                    if let AnsiElement::Csi(csi, _) = element {
+                        state.apply_csi(csi);
+                    }
+                    if self.tooltips {
+                        pending.push(format!("{}: {}", element.escape_repr(), element.description()));
+                    }
+                    if self.side_panel {
+                        let offset = spans
+                            .map(|spans| format!("{}..{}", spans[index].span.start, spans[index].span.end))
+                            .unwrap_or_default();
+                        rows.push(format!(
+                            "<tr><td class=\"offset\">{}</td><td class=\"esc\">{}</td><td>{}</td></tr>\n",
+                            offset,
+                            html_escape(&element.escape_repr()),
+                            html_escape(&element.description()),
+                        ));
+                    }
+                },
+            }
+        }
+
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>seqsee</title>\n");
+        html.push_str(&format!("<style>\n{}</style>\n", STYLESHEET));
+        html.push_str("</head>\n<body>\n<div class=\"layout\">\n");
+        html.push_str(&format!("<pre class=\"terminal\">{}</pre>\n", body));
+        if self.side_panel {
+            html.push_str("<aside class=\"sequences\">\n<table>\n");
+            for row in rows {
+                html.push_str(&row);
+            }
+            html.push_str("</table>\n</aside>\n");
+        }
+        html.push_str("</div>\n</body>\n</html>\n");
+        html
+    }
+}
+
+impl Default for HtmlFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatAnsi for HtmlFormatter {
+    fn format(&self, elements: &[AnsiElement]) -> String {
+        self.document(elements, None)
+    }
+
+    fn format_spanned(&self, elements: &[Spanned]) -> String {
+        let plain: Vec<AnsiElement> = elements.iter().map(|spanned| spanned.element.clone()).collect();
+        self.document(&plain, Some(elements))
+    }
+}
+
+// Wrap already escaped text in a span carrying the current style and pending descriptions
+fn push_span(body: &mut String, state: &SgrState, text: &str, descriptions: &[String]) {
+    let style = css_style(state);
+    let class = if state.slow_blink || state.rapid_blink { " class=\"blink\"" } else { "" };
+    if style.is_empty() && class.is_empty() && descriptions.is_empty() {
+        body.push_str(text);
+        return;
+    }
+
+    body.push_str("<span");
+    body.push_str(class);
+    if !style.is_empty() {
+        body.push_str(&format!(" style=\"{}\"", style));
+    }
+    if !descriptions.is_empty() {
+        body.push_str(&format!(" title=\"{}\"", html_escape(&descriptions.join("\n"))));
+    }
+    body.push('>');
+    body.push_str(text);
+    body.push_str("</span>");
+}
+
+// Inline CSS for a graphics state, empty for the default state
+fn css_style(state: &SgrState) -> String {
+    let mut rules = Vec::new();
+
+    let (mut foreground, mut background) = (state.foreground, state.background);
+    if state.reverse {
+        std::mem::swap(&mut foreground, &mut background);
+    }
+    let foreground = foreground.map(css_color).or_else(|| state.reverse.then(|| rgb_hex(DEFAULT_BACKGROUND)));
+    let background = background.map(css_color).or_else(|| state.reverse.then(|| rgb_hex(DEFAULT_FOREGROUND)));
+
+    if state.conceal {
+        rules.push("color: transparent".to_string());
+    } else if let Some(color) = foreground {
+        rules.push(format!("color: {}", color));
+    }
+    if let Some(color) = background {
+        rules.push(format!("background-color: {}", color));
+    }
+    if state.bold {
+        rules.push("font-weight: bold".to_string());
+    }
+    if state.faint {
+        rules.push("opacity: 0.6".to_string());
+    }
+    if state.italic {
+        rules.push("font-style: italic".to_string());
+    }
+
+    let mut decorations = Vec::new();
+    if state.underline != UnderlineStyle::None {
+        decorations.push("underline");
+    }
+    if state.crossed_out {
+        decorations.push("line-through");
+    }
+    if state.overline {
+        decorations.push("overline");
+    }
+    if !decorations.is_empty() {
+        rules.push(format!("text-decoration-line: {}", decorations.join(" ")));
+        match state.underline {
+            UnderlineStyle::Double => rules.push("text-decoration-style: double".to_string()),
+            UnderlineStyle::Curly => rules.push("text-decoration-style: wavy".to_string()),
+            UnderlineStyle::Dotted => rules.push("text-decoration-style: dotted".to_string()),
+            UnderlineStyle::Dashed => rules.push("text-decoration-style: dashed".to_string()),
+            UnderlineStyle::None | UnderlineStyle::Single => {},
+        }
+        if let Some(color) = state.underline_color {
+            rules.push(format!("text-decoration-color: {}", css_color(color)));
+        }
+    }
+    if state.superscript {
+        rules.push("vertical-align: super".to_string());
+    } else if state.subscript {
+        rules.push("vertical-align: sub".to_string());
+    }
+
+    rules.join("; ")
+}
+
+fn css_color(color: SgrColor) -> String {
+    rgb_hex(color.to_rgb(&XTERM_PALETTE))
+}
+
+fn rgb_hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,184 @@
+use serde_json::{json, Map, Value};
+use crate::ansi::{AnsiElement, Span, Spanned, csi::CSI, osc::{OSC, Terminator}};
+use crate::formatter::FormatAnsi;
+
+/// Machine-readable output: a JSON array, or one object per line (JSON Lines)
+pub struct JsonFormatter {
+    pub lines: bool,    // Emit JSON Lines instead of a single array
+    pub pretty: bool,   // Indent the array output
+}
+
+impl JsonFormatter {
+    pub fn new(lines: bool) -> Self {
+        Self { lines, pretty: false }
+    }
+
+    /// JSON object describing one element, with its span when known. The bytes are the
+    /// input's when known, otherwise the element's canonical form.
+    pub fn element_value(element: &AnsiElement, spanned: Option<&Spanned>) -> Value {
+        let mut object = Map::new();
+        object.insert("type".to_string(), json!(element.element_type()));
+        if let AnsiElement::Csi(csi, _) = element {
+            object.insert("command".to_string(), json!(csi.mnemonic()));
+        }
+        match spanned {
+            Some(spanned) => {
+                object.insert("span".to_string(), span_value(&spanned.span));
+                object.insert("raw".to_string(), json!(spanned.raw));
+            },
+            None => {
+                object.insert("raw".to_string(), json!(element.to_bytes()));
+            },
+        }
+        object.insert("escape".to_string(), json!(element.escape_repr()));
+        object.insert("params".to_string(), params_value(element));
+        object.insert("description".to_string(), json!(element.description()));
+        Value::Object(object)
+    }
+
+    fn render(&self, values: Vec<Value>) -> String {
+        if self.lines {
+            return values.iter().map(|value| format!("{}\n", value)).collect();
+        }
+
+        let array = Value::Array(values);
+        let mut result = if self.pretty {
+            serde_json::to_string_pretty(&array).unwrap_or_default()
+        } else {
+            array.to_string()
+        };
+        result.push('\n');
+        result
+    }
+}
+
+impl Default for JsonFormatter {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl FormatAnsi for JsonFormatter {
+    fn format(&self, elements: &[AnsiElement]) -> String {
+        self.render(elements.iter().map(|element| Self::element_value(element, None)).collect())
+    }
+
+    fn format_spanned(&self, elements: &[Spanned]) -> String {
+        self.render(elements.iter().map(|spanned| Self::element_value(&spanned.element, Some(spanned))).collect())
+    }
+
+    // Only JSON Lines can be streamed; a single array is written by format_spanned
+    fn format_element(&self, spanned: &Spanned) -> String {
+        format!("{}\n", Self::element_value(&spanned.element, Some(spanned)))
+    }
+}
+
+fn span_value(span: &Span) -> Value {
+    json!({
+        "start": span.start,
+        "end": span.end,
+        "line": span.line,
+        "column": span.column,
+    })
+}
+
+fn terminator_value(terminator: Terminator) -> Value {
+    match terminator {
+        Terminator::Bel => json!("BEL"),
+        Terminator::St => json!("ST"),
+        Terminator::Unterminated => Value::Null,
+    }
+}
+
+// Decoded parameters of an element, keyed by meaning
+fn params_value(element: &AnsiElement) -> Value {
+    match element {
+        AnsiElement::Text(text) => json!({ "text": text }),
+        AnsiElement::InvalidUtf8(bytes) => json!({ "bytes": bytes }),
+        AnsiElement::Ctrl(ctrl, _) => json!({ "byte": ctrl.to_byte() }),
+        AnsiElement::Csi(csi, _) => csi_params(csi),
+        AnsiElement::Osc(osc, terminator, _) => {
+            let mut params = osc_params(osc);
+            if let Value::Object(object) = &mut params {
+                object.insert("terminator".to_string(), terminator_value(*terminator));
+            }
+            params
+        },
+        AnsiElement::ControlString(string, _) => json!({
+            "introducer": string.introducer.name(),
+            "params": string.params,
+            "intermediates": string.intermediates,
+            "final": string.final_byte.map(|c| c.to_string()),
+            "data": String::from_utf8_lossy(&string.data),
+            "terminator": terminator_value(string.terminator),
+        }),
+    }
+}
+
+fn csi_params(csi: &CSI) -> Value {
+    match csi {
+        CSI::CursorUp(n) |
+        CSI::CursorDown(n) |
+        CSI::CursorForward(n) |
+        CSI::CursorBackward(n) |
+        CSI::CursorNextLine(n) |
+        CSI::CursorPreviousLine(n) |
+        CSI::CursorForwardTab(n) |
+        CSI::CursorBackwardTab(n) |
+        CSI::EraseCharacters(n) |
+        CSI::InsertCharacters(n) |
+        CSI::DeleteCharacters(n) |
+        CSI::InsertLines(n) |
+        CSI::DeleteLines(n) |
+        CSI::RepeatCharacter(n) |
+        CSI::ScrollUp(n) |
+        CSI::ScrollDown(n) => json!({ "count": n }),
+        CSI::CursorPosition(row, col) => json!({ "row": row, "column": col }),
+        CSI::CursorToColumn(col) => json!({ "column": col }),
+        CSI::CursorToRow(row) => json!({ "row": row }),
+        CSI::EraseInDisplay(mode) | CSI::EraseInLine(mode) | CSI::TabClear(mode) => json!({ "mode": mode }),
+        CSI::SetGraphicsMode(attributes) => json!({
+            "attributes": attributes.iter().map(|attribute| json!({
+                "params": attribute.params(),
+                "description": attribute.description(),
+            })).collect::<Vec<Value>>(),
+        }),
+        CSI::SetForegroundColor(r, g, b) | CSI::SetBackgroundColor(r, g, b) => json!({ "rgb": [r, g, b] }),
+        CSI::SetForegroundColor256(n) | CSI::SetBackgroundColor256(n) => json!({ "index": n }),
+        CSI::SetMode(modes) |
+        CSI::ResetMode(modes) |
+        CSI::SetPrivateMode(modes) |
+        CSI::ResetPrivateMode(modes) => json!({ "modes": modes }),
+        CSI::SetScrollRegion(top, bottom) => json!({ "top": top, "bottom": bottom }),
+        CSI::WindowManipulation(params) => json!({ "params": params }),
+        CSI::SetCursorStyle(style) => json!({ "style": style }),
+        CSI::SetModifyKeys(resource, value) => json!({ "resource": resource, "value": value }),
+        CSI::PushKeyboardFlags(flags) => json!({ "flags": flags }),
+        CSI::PopKeyboardFlags(count) => json!({ "count": count }),
+        CSI::SetKeyboardFlags(flags, mode) => json!({ "flags": flags, "mode": mode }),
+        CSI::Unrecognized(seq) => json!({
+            "private_marker": seq.private_marker.map(|c| c.to_string()),
+            "params": seq.params.iter().map(|p| p.repr()).collect::<Vec<String>>(),
+            "intermediates": seq.intermediates,
+            "final": seq.final_byte.to_string(),
+        }),
+        CSI::Unknown(body) => json!({ "body": body }),
+        _ => json!({}),
+    }
+}
+
+fn osc_params(osc: &OSC) -> Value {
+    match osc {
+        OSC::SetIconNameAndTitle(title) | OSC::SetIconName(title) | OSC::SetWindowTitle(title) => json!({ "title": title }),
+        OSC::SetPaletteColor(colors) => json!({
+            "colors": colors.iter().map(|(index, spec)| json!({ "index": index, "spec": spec })).collect::<Vec<Value>>(),
+        }),
+        OSC::ResetPaletteColor(indexes) => json!({ "indexes": indexes }),
+        OSC::SetWorkingDirectory(uri) => json!({ "uri": uri }),
+        OSC::Hyperlink(params, uri) => json!({ "params": params, "uri": uri }),
+        OSC::DynamicColor(code, specs) => json!({ "code": code, "specs": specs }),
+        OSC::Clipboard(selection, data) => json!({ "selection": selection, "data": data }),
+        OSC::ShellIntegration(mark, params) => json!({ "mark": mark, "params": params }),
+        OSC::Unknown(payload) => json!({ "payload": payload }),
+    }
+}
@@ -1,2 +1,6 @@
 pub mod table;
-pub mod raw; 
\ No newline at end of file
+pub mod raw;
+pub mod json;
+pub mod html;
+pub mod svg;
+pub mod strip;
\ No newline at end of file
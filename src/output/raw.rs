@@ -1,47 +1,85 @@
 use termio::{Color, StyledText};
-use crate::ansi::AnsiElement;
+use crate::ansi::{AnsiElement, Span, Spanned};
 use crate::formatter::FormatAnsi;
 
 pub struct RawFormatter {
     pub colorize: bool,
+    pub show_offsets: bool,     // Prefix sequences with their byte range
+    pub show_positions: bool,   // Prefix sequences with their line:column
 }
 
 impl RawFormatter {
     pub fn new(colorize: bool) -> Self {
-        Self { colorize }
+        Self { colorize, show_offsets: false, show_positions: false }
+    }
+
+    fn push_element(&self, result: &mut String, element: &AnsiElement, span: Option<&Span>) {
+        match element {
+            AnsiElement::Text(text) => {
+                result.push_str(text);
+            },
+            _ => {
+                if let Some(span) = span {
+                    self.push_location(result, span);
+                }
+
+                // Highlight sequences in the original text
+                if self.colorize {
+                    match element.element_type() {
+                        "CSI" => result.push_str(&element.escape_repr().color(Color::Blue).to_string()),
+                        "Ctrl" => result.push_str(&element.escape_repr().color(Color::Yellow).to_string()),
+                        "OSC" => result.push_str(&element.escape_repr().color(Color::Green).to_string()),
+                        "Invalid" => result.push_str(&element.escape_repr().color(Color::Red).to_string()),
+                        "DCS" | "APC" | "PM" | "SOS" => result.push_str(&element.escape_repr().color(Color::Cyan).to_string()),
+                        _ => result.push_str(&element.escape_repr().color(Color::IntenseMagenta).to_string()),
+                    }
+                } else {
+                    result.push_str(&element.escape_repr());
+                }
+            }
+        }
+    }
+
+    fn push_location(&self, result: &mut String, span: &Span) {
+        let location = match (self.show_offsets, self.show_positions) {
+            (true, true) => format!("[{}..{} {}:{}]", span.start, span.end, span.line, span.column),
+            (true, false) => format!("[{}..{}]", span.start, span.end),
+            (false, true) => format!("[{}:{}]", span.line, span.column),
+            (false, false) => return,
+        };
+
+        if self.colorize {
+            result.push_str(&location.color(Color::IntenseBlack).to_string());
+        } else {
+            result.push_str(&location);
+        }
     }
 }
 
 impl Default for RawFormatter {
     fn default() -> Self {
-        Self { colorize: true }
+        Self::new(true)
     }
 }
 
 impl FormatAnsi for RawFormatter {
     fn format(&self, elements: &[AnsiElement]) -> String {
         let mut result = String::new();
-        
+
         for element in elements {
-            match element {
-                AnsiElement::Text(text) => {
-                    result.push_str(text);
-                },
-                _ => {
-                    // Highlight sequences in the original text
-                    if self.colorize {
-                        match element.element_type() {
-                            "CSI" => result.push_str(&element.escape_repr().color(Color::Blue).to_string()),
-                            "Ctrl" => result.push_str(&element.escape_repr().color(Color::Yellow).to_string()),
-                            _ => result.push_str(&element.escape_repr().color(Color::IntenseMagenta).to_string()),
-                        }
-                    } else {
-                        result.push_str(&element.escape_repr());
-                    }
-                }
-            }
+            self.push_element(&mut result, element, None);
+        }
+
+        result
+    }
+
+    fn format_spanned(&self, elements: &[Spanned]) -> String {
+        let mut result = String::new();
+
+        for spanned in elements {
+            self.push_element(&mut result, &spanned.element, Some(&spanned.span));
         }
-        
+
         result
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,193 @@
+use crate::ansi::{AnsiElement, Spanned, csi::CSI, ctrl::ControlCharacter};
+use crate::formatter::FormatAnsi;
+use crate::parser::ParserError;
+use std::io::Write;
+
+// Columns between tab stops, as set by default on a terminal
+const TAB_WIDTH: usize = 8;
+
+/// Plain text with every escape sequence removed
+pub struct StripFormatter {
+    pub resolve_overstrike: bool,   // Apply CR, BS, tabs and line erases to get the text left visible on each line
+}
+
+impl StripFormatter {
+    pub fn new(resolve_overstrike: bool) -> Self {
+        Self { resolve_overstrike }
+    }
+
+    /// Strip a whole stream, writing the text as it is known
+    pub fn strip(
+        &self,
+        elements: impl Iterator<Item = Result<Spanned, ParserError>>,
+        output: &mut impl Write,
+    ) -> Result<(), ParserError> {
+        // Overstrike never reaches past a line feed, so lines can be resolved one at a time
+        let mut line = Vec::new();
+        for element in elements {
+            let element = element?.element;
+            if !self.resolve_overstrike {
+                write!(output, "{}", self.format(std::slice::from_ref(&element)))?;
+                continue;
+            }
+
+            let end_of_line = matches!(element, AnsiElement::Ctrl(ControlCharacter::LineFeed, _));
+            line.push(element);
+            if end_of_line {
+                write!(output, "{}", self.format(&line))?;
+                line.clear();
+            }
+        }
+        write!(output, "{}", self.format(&line))?;
+        Ok(())
+    }
+}
+
+impl Default for StripFormatter {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl FormatAnsi for StripFormatter {
+    fn format(&self, elements: &[AnsiElement]) -> String {
+        let mut result = String::new();
+        let mut line = LineBuffer::default();
+
+        for element in elements {
+            match element {
+                AnsiElement::Text(text) if self.resolve_overstrike => line.write(text),
+                AnsiElement::Text(text) => result.push_str(text),
+                AnsiElement::InvalidUtf8(_) if self.resolve_overstrike => line.write("\u{FFFD}"),
+                AnsiElement::InvalidUtf8(_) => result.push('\u{FFFD}'),
+                AnsiElement::Ctrl(ctrl, _) => match ctrl {
+                    ControlCharacter::LineFeed | ControlCharacter::VerticalTab | ControlCharacter::FormFeed => {
+                        result.push_str(&line.take());
+                        result.push('\n');
+                    },
+                    ControlCharacter::Tab if self.resolve_overstrike => line.column = (line.column / TAB_WIDTH + 1) * TAB_WIDTH,
+                    ControlCharacter::Tab => result.push('\t'),
+                    ControlCharacter::CarriageReturn if self.resolve_overstrike => line.column = 0,
+                    ControlCharacter::CarriageReturn => result.push('\r'),
+                    ControlCharacter::Backspace if self.resolve_overstrike => line.column = line.column.saturating_sub(1),
+                    ControlCharacter::Backspace => result.push('\u{8}'),
+                    _ => {},
+                },
+                AnsiElement::Csi(CSI::EraseInLine(mode), _) if self.resolve_overstrike => line.erase_in_line(*mode),
+                AnsiElement::Csi(CSI::EraseCharacters(n), _) if self.resolve_overstrike => {
+                    let from = line.column;
+                    line.erase(from, from + (*n).max(1) as usize);
+                },
+                _ => {},
+            }
+        }
+
+        result.push_str(&line.take());
+        result
+    }
+}
+
+// Current line as written so far, with characters overwritten in place
+#[derive(Default)]
+struct LineBuffer {
+    chars: Vec<char>,
+    column: usize,
+}
+
+impl LineBuffer {
+    fn write(&mut self, text: &str) {
+        for c in text.chars() {
+            // A tab may have moved the cursor past the end of the line
+            if self.column > self.chars.len() {
+                self.chars.resize(self.column, ' ');
+            }
+            if self.column < self.chars.len() {
+                self.chars[self.column] = c;
+            } else {
+                self.chars.push(c);
+            }
+            self.column += 1;
+        }
+    }
+
+    // EL: 0 from the cursor to the end, 1 from the start to the cursor, 2 the whole line
+    fn erase_in_line(&mut self, mode: u8) {
+        match mode {
+            0 => self.erase(self.column, usize::MAX),
+            1 => self.erase(0, self.column + 1),
+            2 => self.chars.clear(),
+            _ => {},
+        }
+    }
+
+    // Blank the columns from..to; blanks up to the end of the line are dropped
+    fn erase(&mut self, from: usize, to: usize) {
+        if to >= self.chars.len() {
+            self.chars.truncate(from);
+        } else {
+            for c in &mut self.chars[from.min(to)..to] {
+                *c = ' ';
+            }
+        }
+    }
+
+    fn take(&mut self) -> String {
+        self.column = 0;
+        std::mem::take(&mut self.chars).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::AnsiParser;
+
+    // Stripped as a stream, which must agree with formatting the whole input at once
+    fn strip(input: &[u8], resolve_overstrike: bool) -> String {
+        let formatter = StripFormatter::new(resolve_overstrike);
+        let mut output = Vec::new();
+        formatter.strip(AnsiParser::spanned_elements(input), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, formatter.format(&AnsiParser::parse(input).unwrap()));
+        output
+    }
+
+    fn resolve(input: &[u8]) -> String {
+        strip(input, true)
+    }
+
+    #[test]
+    fn sequences_are_removed() {
+        assert_eq!(strip(b"\x1b[1mbold\x1b[0m \x1b]0;title\x07text\x1b[?25l\n", false), "bold text\n");
+        assert_eq!(strip(b"50%\r100%", false), "50%\r100%");
+    }
+
+    #[test]
+    fn carriage_return_and_backspace_overwrite() {
+        assert_eq!(resolve(b"50%\r100%\n"), "100%\n");
+        assert_eq!(resolve(b"100%\r50%\n"), "50%%\n");
+        assert_eq!(resolve(b"a\x08b"), "b");
+    }
+
+    #[test]
+    fn erase_in_line() {
+        assert_eq!(resolve(b"100%\r\x1b[K50%\n"), "50%\n");
+        assert_eq!(resolve(b"abcdef\r\x1b[0Kx"), "x");
+        assert_eq!(resolve(b"abcdef\r\x08\x08\x1b[2Kx"), "x");
+        assert_eq!(resolve(b"abcdef\rab\x1b[1Kx"), "  xdef");
+    }
+
+    #[test]
+    fn erase_characters() {
+        assert_eq!(resolve(b"abcdef\r\x1b[3Xx"), "x  def");
+        assert_eq!(resolve(b"abcdef\r\x1b[Xx"), "xbcdef");
+        assert_eq!(resolve(b"abc\r\x1b[9X"), "");
+    }
+
+    #[test]
+    fn tabs_move_to_the_next_stop() {
+        assert_eq!(resolve(b"a\tb"), "a       b");
+        assert_eq!(resolve(b"12345678\rxy\tz"), "xy345678z");
+        assert_eq!(strip(b"a\tb", false), "a\tb");
+    }
+}
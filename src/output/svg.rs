@@ -0,0 +1,257 @@
+use crate::ansi::AnsiElement;
+use crate::ansi::sgr::{SgrColor, SgrState, UnderlineStyle, XTERM_PALETTE};
+use crate::formatter::FormatAnsi;
+use crate::screen::{Cell, Screen, DEFAULT_COLUMNS, DEFAULT_ROWS};
+
+const PADDING: f64 = 10.0;
+const CELL_WIDTH_RATIO: f64 = 0.6;     // Advance of a monospace glyph relative to the font size
+const LINE_HEIGHT_RATIO: f64 = 1.2;
+
+/// Colors used for the default foreground/background and the 16-color palette
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub foreground: (u8, u8, u8),
+    pub background: (u8, u8, u8),
+    pub palette: [(u8, u8, u8); 16],
+}
+
+impl Theme {
+    pub const NAMES: [&'static str; 4] = ["dark", "light", "xterm", "solarized"];
+
+    pub fn dark() -> Self {
+        Self {
+            foreground: (212, 212, 212),
+            background: (30, 30, 30),
+            palette: [
+                (0, 0, 0), (205, 49, 49), (13, 188, 121), (229, 229, 16),
+                (36, 114, 200), (188, 63, 188), (17, 168, 205), (229, 229, 229),
+                (102, 102, 102), (241, 76, 76), (35, 209, 139), (245, 245, 67),
+                (59, 142, 234), (214, 112, 214), (41, 184, 219), (255, 255, 255),
+            ],
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            foreground: (51, 51, 51),
+            background: (255, 255, 255),
+            palette: [
+                (0, 0, 0), (205, 49, 49), (0, 188, 0), (148, 152, 0),
+                (4, 81, 165), (188, 5, 188), (5, 152, 188), (85, 85, 85),
+                (102, 102, 102), (205, 49, 49), (20, 206, 20), (181, 186, 0),
+                (4, 81, 165), (188, 5, 188), (5, 152, 188), (165, 165, 165),
+            ],
+        }
+    }
+
+    pub fn xterm() -> Self {
+        Self {
+            foreground: (229, 229, 229),
+            background: (0, 0, 0),
+            palette: XTERM_PALETTE,
+        }
+    }
+
+    pub fn solarized() -> Self {
+        Self {
+            foreground: (131, 148, 150),
+            background: (0, 43, 54),
+            palette: [
+                (7, 54, 66), (220, 50, 47), (133, 153, 0), (181, 137, 0),
+                (38, 139, 210), (211, 54, 130), (42, 161, 152), (238, 232, 213),
+                (0, 43, 54), (203, 75, 22), (88, 110, 117), (101, 123, 131),
+                (131, 148, 150), (108, 113, 196), (147, 161, 161), (253, 246, 227),
+            ],
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "xterm" => Some(Self::xterm()),
+            "solarized" => Some(Self::solarized()),
+            _ => None,
+        }
+    }
+
+    fn color(&self, color: SgrColor) -> (u8, u8, u8) {
+        color.to_rgb(&self.palette)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// Renders the screen left by the input as an SVG image of a terminal
+pub struct SvgFormatter {
+    pub columns: usize,
+    pub rows: usize,            // Trailing blank rows are cropped from the image
+    pub font_size: f64,
+    pub theme: Theme,
+}
+
+impl SvgFormatter {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Self { columns, rows, font_size: 14.0, theme: Theme::default() }
+    }
+
+    /// Draw the visible grid of a screen
+    pub fn render(&self, screen: &Screen) -> String {
+        let cell_width = self.font_size * CELL_WIDTH_RATIO;
+        let line_height = self.font_size * LINE_HEIGHT_RATIO;
+
+        let lines = screen.lines();
+        let used_rows = lines.iter()
+            .rposition(|line| line.iter().any(|cell| cell.text != " " || !cell.style.is_default()))
+            .map_or(1, |last| last + 1);
+
+        let width = PADDING * 2.0 + cell_width * screen.columns() as f64;
+        let height = PADDING * 2.0 + line_height * used_rows as f64;
+
+        let mut svg = String::new();
+        svg.push_str(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.1} {:.1}\" \
+             font-family=\"ui-monospace, Menlo, Consolas, 'DejaVu Sans Mono', monospace\" font-size=\"{}\">\n",
+            width.ceil(), height.ceil(), width, height, self.font_size,
+        ));
+        svg.push_str(&format!(
+            "<rect width=\"100%\" height=\"100%\" rx=\"6\" fill=\"{}\"/>\n",
+            hex(self.theme.background),
+        ));
+
+        for (row, line) in lines[..used_rows].iter().enumerate() {
+            let y = PADDING + line_height * row as f64;
+            let runs = style_runs(line);
+
+            // Backgrounds first so text is drawn on top of them
+            for run in &runs {
+                let (_, background) = self.colors(&run.style);
+                if background != self.theme.background {
+                    svg.push_str(&format!(
+                        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>\n",
+                        PADDING + cell_width * run.start as f64, y,
+                        cell_width * run.columns as f64, line_height,
+                        hex(background),
+                    ));
+                }
+            }
+
+            for run in &runs {
+                // Trailing blanks would only stretch the glyphs over textLength
+                let text = run.text.trim_end_matches(' ');
+                if text.trim().is_empty() || run.style.conceal {
+                    continue;
+                }
+                let columns = run.columns - (run.text.len() - text.len());
+                svg.push_str(&self.text_element(run, text, columns, cell_width, y + self.font_size));
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn text_element(&self, run: &Run, text: &str, columns: usize, cell_width: f64, baseline: f64) -> String {
+        let (foreground, _) = self.colors(&run.style);
+        let mut attributes = format!(
+            "x=\"{:.1}\" y=\"{:.1}\" fill=\"{}\" textLength=\"{:.1}\" lengthAdjust=\"spacingAndGlyphs\"",
+            PADDING + cell_width * run.start as f64, baseline, hex(foreground), cell_width * columns as f64,
+        );
+
+        let style = &run.style;
+        if style.bold {
+            attributes.push_str(" font-weight=\"bold\"");
+        }
+        if style.italic {
+            attributes.push_str(" font-style=\"italic\"");
+        }
+        if style.faint {
+            attributes.push_str(" opacity=\"0.6\"");
+        }
+
+        let mut decorations = Vec::new();
+        if style.underline != UnderlineStyle::None {
+            decorations.push("underline");
+        }
+        if style.crossed_out {
+            decorations.push("line-through");
+        }
+        if style.overline {
+            decorations.push("overline");
+        }
+        if !decorations.is_empty() {
+            attributes.push_str(&format!(" text-decoration=\"{}\"", decorations.join(" ")));
+        }
+
+        format!("<text xml:space=\"preserve\" {}>{}</text>\n", attributes, xml_escape(text))
+    }
+
+    // Effective foreground and background of a style
+    fn colors(&self, style: &SgrState) -> ((u8, u8, u8), (u8, u8, u8)) {
+        let foreground = style.foreground.map_or(self.theme.foreground, |c| self.theme.color(c));
+        let background = style.background.map_or(self.theme.background, |c| self.theme.color(c));
+        if style.reverse {
+            (background, foreground)
+        } else {
+            (foreground, background)
+        }
+    }
+}
+
+impl Default for SvgFormatter {
+    fn default() -> Self {
+        Self::new(DEFAULT_COLUMNS, DEFAULT_ROWS)
+    }
+}
+
+impl FormatAnsi for SvgFormatter {
+    fn format(&self, elements: &[AnsiElement]) -> String {
+        let mut screen = Screen::new(self.columns, self.rows);
+        screen.set_newline_mode(true);
+        screen.apply_all(elements);
+        self.render(&screen)
+    }
+}
+
+// Consecutive cells of a row sharing one style
+struct Run {
+    start: usize,
+    columns: usize,
+    text: String,
+    style: SgrState,
+}
+
+fn style_runs(line: &[Cell]) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for (col, cell) in line.iter().enumerate() {
+        match runs.last_mut() {
+            Some(run) if run.style == cell.style => {
+                run.columns += 1;
+                run.text.push_str(&cell.text);
+            },
+            _ => runs.push(Run { start: col, columns: 1, text: cell.text.clone(), style: cell.style }),
+        }
+    }
+    runs
+}
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -1,34 +1,90 @@
+use tabled::builder::Builder;
 use tabled::settings::{Style, Alignment};
-use tabled::{Table, Tabled};
 use termio::{Color, Decoration, StyledText};
-use crate::ansi::AnsiElement;
+use unicode_width::UnicodeWidthStr;
+use crate::ansi::{AnsiElement, Span, Spanned};
 use crate::formatter::FormatAnsi;
 
-#[derive(Tabled)]
-struct Row {
-    #[tabled(rename = "Type")]
-    element_type: String,
-    
-    #[tabled(rename = "Esc")]
-    escape: String,
-    
-    #[tabled(rename = "Desc")]
-    description: String,
-}
+// Column widths used when rows are streamed and the whole table can't be measured
+const STREAM_TYPE_WIDTH: usize = 7;
+const STREAM_ESC_WIDTH: usize = 24;
+const STREAM_OFFSET_WIDTH: usize = 12;
+const STREAM_POSITION_WIDTH: usize = 9;
 
 pub struct TableFormatter {
     pub colorize: bool,
+    pub show_offsets: bool,     // Byte range column
+    pub show_positions: bool,   // Line:column column
 }
 
 impl TableFormatter {
     pub fn new(colorize: bool) -> Self {
-        Self { colorize }
+        Self { colorize, show_offsets: false, show_positions: false }
     }
 }
 
 impl Default for TableFormatter {
     fn default() -> Self {
-        Self { colorize: true }
+        Self::new(true)
+    }
+}
+
+impl TableFormatter {
+    fn styled_type(&self, element: &AnsiElement, text: &str) -> String {
+        if !self.colorize {
+            return text.to_string();
+        }
+        match element.element_type() {
+            "Text" => text.color(Color::IntenseBlack).to_string(),
+            "Invalid" => text.color(Color::Red).decoration(Decoration::Bold).to_string(),
+            "CSI" => text.color(Color::Blue).decoration(Decoration::Bold).to_string(),
+            "OSC" => text.color(Color::Green).decoration(Decoration::Bold).to_string(),
+            "DCS" | "APC" | "PM" | "SOS" => text.color(Color::Cyan).decoration(Decoration::Bold).to_string(),
+            "Ctrl" => text.color(Color::Yellow).decoration(Decoration::Bold).to_string(),
+            _ => text.to_string(),
+        }
+    }
+
+    fn styled_escape(&self, text: &str) -> String {
+        if self.colorize {
+            text.color(Color::IntenseMagenta).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn styled_location(&self, text: &str) -> String {
+        if self.colorize {
+            text.color(Color::IntenseBlack).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn header(&self) -> Vec<String> {
+        let mut header = Vec::new();
+        if self.show_offsets {
+            header.push("Offset".to_string());
+        }
+        if self.show_positions {
+            header.push("Pos".to_string());
+        }
+        header.extend(["Type".to_string(), "Esc".to_string(), "Desc".to_string()]);
+        header
+    }
+
+    fn build(&self, rows: Vec<Vec<String>>) -> String {
+        let mut builder = Builder::new();
+        builder.push_record(self.header());
+        for row in rows {
+            builder.push_record(row);
+        }
+
+        let mut table = builder.build();
+        table.with(Style::empty())
+             .with(Alignment::left());
+
+        table.to_string()
     }
 }
 
@@ -38,35 +94,84 @@ impl FormatAnsi for TableFormatter {
             return String::new();
         }
 
-        let rows: Vec<Row> = elements.iter().map(|element| {
-            let element_type = if self.colorize {
-                match element.element_type() {
-                    "Text" => element.element_type().color(Color::IntenseBlack).to_string(),
-                    "CSI" => element.element_type().color(Color::Blue).decoration(Decoration::Bold).to_string(),
-                    "Ctrl" => element.element_type().color(Color::Yellow).decoration(Decoration::Bold).to_string(),
-                    _ => element.element_type().to_string(),
-                }
-            } else {
-                element.element_type().to_string()
-            };
-            
-            let escape = if self.colorize {
-                element.escape_repr().color(Color::IntenseMagenta).to_string()
-            } else {
-                element.escape_repr()
-            };
-            
-            Row {
-                element_type,
-                escape,
-                description: element.description(),
+        // Without spans there is nothing to put in the location columns
+        let formatter = TableFormatter { show_offsets: false, show_positions: false, ..*self };
+        let rows = elements.iter().map(|element| {
+            vec![
+                formatter.styled_type(element, element.element_type()),
+                formatter.styled_escape(&element.escape_repr()),
+                element.description(),
+            ]
+        }).collect();
+
+        formatter.build(rows)
+    }
+
+    fn format_spanned(&self, elements: &[Spanned]) -> String {
+        if elements.is_empty() {
+            return String::new();
+        }
+
+        let rows = elements.iter().map(|spanned| {
+            let element = &spanned.element;
+            let mut row = Vec::new();
+            if self.show_offsets {
+                row.push(self.styled_location(&offset_repr(&spanned.span)));
+            }
+            if self.show_positions {
+                row.push(self.styled_location(&position_repr(&spanned.span)));
             }
+            row.push(self.styled_type(element, element.element_type()));
+            row.push(self.styled_escape(&element.escape_repr()));
+            row.push(element.description());
+            row
         }).collect();
-        
-        let mut table = Table::new(rows);
-        table.with(Style::empty())
-             .with(Alignment::left());
 
-        table.to_string()
+        self.build(rows)
+    }
+
+    fn stream_header(&self) -> String {
+        let mut header = String::new();
+        if self.show_offsets {
+            header.push_str(&format!(" {} ", pad("Offset", STREAM_OFFSET_WIDTH)));
+        }
+        if self.show_positions {
+            header.push_str(&format!(" {} ", pad("Pos", STREAM_POSITION_WIDTH)));
+        }
+        header.push_str(&format!(" {}  {}  Desc\n", pad("Type", STREAM_TYPE_WIDTH), pad("Esc", STREAM_ESC_WIDTH)));
+        header
     }
-} 
\ No newline at end of file
+
+    fn format_element(&self, spanned: &Spanned) -> String {
+        let element = &spanned.element;
+        let mut row = String::new();
+
+        // Pad before styling so escape codes don't count towards the width
+        if self.show_offsets {
+            let offset = pad(&offset_repr(&spanned.span), STREAM_OFFSET_WIDTH);
+            row.push_str(&format!(" {} ", self.styled_location(&offset)));
+        }
+        if self.show_positions {
+            let position = pad(&position_repr(&spanned.span), STREAM_POSITION_WIDTH);
+            row.push_str(&format!(" {} ", self.styled_location(&position)));
+        }
+        let element_type = self.styled_type(element, &pad(element.element_type(), STREAM_TYPE_WIDTH));
+        let escape = self.styled_escape(&pad(&element.escape_repr(), STREAM_ESC_WIDTH));
+        row.push_str(&format!(" {}  {}  {}\n", element_type, escape, element.description()));
+        row
+    }
+}
+
+fn offset_repr(span: &Span) -> String {
+    format!("{}..{}", span.start, span.end)
+}
+
+fn position_repr(span: &Span) -> String {
+    format!("{}:{}", span.line, span.column)
+}
+
+// Pad text with spaces up to the given display width
+fn pad(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text.width());
+    format!("{}{}", text, " ".repeat(padding))
+}
@@ -1,4 +1,5 @@
-use crate::ansi::{AnsiElement, csi, ctrl};
+use crate::ansi::{AnsiElement, Span, Spanned, csi, ctrl, osc, sgr, string};
+use std::collections::VecDeque;
 use std::io::{self, Read};
 use thiserror::Error;
 
@@ -11,340 +12,629 @@ pub enum ParserError {
     InvalidSequence(String),
 }
 
-pub struct AnsiParser;
+// Size of the chunks read from the input when streaming
+const READ_CHUNK_SIZE: usize = 8192;
+
+// Location of the next unconsumed byte in the original input
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self { offset: 0, line: 1, column: 1 }
+    }
+}
+
+impl Position {
+    fn span_to(&self, end: Position) -> Span {
+        Span {
+            start: self.offset,
+            end: end.offset,
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+/// Push-style ANSI parser: feed it bytes, get elements back as soon as they are complete.
+///
+/// Incomplete sequences, text runs and literal `\e` escapes are kept across
+/// calls to `feed`, so input can be split at any byte boundary.
+#[derive(Debug, Default)]
+pub struct AnsiParser {
+    buf: Vec<u8>,
+    expanded: Vec<Option<u8>>,  // Letter of the literal "\e" or "\E" each buffered byte came from
+    position: Position,         // Position of the first buffered byte
+    text_buf: Vec<u8>,
+    text_start: Position,
+    pending_backslash: bool,
+    string_scanned: usize,      // Bytes of an incomplete OSC or control string at the start of buf already searched for its terminator
+}
 
 impl AnsiParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
     /// Parse ANSI sequences from input
-    pub fn parse<R: Read>(mut input: R) -> Result<Vec<AnsiElement>, ParserError> {
+    pub fn parse<R: Read>(input: R) -> Result<Vec<AnsiElement>, ParserError> {
+        Self::elements(input).collect()
+    }
+    
+    /// Parse ANSI sequences from input, keeping where each one came from
+    pub fn parse_spanned<R: Read>(input: R) -> Result<Vec<Spanned>, ParserError> {
+        Self::spanned_elements(input).collect()
+    }
+    
+    /// Iterate over the elements of a reader, reading it chunk by chunk
+    pub fn elements<R: Read>(input: R) -> Elements<R> {
+        Elements(Self::spanned_elements(input))
+    }
+    
+    /// Iterate over the elements of a reader along with their spans
+    pub fn spanned_elements<R: Read>(input: R) -> SpannedElements<R> {
+        SpannedElements {
+            input,
+            parser: AnsiParser::new(),
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+    
+    /// Feed a chunk of input and return the elements completed by it
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Spanned> {
+        self.expand_literal_escapes(bytes);
+        self.process(false)
+    }
+    
+    /// Signal the end of input and return everything still buffered
+    pub fn finish(&mut self) -> Vec<Spanned> {
+        if std::mem::take(&mut self.pending_backslash) {
+            self.buf.push(b'\\');
+            self.expanded.push(None);
+        }
+        self.process(true)
+    }
+    
+    // Process buffered bytes into elements, keeping an incomplete trailing sequence unless at EOF
+    fn process(&mut self, eof: bool) -> Vec<Spanned> {
         let mut elements = Vec::new();
-        let mut buf = Vec::new();
-        
-        // Read all input into buffer
-        input.read_to_end(&mut buf)?;
-        
-        // Process buffer into elements - first handle literal "\e" sequences
-        let expanded_buf = Self::expand_literal_escapes(&buf);
-        
-        // Process buffer into elements
+        let mut position = self.position;
         let mut i = 0;
-        let mut text_buf = String::new();
         
-        while i < expanded_buf.len() {
-            if expanded_buf[i] == 0x1B {  // ESC character
+        while i < self.buf.len() {
+            if self.buf[i] == 0x1B {  // ESC character
                 // First, add accumulated text if any
-                if !text_buf.is_empty() {
-                    elements.push(AnsiElement::Text(std::mem::take(&mut text_buf)));
-                }
+                self.flush_text(&mut elements);
                 
-                // Process escape sequence
-                if i + 1 >= expanded_buf.len() {
-                    // Just an ESC at the end
-                    elements.push(AnsiElement::Ctrl(ctrl::ControlCharacter::Escape));
-                    i += 1;
-                    continue;
-                }
-                
-                match expanded_buf[i + 1] {
-                    b'[' => { // CSI sequence
-                        let (elem, consumed) = Self::parse_csi(&expanded_buf[i..]);
-                        elements.push(elem);
+                // Process escape sequence, waiting for more input if it is incomplete
+                // Only a sequence left incomplete by the previous call can have been searched already
+                let mut scanned = if i == 0 { self.string_scanned } else { 0 };
+                let parsed = Self::parse_escape(&self.buf[i..], eof, &mut scanned);
+                self.string_scanned = scanned;
+                match parsed {
+                    Some((elem, consumed)) => {
+                        self.string_scanned = 0;
+                        let end = self.advance(position, i, i + consumed);
+                        let raw = self.raw_bytes(i, i + consumed);
+                        elements.push(Spanned::new(elem.with_verbatim(&raw), position.span_to(end), raw));
+                        position = end;
                         i += consumed;
                     }
-                    
-                    // Simple escape sequences now handled as CSI
-                    b'7' => {
-                        elements.push(AnsiElement::Csi(csi::CSI::CursorSavePosition));
-                        i += 2;
-                    }
-                    b'8' => {
-                        elements.push(AnsiElement::Csi(csi::CSI::CursorRestorePosition));
-                        i += 2;
-                    }
-                    b'=' => {
-                        elements.push(AnsiElement::Csi(csi::CSI::ApplicationKeypadMode));
-                        i += 2;
-                    }
-                    b'>' => {
-                        elements.push(AnsiElement::Csi(csi::CSI::NumericKeypadMode));
-                        i += 2;
-                    }
-                    
-                    // G0 character set
-                    b'(' => {
-                        if i + 2 < expanded_buf.len() {
-                            match expanded_buf[i + 2] {
-                                b'0' => {
-                                    elements.push(AnsiElement::Csi(csi::CSI::SetG0SpecialChars));
-                                    i += 3;
-                                }
-                                b'B' => {
-                                    elements.push(AnsiElement::Csi(csi::CSI::SetG0NormalChars));
-                                    i += 3;
-                                }
-                                _ => {
-                                    // Unknown G0 sequence
-                                    let seq = format!("({}", expanded_buf[i + 2] as char);
-                                    elements.push(AnsiElement::Csi(csi::CSI::Unknown(seq)));
-                                    i += 3;
-                                }
-                            }
-                        } else {
-                            // Incomplete sequence
-                            let seq = if i + 2 == expanded_buf.len() {
-                                format!("({})", expanded_buf[i + 2] as char)
-                            } else {
-                                "(".to_string()
-                            };
-                            elements.push(AnsiElement::Csi(csi::CSI::Unknown(seq)));
-                            i = expanded_buf.len(); // End processing
-                        }
-                    }
-                    
-                    // Unrecognized escape sequence - treat as unknown CSI
-                    _ => {
-                        if expanded_buf[i + 1] < 128 {
-                            let seq = format!("{}", expanded_buf[i + 1] as char);
-                            elements.push(AnsiElement::Csi(csi::CSI::Unknown(seq)));
-                        } else {
-                            let seq = format!("0x{:02X}", expanded_buf[i + 1]);
-                            elements.push(AnsiElement::Csi(csi::CSI::Unknown(seq)));
-                        }
-                        i += 2;
-                    }
+                    None => break,
                 }
-            } else if let Some(ctrl_char) = ctrl::ControlCharacter::from_byte(expanded_buf[i]) {
+            } else if let Some(ctrl_char) = ctrl::ControlCharacter::from_byte(self.buf[i]) {
                 // Control character
-                if !text_buf.is_empty() {
-                    elements.push(AnsiElement::Text(std::mem::take(&mut text_buf)));
-                }
-                elements.push(AnsiElement::Ctrl(ctrl_char));
+                self.flush_text(&mut elements);
+                let end = self.advance(position, i, i + 1);
+                elements.push(Spanned::new(AnsiElement::Ctrl(ctrl_char, None), position.span_to(end), vec![self.buf[i]]));
+                position = end;
                 i += 1;
             } else {
                 // Regular text character
-                text_buf.push(expanded_buf[i] as char);
+                if self.text_buf.is_empty() {
+                    self.text_start = position;
+                }
+                self.text_buf.push(self.buf[i]);
+                position = self.advance(position, i, i + 1);
                 i += 1;
             }
         }
         
+        self.buf.drain(..i);
+        self.expanded.drain(..i);
+        self.position = position;
+        
         // Add any remaining text
-        if !text_buf.is_empty() {
-            elements.push(AnsiElement::Text(text_buf));
+        if eof {
+            self.flush_text(&mut elements);
         }
         
-        Ok(elements)
+        elements
     }
     
-    // Helper function to expand literal escape sequences
-    fn expand_literal_escapes(buf: &[u8]) -> Vec<u8> {
-        let mut result = Vec::with_capacity(buf.len());
+    // Move a position over buffered bytes, counting literal "\e" as the two bytes it was
+    fn advance(&self, mut position: Position, from: usize, to: usize) -> Position {
+        for i in from..to {
+            if self.expanded[i].is_some() {
+                position.offset += 2;
+                position.column += 2;
+                continue;
+            }
+            position.offset += 1;
+            match self.buf[i] {
+                b'\n' => {
+                    position.line += 1;
+                    position.column = 1;
+                }
+                // UTF-8 continuation bytes belong to the previous character
+                0x80..=0xBF => {}
+                _ => position.column += 1,
+            }
+        }
+        position
+    }
+    
+    // Buffered bytes as they appeared in the input, with literal escapes restored
+    fn raw_bytes(&self, from: usize, to: usize) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(to - from);
+        for i in from..to {
+            match self.expanded[i] {
+                Some(letter) => raw.extend([b'\\', letter]),
+                None => raw.push(self.buf[i]),
+            }
+        }
+        raw
+    }
+    
+    // Parse the escape sequence at the start of buf, None if more input is needed.
+    // String sequences resume their terminator search at `scanned` and leave it where they stopped.
+    fn parse_escape(buf: &[u8], eof: bool, scanned: &mut usize) -> Option<(AnsiElement, usize)> {
+        if buf.len() < 2 {
+            // Just an ESC at the end
+            return eof.then_some((AnsiElement::Ctrl(ctrl::ControlCharacter::Escape, None), 1));
+        }
+        
+        let parsed = match buf[1] {
+            b'[' => return Self::parse_csi(buf, eof), // CSI sequence
+            b']' => return Self::parse_osc(buf, eof, scanned), // OSC sequence
+            b'P' | b'_' | b'^' | b'X' => return Self::parse_control_string(buf, eof, scanned), // DCS, APC, PM, SOS
+            
+            // Simple escape sequences now handled as CSI
+            b'7' => (AnsiElement::Csi(csi::CSI::CursorSavePosition, None), 2),
+            b'8' => (AnsiElement::Csi(csi::CSI::CursorRestorePosition, None), 2),
+            b'=' => (AnsiElement::Csi(csi::CSI::ApplicationKeypadMode, None), 2),
+            b'>' => (AnsiElement::Csi(csi::CSI::NumericKeypadMode, None), 2),
+            b'D' => (AnsiElement::Csi(csi::CSI::Index, None), 2),
+            b'E' => (AnsiElement::Csi(csi::CSI::NextLine, None), 2),
+            b'H' => (AnsiElement::Csi(csi::CSI::TabSet, None), 2),
+            b'M' => (AnsiElement::Csi(csi::CSI::ReverseIndex, None), 2),
+            b'c' => (AnsiElement::Csi(csi::CSI::FullReset, None), 2),
+            
+            // G0 character set
+            b'(' => {
+                if buf.len() < 3 {
+                    // Incomplete sequence
+                    if !eof {
+                        return None;
+                    }
+                    (AnsiElement::Csi(csi::CSI::Unknown("(".to_string()), None), 2)
+                } else {
+                    match buf[2] {
+                        b'0' => (AnsiElement::Csi(csi::CSI::SetG0SpecialChars, None), 3),
+                        b'B' => (AnsiElement::Csi(csi::CSI::SetG0NormalChars, None), 3),
+                        _ => {
+                            // Unknown G0 sequence
+                            let seq = format!("({}", buf[2] as char);
+                            (AnsiElement::Csi(csi::CSI::Unknown(seq), None), 3)
+                        }
+                    }
+                }
+            }
+            
+            // Unrecognized escape sequence - treat as unknown CSI
+            _ => {
+                let seq = if buf[1] < 128 {
+                    format!("{}", buf[1] as char)
+                } else {
+                    format!("0x{:02X}", buf[1])
+                };
+                (AnsiElement::Csi(csi::CSI::Unknown(seq), None), 2)
+            }
+        };
+        
+        Some(parsed)
+    }
+    
+    // Decode accumulated text bytes as UTF-8, splitting out invalid or truncated sequences
+    fn flush_text(&mut self, elements: &mut Vec<Spanned>) {
+        let start = self.text_start;
+        // Text never contains line feeds or literal escapes, so positions are easy to derive
+        let position_at = |buf: &[u8], index: usize| Position {
+            offset: start.offset + index,
+            line: start.line,
+            column: start.column + buf[..index].iter().filter(|b| !(0x80..=0xBF).contains(*b)).count(),
+        };
+        
+        let mut index = 0;
+        while index < self.text_buf.len() {
+            let rest = &self.text_buf[index..];
+            let (element, len) = match std::str::from_utf8(rest) {
+                Ok(text) => (AnsiElement::Text(text.to_string()), rest.len()),
+                Err(err) if err.valid_up_to() > 0 => {
+                    let valid = err.valid_up_to();
+                    // The prefix is valid, so nothing gets replaced here
+                    (AnsiElement::Text(String::from_utf8_lossy(&rest[..valid]).to_string()), valid)
+                }
+                Err(err) => {
+                    // No error length means the sequence was cut off by the end of the run
+                    let invalid_len = err.error_len().unwrap_or(rest.len());
+                    (AnsiElement::InvalidUtf8(rest[..invalid_len].to_vec()), invalid_len)
+                }
+            };
+            
+            let span = position_at(&self.text_buf, index).span_to(position_at(&self.text_buf, index + len));
+            elements.push(Spanned::new(element, span, rest[..len].to_vec()));
+            index += len;
+        }
+        
+        self.text_buf.clear();
+    }
+    
+    // Expand literal "\e" sequences into ESC while appending to the buffer
+    fn expand_literal_escapes(&mut self, bytes: &[u8]) {
+        self.buf.reserve(bytes.len() + 1);
+        self.expanded.reserve(bytes.len() + 1);
+        
         let mut i = 0;
+        if std::mem::take(&mut self.pending_backslash) {
+            match bytes.first() {
+                Some(&letter @ (b'e' | b'E')) => {
+                    self.buf.push(0x1B);
+                    self.expanded.push(Some(letter));
+                    i = 1;
+                }
+                Some(_) => {
+                    self.buf.push(b'\\');
+                    self.expanded.push(None);
+                }
+                None => {
+                    self.pending_backslash = true;
+                    return;
+                }
+            }
+        }
         
-        while i < buf.len() {
+        while i < bytes.len() {
             // Look for \e escape sequence
-            if i + 1 < buf.len() && buf[i] == b'\\' && (buf[i + 1] == b'e' || buf[i + 1] == b'E') {
+            if bytes[i] == b'\\' && i + 1 == bytes.len() {
+                // Can't tell yet, wait for the next chunk
+                self.pending_backslash = true;
+                i += 1;
+            } else if bytes[i] == b'\\' && (bytes[i + 1] == b'e' || bytes[i + 1] == b'E') {
                 // Replace \e with ESC (0x1B)
-                result.push(0x1B);
+                self.buf.push(0x1B);
+                self.expanded.push(Some(bytes[i + 1]));
                 i += 2;
             } else {
                 // Pass through other characters
-                result.push(buf[i]);
+                self.buf.push(bytes[i]);
+                self.expanded.push(None);
                 i += 1;
             }
         }
+    }
+    
+    // Parse an OSC sequence terminated by BEL or ST, return the element and number of bytes consumed
+    fn parse_osc(buf: &[u8], eof: bool, scanned: &mut usize) -> Option<(AnsiElement, usize)> {
+        let start = 2; // Skip ESC ]
+        let mut i = start.max(*scanned);
+        
+        let (end, consumed, terminator) = loop {
+            if i >= buf.len() || (buf[i] == 0x1B && i + 1 == buf.len() && !eof) {
+                // Input ended before the terminator
+                if !eof {
+                    *scanned = i;
+                    return None;
+                }
+                break (i, i, osc::Terminator::Unterminated);
+            }
+            match buf[i] {
+                0x07 => break (i, i + 1, osc::Terminator::Bel),
+                0x1B if i + 1 < buf.len() && buf[i + 1] == b'\\' => break (i, i + 2, osc::Terminator::St),
+                // Any other escape aborts the OSC and starts a new sequence
+                0x1B => break (i, i, osc::Terminator::Unterminated),
+                _ => i += 1,
+            }
+        };
+        
+        let payload = String::from_utf8_lossy(&buf[start..end]);
+        Some((AnsiElement::Osc(osc::OSC::from_payload(&payload), terminator, None), consumed))
+    }
+    
+    // Parse a DCS, APC, PM or SOS control string up to ST, return the element and number of bytes consumed
+    fn parse_control_string(buf: &[u8], eof: bool, scanned: &mut usize) -> Option<(AnsiElement, usize)> {
+        let introducer = string::Introducer::from_byte(buf[1]).unwrap_or(string::Introducer::Dcs);
+        let mut i = 2; // Skip ESC and the introducer
+        let mut params = String::new();
+        let mut intermediates = String::new();
+        let mut final_byte = None;
+        
+        // Only DCS has a CSI-like header: params, intermediates, final byte
+        if introducer == string::Introducer::Dcs {
+            let mut j = i;
+            while j < buf.len() && (0x30..=0x3F).contains(&buf[j]) {
+                j += 1;
+            }
+            let params_end = j;
+            while j < buf.len() && (0x20..=0x2F).contains(&buf[j]) {
+                j += 1;
+            }
+            if j < buf.len() && (0x40..=0x7E).contains(&buf[j]) {
+                params = String::from_utf8_lossy(&buf[i..params_end]).to_string();
+                intermediates = String::from_utf8_lossy(&buf[params_end..j]).to_string();
+                final_byte = Some(buf[j] as char);
+                i = j + 1;
+            }
+        }
+        
+        // tmux passthrough doubles every ESC in the wrapped sequence
+        let tmux = final_byte == Some('t') && buf[i..].starts_with(b"mux;");
         
-        result
+        // Bytes searched before held no terminator, and stopped short of any ESC pair
+        let start = i;
+        i = i.max(*scanned);
+        let (end, consumed, terminator) = loop {
+            if i >= buf.len() || (buf[i] == 0x1B && i + 1 == buf.len() && !eof) {
+                // Input ended before the terminator
+                if !eof {
+                    *scanned = i;
+                    return None;
+                }
+                break (i, i, osc::Terminator::Unterminated);
+            }
+            match buf[i] {
+                0x1B if i + 1 < buf.len() && buf[i + 1] == b'\\' => break (i, i + 2, osc::Terminator::St),
+                0x1B if tmux && i + 1 < buf.len() && buf[i + 1] == 0x1B => i += 2,
+                // Any other escape aborts the string and starts a new sequence
+                0x1B => break (i, i, osc::Terminator::Unterminated),
+                _ => i += 1,
+            }
+        };
+        
+        let control_string = string::ControlString {
+            introducer,
+            params,
+            intermediates,
+            final_byte,
+            data: buf[start..end].to_vec(),
+            terminator,
+        };
+        Some((AnsiElement::ControlString(control_string, None), consumed))
     }
     
     // Parse a CSI sequence, return the element and number of bytes consumed
-    fn parse_csi(buf: &[u8]) -> (AnsiElement, usize) {
+    fn parse_csi(buf: &[u8], eof: bool) -> Option<(AnsiElement, usize)> {
         let mut i = 2; // Skip ESC [
-        let mut params_u8 = Vec::new();
-        let mut params_u16 = Vec::new();
-        let mut current_param = String::new();
-        let mut is_private = false;
         
-        // Check for private sequences (like ESC [ ? ...)
-        if i < buf.len() && buf[i] == b'?' {
-            is_private = true;
+        // Parameter bytes, including private markers and ':' sub-parameter separators
+        let params_start = i;
+        while i < buf.len() && (0x30..=0x3F).contains(&buf[i]) {
             i += 1;
         }
+        let params_end = i;
         
-        // Parse parameters
-        while i < buf.len() && ((buf[i] as char).is_ascii_digit() || buf[i] == b';') {
-            if buf[i] == b';' {
-                if !current_param.is_empty() {
-                    let param_value = current_param.parse::<u16>().unwrap_or(0);
-                    params_u16.push(param_value);
-                    if param_value <= 255 {
-                        params_u8.push(param_value as u8);
-                    } else {
-                        params_u8.push(0); // Fallback for out-of-range values
-                    }
-                } else {
-                    params_u16.push(0);
-                    params_u8.push(0); // Empty parameter defaults to 0
-                }
-                current_param.clear();
-            } else {
-                current_param.push(buf[i] as char);
-            }
+        // Intermediate bytes
+        while i < buf.len() && (0x20..=0x2F).contains(&buf[i]) {
             i += 1;
         }
         
-        // Add the last parameter if any
-        if !current_param.is_empty() {
-            let param_value = current_param.parse::<u16>().unwrap_or(0);
-            params_u16.push(param_value);
-            if param_value <= 255 {
-                params_u8.push(param_value as u8);
-            } else {
-                params_u8.push(0); // Fallback for out-of-range values
-            }
+        if i >= buf.len() && !eof {
+            // Wait for the rest of the sequence
+            return None;
         }
         
-        // Process by command byte
-        if i < buf.len() {
-            let cmd_byte = buf[i] as char;
-            let element = match cmd_byte {
-                'A' => {
-                    let n = params_u16.first().copied().unwrap_or(1) as u32;
-                    AnsiElement::Csi(csi::CSI::CursorUp(n))
-                }
-                'B' => {
-                    let n = params_u16.first().copied().unwrap_or(1) as u32;
-                    AnsiElement::Csi(csi::CSI::CursorDown(n))
-                }
-                'C' => {
-                    let n = params_u16.first().copied().unwrap_or(1) as u32;
-                    AnsiElement::Csi(csi::CSI::CursorForward(n))
-                }
-                'D' => {
-                    let n = params_u16.first().copied().unwrap_or(1) as u32;
-                    AnsiElement::Csi(csi::CSI::CursorBackward(n))
-                }
-                'E' => {
-                    let n = params_u16.first().copied().unwrap_or(1) as u32;
-                    AnsiElement::Csi(csi::CSI::CursorNextLine(n))
-                }
-                'F' => {
-                    let n = params_u16.first().copied().unwrap_or(1) as u32;
-                    AnsiElement::Csi(csi::CSI::CursorPreviousLine(n))
-                }
-                'G' => {
-                    let n = params_u16.first().copied().unwrap_or(1) as u32;
-                    AnsiElement::Csi(csi::CSI::CursorToColumn(n))
-                }
-                'H' | 'f' => {
-                    let row = params_u16.first().copied().unwrap_or(1) as u32;
-                    let col = params_u16.get(1).copied().unwrap_or(1) as u32;
-                    AnsiElement::Csi(csi::CSI::CursorPosition(row, col))
-                }
-                'J' => {
-                    let n = params_u8.first().copied().unwrap_or(0);
-                    AnsiElement::Csi(csi::CSI::EraseInDisplay(n))
-                }
-                'K' => {
-                    let n = params_u8.first().copied().unwrap_or(0);
-                    AnsiElement::Csi(csi::CSI::EraseInLine(n))
-                }
-                'S' => {
-                    let n = params_u16.first().copied().unwrap_or(1) as u32;
-                    AnsiElement::Csi(csi::CSI::ScrollUp(n))
-                }
-                'T' => {
-                    let n = params_u16.first().copied().unwrap_or(1) as u32;
-                    AnsiElement::Csi(csi::CSI::ScrollDown(n))
-                }
-                's' => AnsiElement::Csi(csi::CSI::CursorSavePosition),
-                'u' => AnsiElement::Csi(csi::CSI::CursorRestorePosition),
-                'h' => AnsiElement::Csi(csi::CSI::SetMode(params_u16)),
-                'l' => AnsiElement::Csi(csi::CSI::ResetMode(params_u16)),
-                't' => AnsiElement::Csi(csi::CSI::WindowManipulation(params_u16)),
-                'n' => {
-                    if is_private && !params_u8.is_empty() && params_u8[0] == 6 {
-                        AnsiElement::Csi(csi::CSI::CursorPositionReport)
-                    } else if !params_u8.is_empty() && params_u8[0] == 6 {
-                        AnsiElement::Csi(csi::CSI::DeviceStatusReport)
-                    } else {
-                        // Unknown report request
-                        let mut seq = String::new();
-                        if is_private {
-                            seq.push('?');
-                        }
-                        for p in &params_u16 {
-                            seq.push_str(&p.to_string());
-                            seq.push(';');
-                        }
-                        if !seq.is_empty() && seq.ends_with(';') {
-                            seq.pop(); // Remove trailing semicolon
-                        }
-                        seq.push('n');
-                        AnsiElement::Csi(csi::CSI::Unknown(seq))
-                    }
-                }
-                'm' => {
-                    if params_u8.is_empty() {
-                        AnsiElement::Csi(csi::CSI::ResetAttributes)
-                    } else if params_u8.len() >= 3 && params_u8[0] == 38 && params_u8[1] == 2 {
-                        // 24-bit RGB color (38;2;r;g;b)
-                        let r = params_u8.get(2).copied().unwrap_or(0);
-                        let g = params_u8.get(3).copied().unwrap_or(0);
-                        let b = params_u8.get(4).copied().unwrap_or(0);
-                        AnsiElement::Csi(csi::CSI::SetForegroundColor(r, g, b))
-                    } else if params_u8.len() >= 3 && params_u8[0] == 48 && params_u8[1] == 2 {
-                        // 24-bit RGB color (48;2;r;g;b)
-                        let r = params_u8.get(2).copied().unwrap_or(0);
-                        let g = params_u8.get(3).copied().unwrap_or(0);
-                        let b = params_u8.get(4).copied().unwrap_or(0);
-                        AnsiElement::Csi(csi::CSI::SetBackgroundColor(r, g, b))
-                    } else if params_u8.len() >= 3 && params_u8[0] == 38 && params_u8[1] == 5 {
-                        // 8-bit/256 color (38;5;n)
-                        let color_index = params_u8.get(2).copied().unwrap_or(0);
-                        // Convert to approximate RGB for display
-                        // This is a simplification - in a real terminal, there would be a color table
-                        AnsiElement::Csi(csi::CSI::SetForegroundColor256(color_index))
-                    } else if params_u8.len() >= 3 && params_u8[0] == 48 && params_u8[1] == 5 {
-                        // 8-bit/256 color (48;5;n)
-                        let color_index = params_u8.get(2).copied().unwrap_or(0);
-                        AnsiElement::Csi(csi::CSI::SetBackgroundColor256(color_index))
-                    } else {
-                        AnsiElement::Csi(csi::CSI::SetGraphicsMode(params_u8))
-                    }
-                }
-                _ => {
-                    // Unknown CSI sequence
-                    let mut seq = String::new();
-                    if is_private {
-                        seq.push('?');
-                    }
-                    for p in &params_u16 {
-                        seq.push_str(&p.to_string());
-                        seq.push(';');
-                    }
-                    if !seq.is_empty() && seq.ends_with(';') {
-                        seq.pop(); // Remove trailing semicolon
-                    }
-                    seq.push(cmd_byte);
-                    AnsiElement::Csi(csi::CSI::Unknown(seq))
+        if i >= buf.len() || !(0x40..=0x7E).contains(&buf[i]) {
+            // Incomplete or malformed CSI sequence, the offending byte is left for the caller
+            let seq = String::from_utf8_lossy(&buf[params_start..i]).to_string();
+            return Some((AnsiElement::Csi(csi::CSI::Unknown(seq), None), i));
+        }
+        
+        let seq = csi::CsiSequence::new(&buf[params_start..params_end], &buf[params_end..i], buf[i]);
+        Some((AnsiElement::Csi(Self::csi_from_sequence(seq), None), i + 1)) // Include the command byte
+    }
+    
+    // Map a parsed control sequence to a known command
+    fn csi_from_sequence(seq: csi::CsiSequence) -> csi::CSI {
+        let params_u16 = seq.values();
+        let params_u8: Vec<u8> = params_u16.iter()
+            .map(|&p| if p <= 255 { p as u8 } else { 0 }) // Fallback for out-of-range values
+            .collect();
+        
+        match (seq.private_marker, seq.intermediates.as_str(), seq.final_byte) {
+            (None, "", 'A') => csi::CSI::CursorUp(seq.param_or(0, 1) as u32),
+            (None, "", 'B') => csi::CSI::CursorDown(seq.param_or(0, 1) as u32),
+            (None, "", 'C') => csi::CSI::CursorForward(seq.param_or(0, 1) as u32),
+            (None, "", 'D') => csi::CSI::CursorBackward(seq.param_or(0, 1) as u32),
+            (None, "", 'E') => csi::CSI::CursorNextLine(seq.param_or(0, 1) as u32),
+            (None, "", 'F') => csi::CSI::CursorPreviousLine(seq.param_or(0, 1) as u32),
+            (None, "", 'G') => csi::CSI::CursorToColumn(seq.param_or(0, 1) as u32),
+            (None, "", 'H') | (None, "", 'f') => {
+                let row = seq.param_or(0, 1) as u32;
+                let col = seq.param_or(1, 1) as u32;
+                csi::CSI::CursorPosition(row, col)
+            }
+            (None, "", 'd') => csi::CSI::CursorToRow(seq.param_or(0, 1) as u32),
+            (None, "", 'I') => csi::CSI::CursorForwardTab(seq.param_or(0, 1) as u32),
+            (None, "", 'Z') => csi::CSI::CursorBackwardTab(seq.param_or(0, 1) as u32),
+            (None, "", 'X') => csi::CSI::EraseCharacters(seq.param_or(0, 1) as u32),
+            (None, "", '@') => csi::CSI::InsertCharacters(seq.param_or(0, 1) as u32),
+            (None, "", 'P') => csi::CSI::DeleteCharacters(seq.param_or(0, 1) as u32),
+            (None, "", 'L') => csi::CSI::InsertLines(seq.param_or(0, 1) as u32),
+            (None, "", 'M') => csi::CSI::DeleteLines(seq.param_or(0, 1) as u32),
+            (None, "", 'b') => csi::CSI::RepeatCharacter(seq.param_or(0, 1) as u32),
+            (None, "", 'g') => csi::CSI::TabClear(params_u8.first().copied().unwrap_or(0)),
+            (None, "", 'r') => {
+                let top = seq.param_or(0, 1) as u32;
+                let bottom = seq.param(1).filter(|&b| b > 0).map(|b| b as u32);
+                csi::CSI::SetScrollRegion(top, bottom)
+            }
+            (None, "", 'J') => csi::CSI::EraseInDisplay(params_u8.first().copied().unwrap_or(0)),
+            (None, "", 'K') => csi::CSI::EraseInLine(params_u8.first().copied().unwrap_or(0)),
+            (None, "", 'S') => csi::CSI::ScrollUp(seq.param_or(0, 1) as u32),
+            (None, "", 'T') => csi::CSI::ScrollDown(seq.param_or(0, 1) as u32),
+            (None, "", 's') if seq.params.is_empty() => csi::CSI::CursorSavePosition,
+            (None, "", 'u') if seq.params.is_empty() => csi::CSI::CursorRestorePosition,
+            (None, "", 'h') => csi::CSI::SetMode(params_u16),
+            (None, "", 'l') => csi::CSI::ResetMode(params_u16),
+            (Some('?'), "", 'h') => csi::CSI::SetPrivateMode(params_u16),
+            (Some('?'), "", 'l') => csi::CSI::ResetPrivateMode(params_u16),
+            (None, "", 't') => csi::CSI::WindowManipulation(params_u16),
+            (Some('?'), "", 'n') if seq.param(0) == Some(6) => csi::CSI::CursorPositionReport,
+            (None, "", 'n') if seq.param(0) == Some(6) => csi::CSI::DeviceStatusReport,
+            (None, "", 'm') => {
+                let attributes = sgr::SgrAttribute::parse(&seq.params);
+                match attributes.as_slice() {
+                    [] => csi::CSI::ResetAttributes,
+                    // A lone extended color keeps its dedicated variant
+                    [sgr::SgrAttribute::Foreground(sgr::SgrColor::Rgb(r, g, b))] => csi::CSI::SetForegroundColor(*r, *g, *b),
+                    [sgr::SgrAttribute::Background(sgr::SgrColor::Rgb(r, g, b))] => csi::CSI::SetBackgroundColor(*r, *g, *b),
+                    [sgr::SgrAttribute::Foreground(sgr::SgrColor::Indexed(n))] => csi::CSI::SetForegroundColor256(*n),
+                    [sgr::SgrAttribute::Background(sgr::SgrColor::Indexed(n))] => csi::CSI::SetBackgroundColor256(*n),
+                    _ => csi::CSI::SetGraphicsMode(attributes),
                 }
-            };
-            
-            (element, i + 1) // Include the command byte
-        } else {
-            // Incomplete CSI sequence
-            let mut seq = String::new();
-            if is_private {
-                seq.push('?');
             }
-            for p in &params_u16 {
-                seq.push_str(&p.to_string());
-                seq.push(';');
+            (None, " ", 'q') => csi::CSI::SetCursorStyle(seq.param(0).unwrap_or(0)),
+            (None, "!", 'p') if seq.params.is_empty() => csi::CSI::SoftReset,
+            (Some('>'), "", 'm') if !seq.params.is_empty() => {
+                csi::CSI::SetModifyKeys(seq.param(0).unwrap_or(0), seq.param(1))
+            }
+            (Some('>'), "", 'u') => csi::CSI::PushKeyboardFlags(seq.param(0).unwrap_or(0)),
+            (Some('<'), "", 'u') => csi::CSI::PopKeyboardFlags(seq.param_or(0, 1)),
+            (Some('='), "", 'u') => csi::CSI::SetKeyboardFlags(seq.param(0).unwrap_or(0), seq.param_or(1, 1)),
+            (Some('?'), "", 'u') if seq.params.is_empty() => csi::CSI::QueryKeyboardFlags,
+            _ => csi::CSI::Unrecognized(seq),
+        }
+    }
+}
+
+/// Iterator over the elements of a reader, see `AnsiParser::elements`
+pub struct Elements<R: Read>(SpannedElements<R>);
+
+impl<R: Read> Iterator for Elements<R> {
+    type Item = Result<AnsiElement, ParserError>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|spanned| spanned.map(|spanned| spanned.element))
+    }
+}
+
+/// Iterator over the spanned elements of a reader, see `AnsiParser::spanned_elements`
+pub struct SpannedElements<R: Read> {
+    input: R,
+    parser: AnsiParser,
+    pending: VecDeque<Spanned>,
+    done: bool,
+}
+
+impl<R: Read> Iterator for SpannedElements<R> {
+    type Item = Result<Spanned, ParserError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        
+        loop {
+            if let Some(element) = self.pending.pop_front() {
+                return Some(Ok(element));
             }
-            if !seq.is_empty() && seq.ends_with(';') {
-                seq.pop(); // Remove trailing semicolon
+            if self.done {
+                return None;
             }
             
-            (AnsiElement::Csi(csi::CSI::Unknown(seq)), i)
+            match self.input.read(&mut chunk) {
+                Ok(0) => {
+                    self.done = true;
+                    self.pending.extend(self.parser.finish());
+                }
+                Ok(n) => self.pending.extend(self.parser.feed(&chunk[..n])),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn whole(input: &[u8]) -> String {
+        format!("{:?}", AnsiParser::parse_spanned(input).unwrap())
+    }
+
+    // Feed the input in pieces of the given sizes, cycling through them
+    fn chunked(input: &[u8], sizes: &[usize]) -> String {
+        let mut parser = AnsiParser::new();
+        let mut elements = Vec::new();
+        let mut rest = input;
+        for &size in sizes.iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at(size.min(rest.len()));
+            elements.extend(parser.feed(chunk));
+            rest = tail;
+        }
+        elements.extend(parser.finish());
+        format!("{:?}", elements)
+    }
+
+    fn assert_split_anywhere(input: &[u8]) {
+        let expected = whole(input);
+        for split in 1..input.len() {
+            let mut parser = AnsiParser::new();
+            let mut elements = parser.feed(&input[..split]);
+            elements.extend(parser.feed(&input[split..]));
+            elements.extend(parser.finish());
+            assert_eq!(format!("{:?}", elements), expected, "split at {}", split);
         }
+        assert_eq!(chunked(input, &[1]), expected);
+        assert_eq!(chunked(input, &[2, 3, 1]), expected);
+    }
+
+    #[test]
+    fn string_sequences_split_anywhere() {
+        assert_split_anywhere(b"a\x1b]0;title\x07b\x1b]8;;https://x\x1b\\link");
+        assert_split_anywhere(b"\x1bPq#0;2;0;0;0~~--\x1b\\after");
+        assert_split_anywhere(b"\x1b_Gf=100;AAAA\x1b\\\x1b^private\x1b\\");
+        // tmux passthrough doubles the ESC of the wrapped sequence
+        assert_split_anywhere(b"\x1bPtmux;\x1b\x1b]0;x\x07\x1b\\z");
+        // An ESC that isn't ST ends the string and starts a new sequence
+        assert_split_anywhere(b"\x1b]0;abc\x1b[1mx\x1bPdata\x1b[0m");
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn unterminated_strings_end_at_eof() {
+        let elements = AnsiParser::parse_spanned(&b"x\x1b]0;never closed"[..]).unwrap();
+        assert_eq!(elements.len(), 2);
+        assert!(matches!(elements[1].element, AnsiElement::Osc(_, osc::Terminator::Unterminated, _)));
+        assert_eq!(elements[1].span.end, 17);
+        assert_split_anywhere(b"x\x1b]0;never closed");
+        assert_split_anywhere(b"\x1bPq~~~");
+    }
+
+    #[test]
+    fn long_strings_fed_in_chunks() {
+        let mut input = b"\x1bPq".to_vec();
+        input.extend(std::iter::repeat_n(b'~', 100_000));
+        input.extend_from_slice(b"\x1b\\");
+        assert_eq!(chunked(&input, &[8192]), whole(&input));
+        assert_eq!(chunked(&input, &[1, 7, 4096]), whole(&input));
+    }
+}
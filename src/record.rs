@@ -0,0 +1,455 @@
+use crate::ansi::{AnsiElement, Span, Spanned};
+use crate::asciicast;
+use crate::parser::{AnsiParser, ParserError};
+use crate::screen::{DEFAULT_COLUMNS, DEFAULT_ROWS};
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use termio::{Color, StyledText};
+use thiserror::Error;
+
+// First word of a recording file, followed by the version and the terminal size
+const MAGIC: &str = "#seqsee-recording";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum RecordingError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid recording header: {0}")]
+    InvalidHeader(String),
+
+    #[error("Invalid event on line {0}: {1}")]
+    InvalidEvent(usize, String),
+}
+
+/// Bytes a program wrote at once, with the time since the recording started
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub time: Duration,
+    pub data: Vec<u8>,
+}
+
+/// An element with the time its last byte was written
+#[derive(Debug, Clone)]
+pub struct Timed {
+    pub time: Duration,
+    pub chunk: usize,       // Index of the chunk that completed the element
+    pub spanned: Spanned,
+}
+
+/// Timestamped output of a terminal session.
+///
+/// Saved as text: a header line "#seqsee-recording 1 80x24", then one line per chunk
+/// with the time in seconds and the bytes, controls and invalid UTF-8 written as \xNN
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    pub columns: u16,
+    pub rows: u16,
+    pub chunks: Vec<Chunk>,
+}
+
+impl Recording {
+    pub fn new(columns: u16, rows: u16) -> Self {
+        Self { columns, rows, chunks: Vec::new() }
+    }
+
+    /// Output without timing, all written at the start
+    pub fn untimed(columns: u16, rows: u16, data: Vec<u8>) -> Self {
+        Self { columns, rows, chunks: vec![Chunk { time: Duration::ZERO, data }] }
+    }
+
+    /// Whether the start of a file looks like a recording
+    pub fn is_recording(start: &[u8]) -> bool {
+        start.starts_with(MAGIC.as_bytes())
+    }
+
+    /// All recorded bytes, without timing
+    pub fn bytes(&self) -> Vec<u8> {
+        self.chunks.iter().flat_map(|chunk| chunk.data.iter().copied()).collect()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.chunks.last().map_or(Duration::ZERO, |chunk| chunk.time)
+    }
+
+    /// Copy keeping only the elements `keep` accepts, each in a chunk at the time of the one that completed it
+    pub fn retain(&self, keep: impl Fn(&AnsiElement) -> bool) -> Result<Self, ParserError> {
+        let mut recording = Self::new(self.columns, self.rows);
+        let mut last = None;
+        for timed in self.elements()? {
+            if !keep(&timed.spanned.element) {
+                continue;
+            }
+            match recording.chunks.last_mut() {
+                Some(chunk) if last == Some(timed.chunk) => chunk.data.extend_from_slice(&timed.spanned.raw),
+                _ => recording.chunks.push(Chunk { time: timed.time, data: timed.spanned.raw }),
+            }
+            last = Some(timed.chunk);
+        }
+        Ok(recording)
+    }
+
+    /// Pause before each chunk when played back `speed` times as fast, each capped at `max_wait`
+    pub fn pauses(&self, speed: f64, max_wait: Option<Duration>) -> Vec<Duration> {
+        let mut previous = Duration::ZERO;
+        self.chunks.iter().map(|chunk| {
+            // A very slow speed can stretch a pause past what a Duration holds
+            let pause = Duration::try_from_secs_f64(chunk.time.saturating_sub(previous).as_secs_f64() / speed)
+                .unwrap_or(Duration::MAX);
+            previous = chunk.time;
+            max_wait.map_or(pause, |max| pause.min(max))
+        }).collect()
+    }
+
+    pub fn read(input: impl BufRead) -> Result<Self, RecordingError> {
+        let mut lines = input.lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let invalid = || RecordingError::InvalidHeader(header.clone());
+
+        let mut words = header.split_whitespace();
+        if words.next() != Some(MAGIC) {
+            return Err(invalid());
+        }
+        if words.next().and_then(|v| v.parse::<u32>().ok()) != Some(VERSION) {
+            return Err(invalid());
+        }
+        let (columns, rows) = words.next()
+            .and_then(|size| size.split_once('x'))
+            .and_then(|(columns, rows)| Some((columns.parse().ok()?, rows.parse().ok()?)))
+            .ok_or_else(invalid)?;
+
+        let mut recording = Self::new(columns, rows);
+        for (number, line) in lines.enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |reason: &str| RecordingError::InvalidEvent(number + 2, reason.to_string());
+            let (time, data) = line.split_once(' ').ok_or_else(|| invalid("missing data"))?;
+            let time = time.parse::<f64>().ok()
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                .ok_or_else(|| invalid("invalid time"))?;
+            let data = unescape(data).ok_or_else(|| invalid("invalid escape"))?;
+            recording.chunks.push(Chunk { time, data });
+        }
+
+        Ok(recording)
+    }
+
+    pub fn write(&self, output: &mut impl Write) -> io::Result<()> {
+        writeln!(output, "{} {} {}x{}", MAGIC, VERSION, self.columns, self.rows)?;
+        for chunk in &self.chunks {
+            writeln!(output, "{:.6} {}", chunk.time.as_secs_f64(), escape(&chunk.data))?;
+        }
+        Ok(())
+    }
+
+    /// Save to a file, as asciicast v2 if its name ends in .cast
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        if path.extension().is_some_and(|ext| ext == "cast") {
+            asciicast::write(self, &mut file)
+        } else {
+            self.write(&mut file)
+        }
+    }
+
+    /// Parse the recorded bytes, timing each element by the chunk that completed it.
+    /// Text written in several chunks is split at the chunk boundaries.
+    pub fn elements(&self) -> Result<Vec<Timed>, ParserError> {
+        // Offset just past each chunk
+        let ends: Vec<usize> = self.chunks.iter()
+            .scan(0, |offset, chunk| {
+                *offset += chunk.data.len();
+                Some(*offset)
+            })
+            .collect();
+        let chunk_of = |offset: usize| ends.partition_point(|&end| end <= offset).min(ends.len().saturating_sub(1));
+
+        let mut timed = Vec::new();
+        for spanned in AnsiParser::parse_spanned(&self.bytes()[..])? {
+            let pieces = match &spanned.element {
+                AnsiElement::Text(text) => split_text(text, spanned.span, &ends),
+                _ => vec![spanned],
+            };
+            for spanned in pieces {
+                let chunk = chunk_of(spanned.span.end.saturating_sub(1));
+                timed.push(Timed { time: self.chunks[chunk].time, chunk, spanned });
+            }
+        }
+
+        Ok(timed)
+    }
+
+    /// Write the chunks to `output` with their original timing, played back `speed` times as fast
+    pub fn replay(&self, output: &mut impl Write, speed: f64, max_wait: Option<Duration>) -> io::Result<()> {
+        for (chunk, pause) in self.chunks.iter().zip(self.pauses(speed, max_wait)) {
+            thread::sleep(pause);
+            output.write_all(&chunk.data)?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write the chunks, or the elements with `by_element`, one per key press on the terminal. q stops.
+    pub fn replay_stepwise(&self, output: &mut impl Write, by_element: bool) -> io::Result<()> {
+        let steps: Vec<Vec<u8>> = if by_element {
+            self.elements().map_err(io::Error::other)?
+                .into_iter()
+                .map(|timed| timed.spanned.raw)
+                .collect()
+        } else {
+            self.chunks.iter().map(|chunk| chunk.data.clone()).collect()
+        };
+
+        let mut keys = Keys::open()?;
+        for step in steps {
+            if keys.next()? == b'q' {
+                break;
+            }
+            output.write_all(&step)?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write the elements `keep` accepts under a header for each write, with the time since the previous one
+    pub fn write_timeline(
+        &self,
+        output: &mut impl Write,
+        keep: impl Fn(&AnsiElement) -> bool,
+        colorize: bool,
+    ) -> Result<(), ParserError> {
+        let elements = self.elements()?;
+        let mut current = None;
+        for timed in elements.iter().filter(|timed| keep(&timed.spanned.element)) {
+            if current != Some(timed.chunk) {
+                current = Some(timed.chunk);
+                let previous = timed.chunk.checked_sub(1).map_or(Duration::ZERO, |i| self.chunks[i].time);
+                let header = format!("{:>10.6}s  +{:>9.3}ms  write {}, {} bytes",
+                    timed.time.as_secs_f64(),
+                    timed.time.saturating_sub(previous).as_secs_f64() * 1000.0,
+                    timed.chunk + 1,
+                    self.chunks[timed.chunk].data.len());
+                let header = if colorize { header.color(Color::Cyan).to_string() } else { header };
+                writeln!(output, "{}", header)?;
+            }
+            let element = &timed.spanned.element;
+            writeln!(output, "    {:<24} {}", element.escape_repr(), element.description())?;
+        }
+        writeln!(output, "\n{} writes, {} elements over {:.3}s",
+            self.chunks.len(), elements.len(), self.duration().as_secs_f64())?;
+
+        Ok(())
+    }
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self::new(DEFAULT_COLUMNS as u16, DEFAULT_ROWS as u16)
+    }
+}
+
+/// Builds a recording from output as it arrives
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    start: Instant,
+    recording: Recording,
+}
+
+impl Recorder {
+    /// Start recording now, for a terminal of the given size
+    pub fn new(columns: u16, rows: u16) -> Self {
+        Self { start: Instant::now(), recording: Recording::new(columns, rows) }
+    }
+
+    pub fn record(&mut self, data: &[u8]) {
+        let time = self.start.elapsed();
+        self.recording.chunks.push(Chunk { time, data: data.to_vec() });
+    }
+
+    pub fn finish(self) -> Recording {
+        self.recording
+    }
+}
+
+// Single key presses from the terminal, read without echo so they don't disturb the replay
+#[cfg(unix)]
+struct Keys {
+    tty: File,
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl Keys {
+    fn open() -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let tty = File::open("/dev/tty")?;
+        // SAFETY: termios is plain data, filled in by tcgetattr before it is used
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(tty.as_raw_fd(), &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        if unsafe { libc::tcsetattr(tty.as_raw_fd(), libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { tty, saved })
+    }
+
+    fn next(&mut self) -> io::Result<u8> {
+        let mut key = [0u8];
+        self.tty.read_exact(&mut key)?;
+        Ok(key[0])
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Keys {
+    fn drop(&mut self) {
+        use std::os::fd::AsRawFd;
+
+        unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSANOW, &self.saved) };
+    }
+}
+
+// Elsewhere a key press is a line on stdin
+#[cfg(not(unix))]
+struct Keys;
+
+#[cfg(not(unix))]
+impl Keys {
+    fn open() -> io::Result<Self> {
+        Ok(Self)
+    }
+
+    fn next(&mut self) -> io::Result<u8> {
+        let mut line = String::new();
+        io::stdin().read_line(&mut line)?;
+        Ok(line.bytes().next().unwrap_or(b'\n'))
+    }
+}
+
+// Split a text element at the chunk boundaries inside it. A character cut in two
+// goes with the chunk that completes it.
+fn split_text(text: &str, span: Span, ends: &[usize]) -> Vec<Spanned> {
+    let mut cuts: Vec<usize> = ends.iter()
+        .filter(|&&end| end > span.start && end < span.end)
+        .map(|&end| {
+            let mut cut = end - span.start;
+            while !text.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            cut
+        })
+        .collect();
+    cuts.dedup();
+    cuts.push(text.len());
+
+    let mut pieces = Vec::new();
+    let mut from = 0;
+    for to in cuts {
+        if to == from {
+            continue;
+        }
+        let piece = &text[from..to];
+        let piece_span = Span {
+            start: span.start + from,
+            end: span.start + to,
+            column: span.column + text[..from].chars().count(),
+            ..span
+        };
+        pieces.push(Spanned::new(AnsiElement::Text(piece.to_string()), piece_span, piece.as_bytes().to_vec()));
+        from = to;
+    }
+    pieces
+}
+
+// Printable UTF-8 stays as it is; backslashes, controls and invalid bytes are escaped
+fn escape(data: &[u8]) -> String {
+    let mut escaped = String::new();
+    for chunk in data.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                '\t' => escaped.push_str("\\t"),
+                c if c.is_ascii_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(backslash) = rest.find('\\') {
+        data.extend_from_slice(&rest.as_bytes()[..backslash]);
+        let escape = rest.get(backslash + 1..)?;
+        let (byte, len) = match escape.as_bytes().first()? {
+            b'\\' => (b'\\', 1),
+            b'n' => (b'\n', 1),
+            b'r' => (b'\r', 1),
+            b't' => (b'\t', 1),
+            b'x' => (u8::from_str_radix(escape.get(1..3)?, 16).ok()?, 3),
+            _ => return None,
+        };
+        data.push(byte);
+        rest = &escape[len..];
+    }
+    data.extend_from_slice(rest.as_bytes());
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(chunks: &[(u64, &[u8])]) -> Recording {
+        let mut recording = Recording::new(80, 24);
+        for &(millis, data) in chunks {
+            recording.chunks.push(Chunk { time: Duration::from_millis(millis), data: data.to_vec() });
+        }
+        recording
+    }
+
+    #[test]
+    fn saved_recordings_read_back() {
+        let recording = recording(&[(0, b"a\x1b[1m\\"), (1500, b"\xff\r\n")]);
+        let mut saved = Vec::new();
+        recording.write(&mut saved).unwrap();
+        assert!(Recording::is_recording(&saved));
+        assert_eq!(Recording::read(&saved[..]).unwrap(), recording);
+    }
+
+    #[test]
+    fn replay_writes_every_chunk() {
+        let recording = recording(&[(0, b"ab"), (3000, b"\x1b[1mc")]);
+        let mut output = Vec::new();
+        recording.replay(&mut output, 1.0, Some(Duration::ZERO)).unwrap();
+        assert_eq!(output, recording.bytes());
+    }
+
+    #[test]
+    fn timeline_groups_elements_by_write() {
+        let recording = recording(&[(0, b"ab\x1b["), (250, b"1mc")]);
+        let mut output = Vec::new();
+        recording.write_timeline(&mut output, |element| !matches!(element, AnsiElement::Text(_)), false).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "  \
+              0.250000s  +  250.000ms  write 2, 3 bytes\n    \
+            \\x1b[1m                  Set graphics mode: Bold\n\
+            \n2 writes, 3 elements over 0.250s\n");
+    }
+}
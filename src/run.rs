@@ -0,0 +1,130 @@
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use crate::record::{Recorder, Recording};
+use crate::screen::{DEFAULT_COLUMNS, DEFAULT_ROWS};
+use std::io::{self, Read, Write};
+use thiserror::Error;
+
+pub const DEFAULT_TERM: &str = "xterm-256color";
+
+// Linux reports EIO on the master side once the slave side is closed
+const EIO: i32 = 5;
+
+#[derive(Debug, Error)]
+pub enum RunError {
+    #[error("Failed to open a pseudo-terminal: {0}")]
+    Pty(String),
+
+    #[error("Failed to start '{0}': {1}")]
+    Spawn(String, String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// How a command run under the pseudo-terminal ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exit {
+    pub code: u32,
+    pub success: bool,
+}
+
+/// Runs a command under a pseudo-terminal, so it writes colors and cursor
+/// movement as it would to a real terminal instead of a pipe
+#[derive(Debug, Clone)]
+pub struct Runner {
+    pub columns: u16,
+    pub rows: u16,
+    pub term: String,       // Value of TERM in the command's environment
+}
+
+impl Runner {
+    pub fn new(columns: u16, rows: u16, term: &str) -> Self {
+        Self { columns, rows, term: term.to_string() }
+    }
+
+    /// Run `command` in the current directory, handing each chunk of its output to
+    /// `on_output` as it arrives. The command gets no input.
+    pub fn run(
+        &self,
+        command: &[String],
+        mut on_output: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<Exit, RunError> {
+        let program = command.first().ok_or_else(|| RunError::Spawn(String::new(), "empty command".to_string()))?;
+
+        let pty = native_pty_system()
+            .openpty(PtySize { rows: self.rows, cols: self.columns, pixel_width: 0, pixel_height: 0 })
+            .map_err(|err| RunError::Pty(err.to_string()))?;
+
+        let mut builder = CommandBuilder::new(program);
+        builder.args(&command[1..]);
+        builder.env("TERM", &self.term);
+        // Without an explicit directory the command would start in $HOME
+        builder.cwd(std::env::current_dir()?);
+
+        let mut child = pty.slave.spawn_command(builder)
+            .map_err(|err| RunError::Spawn(program.clone(), err.to_string()))?;
+        // Reading stops at end of file only once no process holds the slave side open
+        drop(pty.slave);
+
+        let mut reader = pty.master.try_clone_reader().map_err(|err| RunError::Pty(err.to_string()))?;
+        let mut buffer = [0u8; 8192];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => on_output(&buffer[..n])?,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) if err.raw_os_error() == Some(EIO) => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let status = child.wait()?;
+        Ok(Exit { code: status.exit_code(), success: status.success() })
+    }
+
+    /// Run `command` and record its output with its timing, also copying it to `tee` as it arrives
+    pub fn record(&self, command: &[String], mut tee: Option<&mut dyn Write>) -> Result<(Exit, Recording), RunError> {
+        let mut recorder = Recorder::new(self.columns, self.rows);
+        let exit = self.run(command, |chunk| {
+            recorder.record(chunk);
+            if let Some(tee) = tee.as_mut() {
+                tee.write_all(chunk)?;
+                tee.flush()?;
+            }
+            Ok(())
+        })?;
+        Ok((exit, recorder.finish()))
+    }
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Self::new(DEFAULT_COLUMNS as u16, DEFAULT_ROWS as u16, DEFAULT_TERM)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn record_copies_the_output_to_tee() {
+        let mut tee = Vec::new();
+        let (exit, recording) = Runner::default().record(&command(&["printf", "a\\033[1mb"]), Some(&mut tee)).unwrap();
+        assert!(exit.success);
+        assert_eq!(recording.bytes(), b"a\x1b[1mb");
+        assert_eq!(tee, recording.bytes());
+        assert_eq!((recording.columns, recording.rows), (DEFAULT_COLUMNS as u16, DEFAULT_ROWS as u16));
+    }
+
+    #[test]
+    fn record_reports_the_exit_status() {
+        let (exit, recording) = Runner::default().record(&command(&["sh", "-c", "exit 3"]), None).unwrap();
+        assert_eq!(exit, Exit { code: 3, success: false });
+        assert!(recording.chunks.is_empty());
+    }
+}
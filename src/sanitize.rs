@@ -0,0 +1,432 @@
+use crate::ansi::{AnsiElement, Span, Spanned, csi::CSI, ctrl::ControlCharacter, osc::OSC};
+use crate::ansi::sgr::SgrAttribute;
+use crate::parser::ParserError;
+use std::io::{self, Write};
+
+/// What an element does, as far as deciding whether it is safe to pass on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    Text,           // Printable text
+    Whitespace,     // LF, CR, tab, backspace
+    Control,        // Other C0 controls such as BEL or a lone ESC
+    Sgr,            // Colors and text attributes
+    Cursor,         // Cursor movement, save/restore
+    Editing,        // Erasing, inserting and deleting cells, tab stops
+    Scroll,         // Scrolling and scroll regions
+    Modes,          // Terminal modes, keypad, charsets, cursor style, resets, keyboard protocols
+    Title,          // Window and icon titles (OSC 0/1/2)
+    Clipboard,      // Clipboard access (OSC 52)
+    Hyperlink,      // OSC 8 links
+    Query,          // Requests that make the terminal answer on its input (DSR, DA, DECRQSS, title reports, ...)
+    Window,         // Window manipulation (CSI t)
+    Font,           // Font changes (OSC 50, SGR 10-20)
+    Palette,        // Palette and dynamic color changes
+    Other,          // Everything else: unknown sequences, graphics, passthrough, shell integration
+}
+
+impl Category {
+    pub const ALL: [Category; 16] = [
+        Category::Text, Category::Whitespace, Category::Control, Category::Sgr,
+        Category::Cursor, Category::Editing, Category::Scroll, Category::Modes,
+        Category::Title, Category::Clipboard, Category::Hyperlink, Category::Query,
+        Category::Window, Category::Font, Category::Palette, Category::Other,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Text => "text",
+            Category::Whitespace => "whitespace",
+            Category::Control => "control",
+            Category::Sgr => "sgr",
+            Category::Cursor => "cursor",
+            Category::Editing => "editing",
+            Category::Scroll => "scroll",
+            Category::Modes => "modes",
+            Category::Title => "title",
+            Category::Clipboard => "clipboard",
+            Category::Hyperlink => "hyperlink",
+            Category::Query => "query",
+            Category::Window => "window",
+            Category::Font => "font",
+            Category::Palette => "palette",
+            Category::Other => "other",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|category| category.name() == name)
+    }
+
+    /// Classify an element
+    pub fn of(element: &AnsiElement) -> Self {
+        match element {
+            // Invalid UTF-8 is passed on as U+FFFD, so 8-bit C1 controls can't act
+            AnsiElement::Text(_) | AnsiElement::InvalidUtf8(_) => Category::Text,
+            AnsiElement::Ctrl(ctrl, _) => match ctrl {
+                ControlCharacter::LineFeed |
+                ControlCharacter::CarriageReturn |
+                ControlCharacter::Tab |
+                ControlCharacter::Backspace => Category::Whitespace,
+                _ => Category::Control,
+            },
+            AnsiElement::Csi(csi, _) => Self::of_csi(csi),
+            AnsiElement::Osc(osc, _, _) => Self::of_osc(osc),
+            AnsiElement::ControlString(string, _) => {
+                match (string.intermediates.as_str(), string.final_byte) {
+                    // DECRQSS and XTGETTCAP are answered with the setting or capability
+                    ("$", Some('q')) | ("+", Some('q')) => Category::Query,
+                    _ => Category::Other,
+                }
+            },
+        }
+    }
+
+    fn of_csi(csi: &CSI) -> Self {
+        match csi {
+            CSI::SetGraphicsMode(attributes) => {
+                // SGR that does nothing but switch fonts
+                if !attributes.is_empty() && attributes.iter().all(|a| matches!(a, SgrAttribute::Font(_))) {
+                    Category::Font
+                } else {
+                    Category::Sgr
+                }
+            },
+            CSI::SetForegroundColor(..) |
+            CSI::SetBackgroundColor(..) |
+            CSI::SetForegroundColor256(_) |
+            CSI::SetBackgroundColor256(_) |
+            CSI::ResetAttributes => Category::Sgr,
+            CSI::CursorUp(_) |
+            CSI::CursorDown(_) |
+            CSI::CursorForward(_) |
+            CSI::CursorBackward(_) |
+            CSI::CursorPosition(..) |
+            CSI::CursorSavePosition |
+            CSI::CursorRestorePosition |
+            CSI::CursorToColumn(_) |
+            CSI::CursorNextLine(_) |
+            CSI::CursorPreviousLine(_) |
+            CSI::CursorToRow(_) |
+            CSI::CursorForwardTab(_) |
+            CSI::CursorBackwardTab(_) => Category::Cursor,
+            CSI::EraseInDisplay(_) |
+            CSI::EraseInLine(_) |
+            CSI::EraseCharacters(_) |
+            CSI::InsertCharacters(_) |
+            CSI::DeleteCharacters(_) |
+            CSI::InsertLines(_) |
+            CSI::DeleteLines(_) |
+            CSI::RepeatCharacter(_) |
+            CSI::TabSet |
+            CSI::TabClear(_) => Category::Editing,
+            CSI::ScrollUp(_) |
+            CSI::ScrollDown(_) |
+            CSI::SetScrollRegion(..) |
+            CSI::Index |
+            CSI::NextLine |
+            CSI::ReverseIndex => Category::Scroll,
+            CSI::SetMode(_) |
+            CSI::ResetMode(_) |
+            CSI::SetPrivateMode(_) |
+            CSI::ResetPrivateMode(_) |
+            CSI::ApplicationKeypadMode |
+            CSI::NumericKeypadMode |
+            CSI::SetG0SpecialChars |
+            CSI::SetG0NormalChars |
+            CSI::SetCursorStyle(_) |
+            CSI::SoftReset |
+            CSI::FullReset |
+            CSI::SetModifyKeys(..) |
+            CSI::PushKeyboardFlags(_) |
+            CSI::PopKeyboardFlags(_) |
+            CSI::SetKeyboardFlags(..) => Category::Modes,
+            CSI::DeviceStatusReport | CSI::CursorPositionReport | CSI::QueryKeyboardFlags => Category::Query,
+            CSI::WindowManipulation(params) => match params.first() {
+                // Reports of window state, position, size and title
+                Some(11 | 13 | 14 | 15 | 16 | 18 | 19 | 20 | 21) => Category::Query,
+                _ => Category::Window,
+            },
+            CSI::Unrecognized(seq) => match (seq.private_marker, seq.intermediates.as_str(), seq.final_byte) {
+                (_, "", 'c') => Category::Query,           // Device attributes (DA1/DA2/DA3)
+                (_, "$", 'p') => Category::Query,          // Mode request (DECRQM)
+                (Some('>'), "", 'q') => Category::Query,   // Terminal version (XTVERSION)
+                (None, "", 'x') => Category::Query,        // Terminal parameters (DECREQTPARM)
+                _ => Category::Other,
+            },
+            CSI::Unknown(_) => Category::Other,
+        }
+    }
+
+    fn of_osc(osc: &OSC) -> Self {
+        match osc {
+            OSC::SetIconNameAndTitle(_) | OSC::SetIconName(_) | OSC::SetWindowTitle(_) => Category::Title,
+            OSC::Clipboard(..) => Category::Clipboard,
+            OSC::Hyperlink(..) => Category::Hyperlink,
+            OSC::SetPaletteColor(colors) if colors.iter().any(|(_, spec)| spec == "?") => Category::Query,
+            OSC::DynamicColor(_, specs) if specs.iter().any(|spec| spec == "?") => Category::Query,
+            OSC::SetPaletteColor(_) | OSC::ResetPaletteColor(_) | OSC::DynamicColor(..) => Category::Palette,
+            OSC::Unknown(payload) if payload == "50" || payload.starts_with("50;") => Category::Font,
+            OSC::SetWorkingDirectory(_) | OSC::ShellIntegration(..) | OSC::Unknown(_) => Category::Other,
+        }
+    }
+}
+
+/// Which elements a sanitizer lets through
+#[derive(Debug, Clone)]
+pub struct Policy {
+    pub allowed: Vec<Category>,
+    pub link_schemes: Vec<String>,  // URI schemes allowed in hyperlinks, lowercase
+    pub escape_removed: bool,       // Replace removed sequences with their visible escaped form instead of dropping them
+}
+
+impl Policy {
+    pub fn new(allowed: Vec<Category>) -> Self {
+        Self {
+            allowed,
+            link_schemes: vec!["http".to_string(), "https".to_string()],
+            escape_removed: false,
+        }
+    }
+
+    pub fn allows(&self, category: Category) -> bool {
+        self.allowed.contains(&category)
+    }
+}
+
+impl Default for Policy {
+    /// Text, styling and simple cursor motion, plus web links
+    fn default() -> Self {
+        Self::new(vec![
+            Category::Text,
+            Category::Whitespace,
+            Category::Sgr,
+            Category::Cursor,
+            Category::Editing,
+            Category::Hyperlink,
+        ])
+    }
+}
+
+/// An element taken out of the stream
+#[derive(Debug, Clone)]
+pub struct Removal {
+    pub span: Span,
+    pub category: Category,
+    pub escape: String,
+    pub reason: String,
+}
+
+/// Result of sanitizing a whole stream
+#[derive(Debug, Clone, Default)]
+pub struct Sanitized {
+    pub output: Vec<u8>,
+    pub removed: Vec<Removal>,
+}
+
+/// Filters parsed elements against a policy
+#[derive(Debug, Clone, Default)]
+pub struct Sanitizer {
+    pub policy: Policy,
+}
+
+impl Sanitizer {
+    pub fn new(policy: Policy) -> Self {
+        Self { policy }
+    }
+
+    pub fn sanitize(&self, elements: &[Spanned]) -> Sanitized {
+        let mut sanitized = Sanitized::default();
+        for spanned in elements {
+            if let Some(removal) = self.filter(spanned, &mut sanitized.output) {
+                sanitized.removed.push(removal);
+            }
+        }
+        sanitized
+    }
+
+    /// Sanitize a whole stream as it is parsed, writing the result to `output` and what was removed to `report`
+    pub fn sanitize_stream(
+        &self,
+        elements: impl Iterator<Item = Result<Spanned, ParserError>>,
+        output: &mut impl Write,
+        report: &mut impl Write,
+    ) -> Result<(), ParserError> {
+        let mut removed = Vec::new();
+        let mut buffer = Vec::new();
+        for element in elements {
+            buffer.clear();
+            if let Some(removal) = self.filter(&element?, &mut buffer) {
+                removed.push(removal);
+            }
+            output.write_all(&buffer)?;
+        }
+        output.flush()?;
+
+        write_report(&removed, report)?;
+        Ok(())
+    }
+
+    /// Write what should replace an element to the output, reporting it if it was removed
+    pub fn filter(&self, spanned: &Spanned, output: &mut Vec<u8>) -> Option<Removal> {
+        let element = &spanned.element;
+        let category = Category::of(element);
+
+        let reason = if !self.policy.allows(category) {
+            format!("{} not allowed", category.name())
+        } else {
+            match element {
+                AnsiElement::Osc(OSC::Hyperlink(_, uri), _, _) if !self.link_allowed(uri) => {
+                    format!("link scheme not allowed: {}", uri)
+                },
+                AnsiElement::Csi(CSI::SetGraphicsMode(attributes), _) if !self.policy.allows(Category::Font) => {
+                    // Keep the styling but drop any font switches mixed into it
+                    let kept: Vec<&SgrAttribute> = attributes.iter()
+                        .filter(|a| !matches!(a, SgrAttribute::Font(_)))
+                        .collect();
+                    if kept.len() == attributes.len() {
+                        output.extend_from_slice(&spanned.raw);
+                        return None;
+                    }
+                    let params: Vec<String> = kept.iter().map(|a| a.params()).collect();
+                    output.extend_from_slice(format!("\x1b[{}m", params.join(";")).as_bytes());
+                    return Some(self.removal(spanned, Category::Font, "font change removed from SGR".to_string()));
+                },
+                AnsiElement::InvalidUtf8(bytes) => {
+                    output.extend_from_slice(String::from_utf8_lossy(bytes).as_bytes());
+                    return None;
+                },
+                _ => {
+                    output.extend_from_slice(&spanned.raw);
+                    return None;
+                },
+            }
+        };
+
+        if self.policy.escape_removed {
+            output.extend_from_slice(printable_escape(element).as_bytes());
+        }
+        Some(self.removal(spanned, category, reason))
+    }
+
+    // Closing a link (empty URI) is always fine
+    fn link_allowed(&self, uri: &str) -> bool {
+        if uri.is_empty() {
+            return true;
+        }
+        match uri.split_once(':') {
+            Some((scheme, _)) => self.policy.link_schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)),
+            None => false,
+        }
+    }
+
+    fn removal(&self, spanned: &Spanned, category: Category, reason: String) -> Removal {
+        Removal {
+            span: spanned.span,
+            category,
+            escape: printable_escape(&spanned.element),
+            reason,
+        }
+    }
+}
+
+/// Write the list of removals shown by --sanitize, or nothing if there are none
+pub fn write_report(removed: &[Removal], output: &mut impl Write) -> io::Result<()> {
+    if removed.is_empty() {
+        return Ok(());
+    }
+    writeln!(output, "Removed {} sequence(s):", removed.len())?;
+    for removal in removed {
+        writeln!(output, "  {}..{}  {:<10} {}  ({})",
+            removal.span.start, removal.span.end, removal.category.name(), removal.escape, removal.reason)?;
+    }
+    Ok(())
+}
+
+// Escaped form of an element with no control characters left in it, as string
+// payloads are shown as written
+fn printable_escape(element: &AnsiElement) -> String {
+    let mut escaped = String::new();
+    for c in element.escape_repr().chars() {
+        if c.is_control() {
+            let mut buffer = [0; 4];
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                escaped.push_str(&format!("\\x{:02x}", byte));
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::AnsiParser;
+
+    fn sanitize(input: &[u8], policy: Policy) -> Sanitized {
+        Sanitizer::new(policy).sanitize(&AnsiParser::parse_spanned(input).unwrap())
+    }
+
+    #[test]
+    fn allowed_elements_pass_unchanged() {
+        let input = b"\x1b[1;31mred\x1b[0m\r\n\x1b[2;3H\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        let sanitized = sanitize(input, Policy::default());
+        assert_eq!(sanitized.output, input);
+        assert!(sanitized.removed.is_empty());
+    }
+
+    #[test]
+    fn disallowed_elements_are_removed() {
+        let sanitized = sanitize(b"a\x1b]0;title\x07b\x1b[6nc\x1b]52;c;?\x07", Policy::default());
+        assert_eq!(sanitized.output, b"abc");
+        let categories: Vec<Category> = sanitized.removed.iter().map(|r| r.category).collect();
+        assert_eq!(categories, vec![Category::Title, Category::Query, Category::Clipboard]);
+    }
+
+    #[test]
+    fn link_schemes_are_checked() {
+        let sanitized = sanitize(b"\x1b]8;;file:///etc/passwd\x07x\x1b]8;;\x07", Policy::default());
+        assert_eq!(sanitized.output, b"x\x1b]8;;\x07");
+        assert_eq!(sanitized.removed.len(), 1);
+    }
+
+    #[test]
+    fn font_changes_are_dropped_from_sgr() {
+        let sanitized = sanitize(b"\x1b[1;11mx", Policy::default());
+        assert_eq!(sanitized.output, b"\x1b[1mx");
+        assert_eq!(sanitized.removed[0].category, Category::Font);
+    }
+
+    #[test]
+    fn eight_bit_controls_are_replaced() {
+        // 8-bit CSI, OSC, DCS, PM and APC introducers
+        for byte in [0x9b, 0x9d, 0x90, 0x9e, 0x9f] {
+            let sanitized = sanitize(&[b'a', byte, b'1', b'm'], Policy::default());
+            assert_eq!(sanitized.output, "a\u{FFFD}1m".as_bytes());
+        }
+    }
+
+    #[test]
+    fn escaped_removals_hold_no_controls() {
+        let policy = Policy { escape_removed: true, ..Policy::default() };
+        let sanitized = sanitize(b"\x1b]0;a\x01\xc2\x85b\x07", policy);
+        assert_eq!(sanitized.output, b"\\x1b]0;a\\x01\\xc2\\x85b\\a");
+        assert!(!sanitized.removed[0].escape.chars().any(char::is_control));
+    }
+
+    #[test]
+    fn streams_are_sanitized_with_a_report() {
+        let (mut output, mut report) = (Vec::new(), Vec::new());
+        Sanitizer::default()
+            .sanitize_stream(AnsiParser::spanned_elements(&b"a\x1b]0;t\x07b"[..]), &mut output, &mut report)
+            .unwrap();
+        assert_eq!(output, b"ab");
+        assert_eq!(String::from_utf8(report).unwrap(),
+            "Removed 1 sequence(s):\n  1..7  title      \\x1b]0;t\\a  (title not allowed)\n");
+
+        let (mut output, mut report) = (Vec::new(), Vec::new());
+        Sanitizer::default().sanitize_stream(AnsiParser::spanned_elements(&b"ab"[..]), &mut output, &mut report).unwrap();
+        assert!(report.is_empty());
+    }
+}
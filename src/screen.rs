@@ -0,0 +1,793 @@
+use unicode_width::UnicodeWidthChar;
+use crate::ansi::{AnsiElement, csi::CSI, ctrl::ControlCharacter, osc::OSC};
+use crate::ansi::sgr::SgrState;
+
+pub const DEFAULT_COLUMNS: usize = 80;
+pub const DEFAULT_ROWS: usize = 24;
+
+const TAB_WIDTH: usize = 8;
+
+/// A single character cell of the screen grid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    pub text: String,       // Base character plus any combining marks; empty for the right half of a wide character
+    pub style: SgrState,
+}
+
+impl Cell {
+    fn blank(style: SgrState) -> Self {
+        Self { text: " ".to_string(), style }
+    }
+
+    /// Whether this cell is covered by the wide character to its left
+    pub fn is_continuation(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    fn is_wide(&self) -> bool {
+        self.text.chars().next().and_then(|c| c.width()) == Some(2)
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self::blank(SgrState::default())
+    }
+}
+
+/// Cursor state saved by DECSC and restored by DECRC
+#[derive(Debug, Clone, Copy, Default)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    style: SgrState,
+    origin_mode: bool,
+    special_chars: bool,
+}
+
+/// Virtual terminal that replays elements into a grid of cells
+#[derive(Debug, Clone)]
+pub struct Screen {
+    columns: usize,
+    rows: usize,
+    primary: Vec<Vec<Cell>>,
+    alternate: Vec<Vec<Cell>>,
+    alternate_active: bool,
+    row: usize,
+    col: usize,
+    wrap_pending: bool,         // Cursor sits past the last column until the next character
+    style: SgrState,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    tab_stops: Vec<bool>,
+    saved_cursor: SavedCursor,
+    last_char: Option<char>,    // For REP
+    title: String,
+
+    // Modes
+    auto_wrap: bool,            // DECAWM
+    origin_mode: bool,          // DECOM
+    insert_mode: bool,          // IRM
+    newline_mode: bool,         // LNM
+    cursor_visible: bool,       // DECTCEM
+    special_chars: bool,        // G0 is DEC Special Graphics
+}
+
+impl Screen {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+
+        Self {
+            columns,
+            rows,
+            primary: blank_grid(columns, rows),
+            alternate: blank_grid(columns, rows),
+            alternate_active: false,
+            row: 0,
+            col: 0,
+            wrap_pending: false,
+            style: SgrState::default(),
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            tab_stops: default_tab_stops(columns),
+            saved_cursor: SavedCursor::default(),
+            last_char: None,
+            title: String::new(),
+            auto_wrap: true,
+            origin_mode: false,
+            insert_mode: false,
+            newline_mode: false,
+            cursor_visible: true,
+            special_chars: false,
+        }
+    }
+
+    /// Make line feeds also return the carriage, as LNM does. Useful for output
+    /// captured without a terminal, where lines end in a bare LF.
+    pub fn set_newline_mode(&mut self, enabled: bool) {
+        self.newline_mode = enabled;
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Cursor position as zero-based (row, column)
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Cursor sits past the last column, wrapping before the next character
+    pub fn wrap_pending(&self) -> bool {
+        self.wrap_pending
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    pub fn is_alternate_screen(&self) -> bool {
+        self.alternate_active
+    }
+
+    /// Current graphics state applied to newly written characters
+    pub fn style(&self) -> &SgrState {
+        &self.style
+    }
+
+    /// Window title set by the last OSC 0/2
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn cell(&self, row: usize, col: usize) -> Option<&Cell> {
+        self.grid().get(row).and_then(|line| line.get(col))
+    }
+
+    /// Rows of the visible grid
+    pub fn lines(&self) -> &[Vec<Cell>] {
+        self.grid()
+    }
+
+    /// Plain text of each row with trailing blanks removed
+    pub fn text(&self) -> Vec<String> {
+        self.grid().iter().map(|line| {
+            let text: String = line.iter().map(|cell| cell.text.as_str()).collect();
+            text.trim_end().to_string()
+        }).collect()
+    }
+
+    /// Render the visible grid, re-encoding cell styles as SGR when colorized.
+    /// Trailing blank rows are omitted.
+    pub fn render(&self, colorize: bool) -> String {
+        let grid = self.grid();
+        let used_rows = grid.iter()
+            .rposition(|line| line.iter().any(|cell| !is_blank(cell, colorize)))
+            .map_or(0, |last| last + 1);
+
+        let mut result = String::new();
+        for line in &grid[..used_rows] {
+            let used_cols = line.iter()
+                .rposition(|cell| !is_blank(cell, colorize))
+                .map_or(0, |last| last + 1);
+
+            let mut current = SgrState::default();
+            for cell in &line[..used_cols] {
+                if colorize && cell.style != current {
+                    result.push_str(&cell.style.sequence());
+                    current = cell.style;
+                }
+                result.push_str(&cell.text);
+            }
+            if !current.is_default() {
+                result.push_str("\x1b[0m");
+            }
+            result.push('\n');
+        }
+
+        result
+    }
+
+    pub fn apply_all<'a>(&mut self, elements: impl IntoIterator<Item = &'a AnsiElement>) {
+        for element in elements {
+            self.apply(element);
+        }
+    }
+
+    /// Update the screen with one parsed element
+    pub fn apply(&mut self, element: &AnsiElement) {
+        match element {
+            AnsiElement::Text(text) => {
+                for c in text.chars() {
+                    self.print(c);
+                }
+            },
+            AnsiElement::InvalidUtf8(_) => self.print(char::REPLACEMENT_CHARACTER),
+            AnsiElement::Ctrl(ctrl, _) => self.control(ctrl),
+            AnsiElement::Csi(csi, _) => self.csi(csi),
+            AnsiElement::Osc(osc, _, _) => match osc {
+                OSC::SetIconNameAndTitle(title) | OSC::SetWindowTitle(title) => self.title = title.clone(),
+                _ => {},
+            },
+            AnsiElement::ControlString(..) => {},
+        }
+    }
+
+    fn grid(&self) -> &Vec<Vec<Cell>> {
+        if self.alternate_active { &self.alternate } else { &self.primary }
+    }
+
+    fn grid_mut(&mut self) -> &mut Vec<Vec<Cell>> {
+        if self.alternate_active { &mut self.alternate } else { &mut self.primary }
+    }
+
+    // Blank cell carrying the current background, as erased cells do in xterm
+    fn blank(&self) -> Cell {
+        Cell::blank(SgrState { background: self.style.background, ..SgrState::default() })
+    }
+
+    fn print(&mut self, c: char) {
+        let c = if self.special_chars { special_graphics(c) } else { c };
+        let width = match c.width() {
+            Some(width) => width,
+            None => return,
+        };
+
+        // Combining marks join the previously written cell
+        if width == 0 {
+            let row = self.row;
+            let mut col = if self.wrap_pending { self.col } else { self.col.saturating_sub(1) };
+            while col > 0 && self.grid()[row][col].is_continuation() {
+                col -= 1;
+            }
+            self.grid_mut()[row][col].text.push(c);
+            return;
+        }
+
+        if self.wrap_pending && self.auto_wrap {
+            self.col = 0;
+            self.linefeed();
+        }
+        self.wrap_pending = false;
+
+        // A wide character that doesn't fit moves to the next line
+        if width == 2 && self.col + 1 >= self.columns {
+            if self.auto_wrap && self.columns > 1 {
+                let blank = self.blank();
+                let (row, col) = (self.row, self.col);
+                self.grid_mut()[row][col] = blank;
+                self.col = 0;
+                self.linefeed();
+            } else {
+                return;
+            }
+        }
+
+        if self.insert_mode {
+            self.insert_blank(width);
+        }
+
+        let (row, col) = (self.row, self.col);
+        self.clear_wide_at(row, col);
+        if width == 2 {
+            self.clear_wide_at(row, col + 1);
+        }
+
+        let style = self.style;
+        let line = &mut self.grid_mut()[row];
+        line[col] = Cell { text: c.to_string(), style };
+        if width == 2 {
+            line[col + 1] = Cell { text: String::new(), style };
+        }
+        self.last_char = Some(c);
+
+        self.col += width;
+        if self.col >= self.columns {
+            self.col = self.columns - 1;
+            self.wrap_pending = self.auto_wrap;
+        }
+    }
+
+    // Blank out the other half of a wide character that is about to be overwritten
+    fn clear_wide_at(&mut self, row: usize, col: usize) {
+        let blank = self.blank();
+        let columns = self.columns;
+        let line = &mut self.grid_mut()[row];
+        if line[col].is_continuation() && col > 0 {
+            line[col - 1] = blank.clone();
+        }
+        if line[col].is_wide() && col + 1 < columns {
+            line[col + 1] = blank;
+        }
+    }
+
+    fn control(&mut self, ctrl: &ControlCharacter) {
+        match ctrl {
+            ControlCharacter::Backspace => {
+                self.col = self.col.saturating_sub(1);
+                self.wrap_pending = false;
+            },
+            ControlCharacter::Tab => self.tab_forward(1),
+            ControlCharacter::LineFeed | ControlCharacter::VerticalTab | ControlCharacter::FormFeed => {
+                if self.newline_mode {
+                    self.col = 0;
+                }
+                self.linefeed();
+            },
+            ControlCharacter::CarriageReturn => {
+                self.col = 0;
+                self.wrap_pending = false;
+            },
+            _ => {},
+        }
+    }
+
+    fn csi(&mut self, csi: &CSI) {
+        if self.style.apply_csi(csi) {
+            return;
+        }
+
+        match csi {
+            CSI::CursorUp(n) => {
+                let top = if self.row >= self.scroll_top { self.scroll_top } else { 0 };
+                self.row = self.row.saturating_sub(*n as usize).max(top);
+                self.wrap_pending = false;
+            },
+            CSI::CursorDown(n) => {
+                let bottom = if self.row <= self.scroll_bottom { self.scroll_bottom } else { self.rows - 1 };
+                self.row = (self.row + *n as usize).min(bottom);
+                self.wrap_pending = false;
+            },
+            CSI::CursorForward(n) => {
+                self.col = (self.col + *n as usize).min(self.columns - 1);
+                self.wrap_pending = false;
+            },
+            CSI::CursorBackward(n) => {
+                self.col = self.col.saturating_sub(*n as usize);
+                self.wrap_pending = false;
+            },
+            CSI::CursorPosition(row, col) => self.move_to(*row as usize, *col as usize),
+            CSI::CursorToRow(row) => self.move_to(*row as usize, self.col + 1),
+            CSI::CursorToColumn(col) => {
+                self.col = (*col as usize).clamp(1, self.columns) - 1;
+                self.wrap_pending = false;
+            },
+            CSI::CursorNextLine(n) => {
+                self.csi(&CSI::CursorDown(*n));
+                self.col = 0;
+            },
+            CSI::CursorPreviousLine(n) => {
+                self.csi(&CSI::CursorUp(*n));
+                self.col = 0;
+            },
+            CSI::CursorForwardTab(n) => self.tab_forward(*n as usize),
+            CSI::CursorBackwardTab(n) => self.tab_backward(*n as usize),
+            CSI::CursorSavePosition => self.save_cursor(),
+            CSI::CursorRestorePosition => self.restore_cursor(),
+
+            CSI::EraseInDisplay(n) => self.erase_in_display(*n),
+            CSI::EraseInLine(n) => self.erase_in_line(*n),
+            CSI::EraseCharacters(n) => {
+                let end = (self.col + *n as usize).min(self.columns);
+                self.erase_cells(self.row, self.col, end);
+            },
+            CSI::InsertCharacters(n) => self.insert_blank(*n as usize),
+            CSI::DeleteCharacters(n) => self.delete_chars(*n as usize),
+            CSI::InsertLines(n) if self.in_scroll_region() => {
+                self.scroll_down_region(self.row, self.scroll_bottom, *n as usize);
+                self.col = 0;
+            },
+            CSI::DeleteLines(n) if self.in_scroll_region() => {
+                self.scroll_up_region(self.row, self.scroll_bottom, *n as usize);
+                self.col = 0;
+            },
+            CSI::RepeatCharacter(n) => {
+                if let Some(c) = self.last_char {
+                    for _ in 0..*n {
+                        self.print(c);
+                    }
+                }
+            },
+
+            CSI::TabSet => self.tab_stops[self.col] = true,
+            CSI::TabClear(0) => self.tab_stops[self.col] = false,
+            CSI::TabClear(3) => self.tab_stops.iter_mut().for_each(|stop| *stop = false),
+
+            CSI::ScrollUp(n) => self.scroll_up_region(self.scroll_top, self.scroll_bottom, *n as usize),
+            CSI::ScrollDown(n) => self.scroll_down_region(self.scroll_top, self.scroll_bottom, *n as usize),
+            CSI::SetScrollRegion(top, bottom) => {
+                let top = (*top as usize).max(1) - 1;
+                let bottom = bottom.map_or(self.rows, |b| (b as usize).min(self.rows)) - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(1, 1);
+                }
+            },
+            CSI::Index => self.linefeed(),
+            CSI::NextLine => {
+                self.col = 0;
+                self.linefeed();
+            },
+            CSI::ReverseIndex => self.reverse_index(),
+
+            CSI::SetMode(modes) => self.set_modes(modes, false, true),
+            CSI::ResetMode(modes) => self.set_modes(modes, false, false),
+            CSI::SetPrivateMode(modes) => self.set_modes(modes, true, true),
+            CSI::ResetPrivateMode(modes) => self.set_modes(modes, true, false),
+
+            CSI::SetG0SpecialChars => self.special_chars = true,
+            CSI::SetG0NormalChars => self.special_chars = false,
+
+            CSI::SoftReset => {
+                self.style = SgrState::default();
+                self.insert_mode = false;
+                self.origin_mode = false;
+                self.auto_wrap = false;
+                self.cursor_visible = true;
+                self.special_chars = false;
+                self.scroll_top = 0;
+                self.scroll_bottom = self.rows - 1;
+                self.saved_cursor = SavedCursor::default();
+            },
+            CSI::FullReset => *self = Screen::new(self.columns, self.rows),
+
+            _ => {},
+        }
+    }
+
+    fn set_modes(&mut self, modes: &[u16], private: bool, enable: bool) {
+        for &mode in modes {
+            match (private, mode) {
+                (false, 4) => self.insert_mode = enable,
+                (false, 20) => self.newline_mode = enable,
+                (true, 6) => {
+                    self.origin_mode = enable;
+                    self.move_to(1, 1);
+                },
+                (true, 7) => self.auto_wrap = enable,
+                (true, 25) => self.cursor_visible = enable,
+                (true, 47) => self.switch_screen(enable, false),
+                (true, 1047) => {
+                    // The alternate screen is cleared when leaving it
+                    if !enable && self.alternate_active {
+                        self.alternate = blank_grid(self.columns, self.rows);
+                    }
+                    self.switch_screen(enable, false);
+                },
+                (true, 1048) => {
+                    if enable { self.save_cursor() } else { self.restore_cursor() }
+                },
+                (true, 1049) => {
+                    if enable {
+                        self.save_cursor();
+                        self.switch_screen(true, true);
+                    } else {
+                        self.switch_screen(false, false);
+                        self.restore_cursor();
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn switch_screen(&mut self, alternate: bool, clear: bool) {
+        if alternate && clear {
+            self.alternate = blank_grid(self.columns, self.rows);
+        }
+        self.alternate_active = alternate;
+        self.wrap_pending = false;
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = SavedCursor {
+            row: self.row,
+            col: self.col,
+            style: self.style,
+            origin_mode: self.origin_mode,
+            special_chars: self.special_chars,
+        };
+    }
+
+    fn restore_cursor(&mut self) {
+        let saved = self.saved_cursor;
+        self.row = saved.row.min(self.rows - 1);
+        self.col = saved.col.min(self.columns - 1);
+        self.style = saved.style;
+        self.origin_mode = saved.origin_mode;
+        self.special_chars = saved.special_chars;
+        self.wrap_pending = false;
+    }
+
+    // Move to a one-based position, relative to the scroll region in origin mode
+    fn move_to(&mut self, row: usize, col: usize) {
+        let (top, bottom) = if self.origin_mode {
+            (self.scroll_top, self.scroll_bottom)
+        } else {
+            (0, self.rows - 1)
+        };
+        self.row = (top + row.max(1) - 1).min(bottom);
+        self.col = (col.max(1) - 1).min(self.columns - 1);
+        self.wrap_pending = false;
+    }
+
+    fn in_scroll_region(&self) -> bool {
+        self.row >= self.scroll_top && self.row <= self.scroll_bottom
+    }
+
+    fn linefeed(&mut self) {
+        if self.row == self.scroll_bottom {
+            self.scroll_up_region(self.scroll_top, self.scroll_bottom, 1);
+        } else if self.row < self.rows - 1 {
+            self.row += 1;
+        }
+        self.wrap_pending = false;
+    }
+
+    fn reverse_index(&mut self) {
+        if self.row == self.scroll_top {
+            self.scroll_down_region(self.scroll_top, self.scroll_bottom, 1);
+        } else if self.row > 0 {
+            self.row -= 1;
+        }
+        self.wrap_pending = false;
+    }
+
+    // Move lines top..=bottom up by n, filling the bottom with blank lines
+    fn scroll_up_region(&mut self, top: usize, bottom: usize, n: usize) {
+        let n = n.min(bottom + 1 - top);
+        let blank = vec![self.blank(); self.columns];
+        let grid = self.grid_mut();
+        grid[top..=bottom].rotate_left(n);
+        for line in &mut grid[bottom + 1 - n..=bottom] {
+            line.clone_from(&blank);
+        }
+    }
+
+    // Move lines top..=bottom down by n, filling the top with blank lines
+    fn scroll_down_region(&mut self, top: usize, bottom: usize, n: usize) {
+        let n = n.min(bottom + 1 - top);
+        let blank = vec![self.blank(); self.columns];
+        let grid = self.grid_mut();
+        grid[top..=bottom].rotate_right(n);
+        for line in &mut grid[top..top + n] {
+            line.clone_from(&blank);
+        }
+    }
+
+    fn insert_blank(&mut self, n: usize) {
+        let (row, col) = (self.row, self.col);
+        let n = n.min(self.columns - col);
+        let blank = self.blank();
+        let line = &mut self.grid_mut()[row];
+        line[col..].rotate_right(n);
+        for cell in &mut line[col..col + n] {
+            *cell = blank.clone();
+        }
+        self.wrap_pending = false;
+    }
+
+    fn delete_chars(&mut self, n: usize) {
+        let (row, col) = (self.row, self.col);
+        let n = n.min(self.columns - col);
+        let blank = self.blank();
+        let line = &mut self.grid_mut()[row];
+        line[col..].rotate_left(n);
+        let len = line.len();
+        for cell in &mut line[len - n..] {
+            *cell = blank.clone();
+        }
+        self.wrap_pending = false;
+    }
+
+    fn erase_cells(&mut self, row: usize, start: usize, end: usize) {
+        let blank = self.blank();
+        for cell in &mut self.grid_mut()[row][start..end] {
+            *cell = blank.clone();
+        }
+    }
+
+    fn erase_in_line(&mut self, mode: u8) {
+        let (row, col) = (self.row, self.col);
+        match mode {
+            0 => self.erase_cells(row, col, self.columns),
+            1 => self.erase_cells(row, 0, col + 1),
+            2 => self.erase_cells(row, 0, self.columns),
+            _ => {},
+        }
+    }
+
+    fn erase_in_display(&mut self, mode: u8) {
+        let (row, col) = (self.row, self.col);
+        match mode {
+            0 => {
+                self.erase_cells(row, col, self.columns);
+                for r in row + 1..self.rows {
+                    self.erase_cells(r, 0, self.columns);
+                }
+            },
+            1 => {
+                for r in 0..row {
+                    self.erase_cells(r, 0, self.columns);
+                }
+                self.erase_cells(row, 0, col + 1);
+            },
+            2 => {
+                for r in 0..self.rows {
+                    self.erase_cells(r, 0, self.columns);
+                }
+            },
+            // 3 clears only the scrollback, which isn't kept
+            _ => {},
+        }
+    }
+
+    fn tab_forward(&mut self, n: usize) {
+        for _ in 0..n {
+            self.col = (self.col + 1..self.columns)
+                .find(|&col| self.tab_stops[col])
+                .unwrap_or(self.columns - 1);
+        }
+        self.wrap_pending = false;
+    }
+
+    fn tab_backward(&mut self, n: usize) {
+        for _ in 0..n {
+            self.col = (0..self.col).rev()
+                .find(|&col| self.tab_stops[col])
+                .unwrap_or(0);
+        }
+        self.wrap_pending = false;
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new(DEFAULT_COLUMNS, DEFAULT_ROWS)
+    }
+}
+
+fn blank_grid(columns: usize, rows: usize) -> Vec<Vec<Cell>> {
+    vec![vec![Cell::default(); columns]; rows]
+}
+
+fn default_tab_stops(columns: usize) -> Vec<bool> {
+    (0..columns).map(|col| col > 0 && col % TAB_WIDTH == 0).collect()
+}
+
+// Spaces only count as content when their styling is visible
+fn is_blank(cell: &Cell, colorize: bool) -> bool {
+    cell.text == " " && (!colorize || cell.style.is_default())
+}
+
+// Map ASCII to the DEC Special Graphics set used for line drawing
+fn special_graphics(c: char) -> char {
+    match c {
+        '_' => ' ',
+        '`' => '◆',
+        'a' => '▒',
+        'b' => '␉',
+        'c' => '␌',
+        'd' => '␍',
+        'e' => '␊',
+        'f' => '°',
+        'g' => '±',
+        'h' => '␤',
+        'i' => '␋',
+        'j' => '┘',
+        'k' => '┐',
+        'l' => '┌',
+        'm' => '└',
+        'n' => '┼',
+        'o' => '⎺',
+        'p' => '⎻',
+        'q' => '─',
+        'r' => '⎼',
+        's' => '⎽',
+        't' => '├',
+        'u' => '┤',
+        'v' => '┴',
+        'w' => '┬',
+        'x' => '│',
+        'y' => '≤',
+        'z' => '≥',
+        '{' => 'π',
+        '|' => '≠',
+        '}' => '£',
+        '~' => '·',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::AnsiParser;
+
+    fn screen(input: &[u8]) -> Screen {
+        let mut screen = Screen::new(10, 4);
+        screen.apply_all(&AnsiParser::parse(input).unwrap());
+        screen
+    }
+
+    fn text(input: &[u8]) -> Vec<String> {
+        screen(input).text()
+    }
+
+    #[test]
+    fn text_wraps_at_the_last_column() {
+        assert_eq!(text(b"0123456789ab"), ["0123456789", "ab", "", ""]);
+        // The cursor waits on the last column until the next character
+        let screen = screen(b"0123456789");
+        assert_eq!(screen.cursor(), (0, 9));
+        assert!(screen.wrap_pending());
+    }
+
+    #[test]
+    fn cursor_movement() {
+        assert_eq!(text(b"\x1b[2;3Hx\x1b[Ay\x1b[3Gz"), ["  zy", "  x", "", ""]);
+        assert_eq!(screen(b"\x1b[99;99H").cursor(), (3, 9));
+        assert_eq!(text(b"ab\rc\x08d"), ["db", "", "", ""]);
+    }
+
+    #[test]
+    fn erase_in_line() {
+        assert_eq!(text(b"abcdef\x1b[4G\x1b[K"), ["abc", "", "", ""]);
+        assert_eq!(text(b"abcdef\x1b[4G\x1b[1K"), ["    ef", "", "", ""]);
+        assert_eq!(text(b"abcdef\x1b[4G\x1b[2K"), ["", "", "", ""]);
+    }
+
+    #[test]
+    fn erase_in_display() {
+        let full = b"aaaa\r\nbbbb\r\ncccc\x1b[2;3H";
+        let erased = |mode: &[u8]| text(&[&full[..], mode].concat());
+        assert_eq!(erased(b"\x1b[J"), ["aaaa", "bb", "", ""]);
+        assert_eq!(erased(b"\x1b[1J"), ["", "   b", "cccc", ""]);
+        assert_eq!(erased(b"\x1b[2J"), ["", "", "", ""]);
+        // 3 only clears the scrollback, leaving the screen as it is
+        assert_eq!(erased(b"\x1b[3J"), ["aaaa", "bbbb", "cccc", ""]);
+        assert_eq!(text(b"old\x1b[H\x1b[2J\x1b[3Jnew"), ["new", "", "", ""]);
+    }
+
+    #[test]
+    fn styles_are_kept_per_cell() {
+        let screen = screen(b"a\x1b[1;31mb\x1b[0mc");
+        assert!(screen.cell(0, 0).unwrap().style.is_default());
+        assert!(screen.cell(0, 1).unwrap().style.bold);
+        assert!(screen.cell(0, 2).unwrap().style.is_default());
+    }
+
+    #[test]
+    fn saved_cursor_restores_position_and_style() {
+        let screen = screen(b"\x1b[2;2H\x1b[1m\x1b7\x1b[0m\x1b[4;4H\x1b8");
+        assert_eq!(screen.cursor(), (1, 1));
+        assert!(screen.style().bold);
+    }
+
+    #[test]
+    fn alternate_screen_keeps_the_primary() {
+        let on = screen(b"main\x1b[?1049halt");
+        assert!(on.is_alternate_screen());
+        assert_eq!(on.text()[0], "    alt");
+        let off = screen(b"main\x1b[?1049halt\x1b[?1049l");
+        assert!(!off.is_alternate_screen());
+        assert_eq!(off.text()[0], "main");
+        assert_eq!(off.cursor(), (0, 4));
+    }
+
+    #[test]
+    fn lines_scroll_off_the_top() {
+        assert_eq!(text(b"1\r\n2\r\n3\r\n4\r\n5"), ["2", "3", "4", "5"]);
+    }
+
+    #[test]
+    fn title_is_kept() {
+        assert_eq!(screen(b"\x1b]2;hello\x07").title(), "hello");
+    }
+}
@@ -0,0 +1,234 @@
+use crate::ansi::{AnsiElement, Spanned, csi::CSI, osc::OSC};
+use crate::ansi::sgr::SgrState;
+use crate::parser::ParserError;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{self, Write};
+
+/// Number of sequences listed in the report
+pub const MOST_FREQUENT: usize = 10;
+
+/// DEC private modes that make the terminal report mouse events
+pub const MOUSE_MODES: [u16; 9] = [9, 1000, 1001, 1002, 1003, 1005, 1006, 1015, 1016];
+
+/// DEC private modes that switch to the alternate screen
+pub const ALTERNATE_SCREEN_MODES: [u16; 3] = [47, 1047, 1049];
+
+/// How often one sequence occurs
+#[derive(Debug, Clone)]
+pub struct Frequency {
+    pub escape: String,
+    pub description: String,
+    pub count: usize,
+}
+
+/// Overview of a whole stream
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub elements: usize,
+    pub kinds: BTreeMap<&'static str, usize>,  // Element count per type (Text, CSI, OSC, ...)
+    pub text_bytes: usize,                      // Printable text, including invalid UTF-8
+    pub control_bytes: usize,                   // Control characters and escape sequences
+    pub max_row: Option<u32>,                   // Largest row addressed by CUP or VPA
+    pub max_column: Option<u32>,                // Largest column addressed by CUP or CHA
+    pub alternate_screen: bool,                 // Alternate screen still active at the end
+    pub mouse_modes: BTreeSet<u16>,             // Mouse reporting modes still enabled at the end
+    sequences: HashMap<String, Frequency>,
+    unknown: HashMap<String, Frequency>,
+    sgr_states: HashSet<String>,
+    style: SgrState,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn collect(elements: &[Spanned]) -> Self {
+        let mut stats = Self::new();
+        for spanned in elements {
+            stats.feed(spanned);
+        }
+        stats
+    }
+
+    pub fn feed(&mut self, spanned: &Spanned) {
+        let element = &spanned.element;
+        self.elements += 1;
+        *self.kinds.entry(element.element_type()).or_default() += 1;
+
+        match element {
+            AnsiElement::Text(_) | AnsiElement::InvalidUtf8(_) => {
+                self.text_bytes += spanned.raw.len();
+                self.sgr_states.insert(self.style.sequence());
+                return;
+            },
+            AnsiElement::Ctrl(..) => {
+                self.control_bytes += spanned.raw.len();
+                return;
+            },
+            _ => self.control_bytes += spanned.raw.len(),
+        }
+
+        count(&mut self.sequences, element);
+        let unknown = matches!(
+            element,
+            AnsiElement::Csi(CSI::Unknown(_) | CSI::Unrecognized(_), _) | AnsiElement::Osc(OSC::Unknown(_), _, _)
+        );
+        if unknown {
+            count(&mut self.unknown, element);
+        }
+
+        if let AnsiElement::Csi(csi, _) = element {
+            self.style.apply_csi(csi);
+            self.csi(csi);
+        }
+    }
+
+    fn csi(&mut self, csi: &CSI) {
+        match csi {
+            CSI::CursorPosition(row, col) => {
+                self.max_row = self.max_row.max(Some(*row));
+                self.max_column = self.max_column.max(Some(*col));
+            },
+            CSI::CursorToRow(row) => self.max_row = self.max_row.max(Some(*row)),
+            CSI::CursorToColumn(col) => self.max_column = self.max_column.max(Some(*col)),
+            CSI::SetPrivateMode(modes) => self.private_modes(modes, true),
+            CSI::ResetPrivateMode(modes) => self.private_modes(modes, false),
+            CSI::FullReset => {
+                self.alternate_screen = false;
+                self.mouse_modes.clear();
+            },
+            _ => {},
+        }
+    }
+
+    fn private_modes(&mut self, modes: &[u16], enabled: bool) {
+        for &mode in modes {
+            if ALTERNATE_SCREEN_MODES.contains(&mode) {
+                self.alternate_screen = enabled;
+            } else if MOUSE_MODES.contains(&mode) {
+                if enabled {
+                    self.mouse_modes.insert(mode);
+                } else {
+                    self.mouse_modes.remove(&mode);
+                }
+            }
+        }
+    }
+
+    /// Number of different graphics states text was drawn in
+    pub fn distinct_sgr_states(&self) -> usize {
+        self.sgr_states.len()
+    }
+
+    /// Escape sequences by how often they occur, most frequent first
+    pub fn most_frequent(&self, limit: usize) -> Vec<&Frequency> {
+        ranked(&self.sequences, limit)
+    }
+
+    /// Unknown and unrecognized sequences, most frequent first
+    pub fn unknown(&self) -> Vec<&Frequency> {
+        ranked(&self.unknown, usize::MAX)
+    }
+
+    /// Write the overview shown by --stats
+    pub fn write_report(&self, output: &mut impl Write) -> io::Result<()> {
+        writeln!(output, "Elements: {}", self.elements)?;
+        for (kind, count) in &self.kinds {
+            writeln!(output, "  {:<8} {}", kind, count)?;
+        }
+        writeln!(output, "Bytes: {} text, {} control", self.text_bytes, self.control_bytes)?;
+        writeln!(output, "Distinct SGR states in text: {}", self.distinct_sgr_states())?;
+
+        let addressed = |max: Option<u32>| max.map_or("none".to_string(), |n| n.to_string());
+        writeln!(output, "Max cursor row addressed: {}, column: {}", addressed(self.max_row), addressed(self.max_column))?;
+
+        let frequent = self.most_frequent(MOST_FREQUENT);
+        if !frequent.is_empty() {
+            writeln!(output, "\nMost frequent sequences:")?;
+            for sequence in frequent {
+                writeln!(output, "  {:>6}  {:<24} {}", sequence.count, sequence.escape, sequence.description)?;
+            }
+        }
+
+        let unknown = self.unknown();
+        if !unknown.is_empty() {
+            writeln!(output, "\nUnknown sequences:")?;
+            for sequence in unknown {
+                writeln!(output, "  {:>6}  {}", sequence.count, sequence.escape)?;
+            }
+        }
+
+        let mut left_enabled = Vec::new();
+        if self.alternate_screen {
+            left_enabled.push("alternate screen".to_string());
+        }
+        if !self.mouse_modes.is_empty() {
+            let modes: Vec<String> = self.mouse_modes.iter().map(|m| m.to_string()).collect();
+            left_enabled.push(format!("mouse reporting ({})", modes.join(", ")));
+        }
+        if left_enabled.is_empty() {
+            writeln!(output, "\nNo alternate screen or mouse modes left enabled at EOF")
+        } else {
+            writeln!(output, "\nLeft enabled at EOF: {}", left_enabled.join(", "))
+        }
+    }
+}
+
+/// Collect statistics over a whole stream and write the report shown by --stats
+pub fn report(
+    elements: impl Iterator<Item = Result<Spanned, ParserError>>,
+    output: &mut impl Write,
+) -> Result<(), ParserError> {
+    let mut stats = Stats::new();
+    for element in elements {
+        stats.feed(&element?);
+    }
+    stats.write_report(output)?;
+    Ok(())
+}
+
+fn count(counts: &mut HashMap<String, Frequency>, element: &AnsiElement) {
+    let escape = element.escape_repr();
+    counts.entry(escape.clone())
+        .or_insert_with(|| Frequency { escape, description: element.description(), count: 0 })
+        .count += 1;
+}
+
+// Ties are broken by the escape so the order is stable between runs
+fn ranked(counts: &HashMap<String, Frequency>, limit: usize) -> Vec<&Frequency> {
+    let mut ranked: Vec<&Frequency> = counts.values().collect();
+    ranked.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.escape.cmp(&b.escape)));
+    ranked.truncate(limit);
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::AnsiParser;
+
+    fn report_of(input: &[u8]) -> String {
+        let mut output = Vec::new();
+        report(AnsiParser::spanned_elements(input), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn report_counts_and_modes() {
+        let report = report_of(b"\x1b[1mab\x1b[0mc\x1b[5;7H\x1b[?1049h\x1b[?1000h");
+        assert!(report.starts_with("Elements: 7\n  CSI      5\n  Text     2\n"), "{}", report);
+        assert!(report.contains("Bytes: 3 text, 30 control\n"), "{}", report);
+        assert!(report.contains("Distinct SGR states in text: 2\n"), "{}", report);
+        assert!(report.contains("Max cursor row addressed: 5, column: 7\n"), "{}", report);
+        assert!(report.ends_with("Left enabled at EOF: alternate screen, mouse reporting (1000)\n"), "{}", report);
+    }
+
+    #[test]
+    fn report_without_sequences() {
+        let report = report_of(b"plain");
+        assert_eq!(report, "Elements: 1\n  Text     1\nBytes: 5 text, 0 control\n\
+            Distinct SGR states in text: 1\nMax cursor row addressed: none, column: none\n\
+            \nNo alternate screen or mouse modes left enabled at EOF\n");
+    }
+}